@@ -0,0 +1,370 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+    time::SystemTime,
+};
+
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::locale::{Locale, Localized};
+use crate::ports::LookupCache;
+use crate::template::{RequestMetadata, Template, TemplateContext, TemplateError};
+
+const DEFAULT_LOCALE: &str = "en";
+const DEFAULT_UNKNOWN_MESSAGE: &str = "Unknown message \"{{ message }}\"";
+
+#[derive(thiserror::Error, Debug)]
+pub enum CatalogError {
+    #[error("Invalid pattern \"{pattern}\": {reason}")]
+    InvalidPattern { pattern: String, reason: String },
+    #[error("Invalid reply template for pattern \"{pattern}\": {source}")]
+    InvalidTemplate {
+        pattern: String,
+        source: TemplateError,
+    },
+    #[error("Invalid \"{name}\" error message for locale \"{locale}\": {source}")]
+    InvalidErrorMessage {
+        name: &'static str,
+        locale: String,
+        source: TemplateError,
+    },
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    #[default]
+    Exact,
+    CaseInsensitive,
+    Regex,
+    Glob,
+}
+
+/// A single catalog rule. Entries with a higher `priority` are tried first; entries with the
+/// same priority keep the order in which they were declared. `reply` is a [`Template`] in the
+/// catalog's default locale and `translations` holds the same reply for other locales.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct CatalogEntry {
+    pub pattern: String,
+    #[serde(default, rename = "match")]
+    pub kind: MatchKind,
+    pub reply: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub translations: BTreeMap<String, String>,
+    #[serde(default)]
+    pub priority: i32,
+}
+
+impl CatalogEntry {
+    pub fn exact(pattern: &str, reply: &str) -> Self {
+        CatalogEntry {
+            pattern: pattern.to_string(),
+            kind: MatchKind::Exact,
+            reply: reply.to_string(),
+            translations: BTreeMap::new(),
+            priority: 0,
+        }
+    }
+}
+
+/// Localized error messages, keyed by locale. Each message is a [`Template`].
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorMessages {
+    #[serde(default)]
+    pub unknown_message: BTreeMap<String, String>,
+}
+
+/// Contents of a catalog file.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct CatalogDefinition {
+    #[serde(default = "default_locale")]
+    pub default_locale: String,
+    pub entries: Vec<CatalogEntry>,
+    #[serde(default)]
+    pub errors: ErrorMessages,
+}
+
+fn default_locale() -> String {
+    DEFAULT_LOCALE.to_string()
+}
+
+enum Matcher {
+    Exact(String),
+    CaseInsensitive(String),
+    Regex(Regex),
+    Glob(GlobMatcher),
+}
+
+impl Matcher {
+    fn compile(entry: &CatalogEntry) -> Result<Self, CatalogError> {
+        let invalid = |reason: String| CatalogError::InvalidPattern {
+            pattern: entry.pattern.clone(),
+            reason,
+        };
+
+        Ok(match entry.kind {
+            MatchKind::Exact => Matcher::Exact(entry.pattern.clone()),
+            MatchKind::CaseInsensitive => Matcher::CaseInsensitive(entry.pattern.to_lowercase()),
+            MatchKind::Regex => {
+                Matcher::Regex(Regex::new(&entry.pattern).map_err(|err| invalid(err.to_string()))?)
+            }
+            MatchKind::Glob => Matcher::Glob(
+                Glob::new(&entry.pattern)
+                    .map_err(|err| invalid(err.to_string()))?
+                    .compile_matcher(),
+            ),
+        })
+    }
+
+    fn is_match(&self, message: &str) -> bool {
+        match self {
+            Matcher::Exact(pattern) => pattern == message,
+            Matcher::CaseInsensitive(pattern) => *pattern == message.to_lowercase(),
+            Matcher::Regex(regex) => regex.is_match(message),
+            Matcher::Glob(glob) => glob.is_match(message),
+        }
+    }
+
+    fn regex(&self) -> Option<&Regex> {
+        match self {
+            Matcher::Regex(regex) => Some(regex),
+            _ => None,
+        }
+    }
+}
+
+/// Templates of one text in several locales, keyed by lowercase language tag. The original
+/// spelling of the tag is kept to report which locale was served.
+struct Translations(BTreeMap<String, (String, Template)>);
+
+impl Translations {
+    fn compile<'a, E>(
+        texts: impl IntoIterator<Item = (&'a String, &'a String)>,
+        pattern: Option<&Regex>,
+        error: impl Fn(&str, TemplateError) -> E,
+    ) -> Result<Self, E> {
+        texts
+            .into_iter()
+            .map(|(locale, text)| {
+                Template::compile(text, pattern)
+                    .map(|template| (locale.to_lowercase(), (locale.clone(), template)))
+                    .map_err(|err| error(locale, err))
+            })
+            .collect::<Result<_, _>>()
+            .map(Translations)
+    }
+
+    /// First template along the fallback chain, with the locale it is written in.
+    fn negotiate(&self, chain: &[String]) -> Option<(&str, &Template)> {
+        chain
+            .iter()
+            .find_map(|tag| self.0.get(tag))
+            .map(|(locale, template)| (locale.as_str(), template))
+    }
+}
+
+struct CompiledEntry {
+    entry: CatalogEntry,
+    matcher: Matcher,
+    replies: Translations,
+}
+
+impl CompiledEntry {
+    fn compile(entry: CatalogEntry, default_locale: &String) -> Result<Self, CatalogError> {
+        let matcher = Matcher::compile(&entry)?;
+        let replies = Translations::compile(
+            std::iter::once((default_locale, &entry.reply)).chain(&entry.translations),
+            matcher.regex(),
+            |_, source| CatalogError::InvalidTemplate {
+                pattern: entry.pattern.clone(),
+                source,
+            },
+        )?;
+
+        Ok(CompiledEntry {
+            entry,
+            matcher,
+            replies,
+        })
+    }
+}
+
+/// Ordered set of rules mapping incoming messages to replies. Patterns and reply templates are
+/// compiled once when the catalog is built, so an invalid rule is reported before the catalog
+/// is used.
+pub struct ReplyCatalog {
+    default_locale: String,
+    entries: Vec<CompiledEntry>,
+    unknown_message: Translations,
+    /// Lowercase tags of every locale some text is written in.
+    locales: BTreeSet<String>,
+    lookups: OnceLock<Arc<dyn LookupCache>>,
+}
+
+impl ReplyCatalog {
+    pub fn new(definition: CatalogDefinition) -> Result<Self, CatalogError> {
+        let default_locale = definition.default_locale;
+
+        let mut entries = definition
+            .entries
+            .into_iter()
+            .map(|entry| CompiledEntry::compile(entry, &default_locale))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        entries.sort_by_key(|compiled| std::cmp::Reverse(compiled.entry.priority));
+
+        let default_unknown_message = DEFAULT_UNKNOWN_MESSAGE.to_string();
+        let mut unknown_message = definition.errors.unknown_message;
+        unknown_message
+            .entry(default_locale.clone())
+            .or_insert(default_unknown_message);
+
+        let unknown_message = Translations::compile(&unknown_message, None, |locale, source| {
+            CatalogError::InvalidErrorMessage {
+                name: "unknown_message",
+                locale: locale.to_string(),
+                source,
+            }
+        })?;
+
+        let locales = entries
+            .iter()
+            .flat_map(|compiled| compiled.replies.0.keys())
+            .chain(unknown_message.0.keys())
+            .cloned()
+            .collect();
+
+        Ok(ReplyCatalog {
+            default_locale,
+            entries,
+            unknown_message,
+            locales,
+            lookups: OnceLock::new(),
+        })
+    }
+
+    pub fn default_locale(&self) -> &str {
+        &self.default_locale
+    }
+
+    /// Whether some text of the catalog is written in the `tag` locale.
+    pub fn has_locale(&self, tag: &str) -> bool {
+        self.locales.contains(&tag.to_lowercase())
+    }
+
+    /// Entries in the order they are tried.
+    pub fn entries(&self) -> impl Iterator<Item = &CatalogEntry> {
+        self.entries.iter().map(|compiled| &compiled.entry)
+    }
+
+    /// Remembers the entries messages match in `cache` from now on. Only the first cache set is
+    /// used.
+    pub fn cache_lookups(&self, cache: Arc<dyn LookupCache>) {
+        let _ = self.lookups.set(cache);
+    }
+
+    pub fn find(&self, message: &str) -> Option<&CatalogEntry> {
+        self.find_compiled(message).map(|compiled| &compiled.entry)
+    }
+
+    /// Renders the reply of the first entry matching `message` in the best available locale.
+    pub fn reply(
+        &self,
+        message: &str,
+        locale: &Locale,
+        metadata: &RequestMetadata,
+    ) -> Option<Localized> {
+        let compiled = self.find_compiled(message)?;
+        let captures = compiled
+            .matcher
+            .regex()
+            .and_then(|regex| regex.captures(message));
+
+        self.render(
+            &compiled.replies,
+            message,
+            captures.as_ref(),
+            locale,
+            metadata,
+        )
+    }
+
+    /// Renders the error reported for a message no entry matches.
+    pub fn unknown_message(
+        &self,
+        message: &str,
+        locale: &Locale,
+        metadata: &RequestMetadata,
+    ) -> Localized {
+        self.render(&self.unknown_message, message, None, locale, metadata)
+            .expect("the default locale always has an unknown message")
+    }
+
+    fn render(
+        &self,
+        translations: &Translations,
+        message: &str,
+        captures: Option<&regex::Captures>,
+        locale: &Locale,
+        metadata: &RequestMetadata,
+    ) -> Option<Localized> {
+        let chain = locale.fallback_chain(&self.default_locale);
+        let (served, template) = translations.negotiate(&chain)?;
+
+        Some(Localized {
+            text: template.render(&TemplateContext {
+                message,
+                captures,
+                locale: served,
+                metadata,
+                now: SystemTime::now(),
+            }),
+            locale: served.to_string(),
+            requested: locale.requested().map(str::to_string),
+        })
+    }
+
+    fn find_compiled(&self, message: &str) -> Option<&CompiledEntry> {
+        let matching = || {
+            self.entries
+                .iter()
+                .position(|compiled| compiled.matcher.is_match(message))
+        };
+
+        let index = match self.lookups.get() {
+            Some(cache) => cache.get(message).unwrap_or_else(|| {
+                let index = matching();
+                cache.insert(message, index);
+                index
+            }),
+            None => matching(),
+        };
+        index.map(|index| &self.entries[index])
+    }
+}
+
+impl Default for ReplyCatalog {
+    fn default() -> Self {
+        ReplyCatalog::new(CatalogDefinition {
+            default_locale: default_locale(),
+            entries: vec![
+                CatalogEntry::exact("hello", "world"),
+                CatalogEntry::exact("ping", "pong"),
+            ],
+            errors: ErrorMessages::default(),
+        })
+        .expect("default catalog is valid")
+    }
+}
+
+/// A catalog together with where and when it was loaded from. `version` identifies its
+/// contents, e.g. the hash of the file it was parsed from.
+pub struct CatalogSnapshot {
+    pub catalog: ReplyCatalog,
+    pub source: Option<PathBuf>,
+    pub version: String,
+    pub loaded_at: SystemTime,
+}
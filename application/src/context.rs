@@ -0,0 +1,23 @@
+use std::sync::Arc;
+
+use crate::ports::{
+    CatalogSource, Clock, EventPublisher, HealthCheck, IdGenerator, IdempotencyStore,
+    MessageRepository, Outbox,
+};
+
+/// The ports use cases run against. Gateways receive one assembled from `infrastructure`
+/// adapters; tests assemble one from fakes.
+#[derive(Clone)]
+pub struct AppContext {
+    pub messages: Arc<dyn MessageRepository>,
+    /// Where the events [`AppContext::outbox`] holds are published.
+    pub events: Arc<dyn EventPublisher>,
+    pub outbox: Arc<dyn Outbox>,
+    pub clock: Arc<dyn Clock>,
+    pub ids: Arc<dyn IdGenerator>,
+    pub idempotency: Arc<dyn IdempotencyStore>,
+    /// The catalog replies are rendered from.
+    pub catalog: Arc<dyn CatalogSource>,
+    /// Checks that must pass for the service to be ready.
+    pub readiness: Vec<Arc<dyn HealthCheck>>,
+}
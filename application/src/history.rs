@@ -0,0 +1,48 @@
+//! Queries over the exchanges stored by [`crate::messages::record`].
+
+use std::time::Duration;
+
+use crate::context::AppContext;
+use crate::ports::{HistoryCursor, HistoryFilter, MessageRecord, PortError};
+
+/// A page of the history and the cursor of the next one, if there are older records.
+#[derive(Debug)]
+pub struct HistoryPage {
+    pub records: Vec<MessageRecord>,
+    pub next: Option<HistoryCursor>,
+}
+
+#[tracing::instrument(name = "application.history.list", skip_all)]
+pub async fn list(ctx: &AppContext, filter: HistoryFilter) -> Result<HistoryPage, PortError> {
+    let limit = filter.limit;
+
+    // one extra record tells whether another page follows
+    let mut records = ctx
+        .messages
+        .list(&HistoryFilter {
+            limit: limit + 1,
+            ..filter
+        })
+        .await?;
+
+    let next = if records.len() > limit {
+        records.truncate(limit);
+        records.last().map(|record| HistoryCursor {
+            created_at: record.created_at,
+            id: record.id.clone(),
+        })
+    } else {
+        None
+    };
+
+    Ok(HistoryPage { records, next })
+}
+
+/// Deletes the records older than `retention`, returning how many were deleted.
+#[tracing::instrument(name = "application.history.purge", skip(ctx))]
+pub async fn purge(ctx: &AppContext, retention: Duration) -> Result<u64, PortError> {
+    match ctx.clock.now().checked_sub(retention) {
+        Some(cutoff) => ctx.messages.delete_before(cutoff).await,
+        None => Ok(0),
+    }
+}
@@ -0,0 +1,47 @@
+//! Replaying the response of a request to its retries, so that a request sent twice under the
+//! same [`IdempotencyKey`] only takes effect once.
+
+use std::time::Duration;
+
+use crate::context::AppContext;
+use crate::ports::{IdempotencyClaim, IdempotencyKey, PortError, StoredResponse};
+
+/// Claims `key` while its request runs, for at most `in_flight`, see
+/// [`crate::ports::IdempotencyStore::claim`]. The claim of a request that never completes,
+/// e.g. because the process died, expires after `in_flight` instead of blocking retries.
+#[tracing::instrument(name = "application.idempotency.claim", skip(ctx, fingerprint))]
+pub async fn claim(
+    ctx: &AppContext,
+    key: &IdempotencyKey,
+    fingerprint: &str,
+    in_flight: Duration,
+) -> Result<IdempotencyClaim, PortError> {
+    let now = ctx.clock.now();
+    ctx.idempotency
+        .claim(key, fingerprint, now, now + in_flight)
+        .await
+}
+
+/// Stores the `response` of a claimed request, replayed to its retries for `ttl`.
+#[tracing::instrument(name = "application.idempotency.complete", skip(ctx, response))]
+pub async fn complete(
+    ctx: &AppContext,
+    key: &IdempotencyKey,
+    response: &StoredResponse,
+    ttl: Duration,
+) -> Result<(), PortError> {
+    ctx.idempotency
+        .complete(key, response, ctx.clock.now() + ttl)
+        .await
+}
+
+#[tracing::instrument(name = "application.idempotency.release", skip(ctx))]
+pub async fn release(ctx: &AppContext, key: &IdempotencyKey) -> Result<(), PortError> {
+    ctx.idempotency.release(key).await
+}
+
+/// Deletes the expired responses, returning how many were deleted.
+#[tracing::instrument(name = "application.idempotency.purge", skip(ctx))]
+pub async fn purge(ctx: &AppContext) -> Result<u64, PortError> {
+    ctx.idempotency.purge_expired(ctx.clock.now()).await
+}
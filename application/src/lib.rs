@@ -1 +1,9 @@
+pub mod catalog;
+pub mod context;
+pub mod history;
+pub mod idempotency;
+pub mod locale;
 pub mod messages;
+pub mod outbox;
+pub mod ports;
+pub mod template;
@@ -0,0 +1,66 @@
+/// Languages a caller accepts, most preferred first (e.g. from `Accept-Language`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Locale {
+    preferences: Vec<String>,
+}
+
+impl Locale {
+    pub fn new<I, S>(preferences: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Locale {
+            preferences: preferences.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// The most preferred language, if the caller stated any.
+    pub fn requested(&self) -> Option<&str> {
+        self.preferences.first().map(String::as_str)
+    }
+
+    /// Lowercase language tags to try in order: each preference followed by its less specific
+    /// forms (`pt-br` then `pt`), ending with `default`.
+    pub fn fallback_chain(&self, default: &str) -> Vec<String> {
+        let mut chain: Vec<String> = Vec::new();
+
+        let tags = self
+            .preferences
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(default));
+
+        for tag in tags {
+            let tag = tag.to_lowercase();
+            let mut subtags: Vec<&str> = tag.split('-').collect();
+
+            while !subtags.is_empty() {
+                let candidate = subtags.join("-");
+                if !chain.contains(&candidate) {
+                    chain.push(candidate);
+                }
+                subtags.pop();
+            }
+        }
+
+        chain
+    }
+}
+
+/// A text rendered in `locale`, the first language of the fallback chain that had one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Localized {
+    pub text: String,
+    pub locale: String,
+    pub requested: Option<String>,
+}
+
+impl Localized {
+    /// Whether the text is in a different language than the one the caller preferred.
+    pub fn is_fallback(&self) -> bool {
+        self.requested
+            .as_ref()
+            .is_some_and(|requested| !requested.eq_ignore_ascii_case(&self.locale))
+    }
+}
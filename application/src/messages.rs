@@ -1,14 +1,71 @@
+use crate::catalog::ReplyCatalog;
+use crate::context::AppContext;
+use crate::locale::{Locale, Localized};
+use crate::ports::{DomainEvent, MessageRecord, OutboxEvent, PortError};
+use crate::template::RequestMetadata;
+
 #[derive(thiserror::Error, Debug)]
 pub enum ReplyError {
-    #[error("Unknown message \"{0}\"")]
-    UnknownMessage(String),
+    /// The unknown message and the error localized for the caller.
+    #[error("{}", .1.text)]
+    UnknownMessage(String, Localized),
 }
 
-#[tracing::instrument(name = "application.messages.reply")]
-pub fn reply(message: &str) -> Result<String, ReplyError> {
-    match message {
-        "hello" => Ok("world".to_string()),
-        "ping" => Ok("pong".to_string()),
-        _ => Err(ReplyError::UnknownMessage(message.to_string())),
+#[tracing::instrument(name = "application.messages.reply", skip(catalog, metadata))]
+pub fn reply(
+    catalog: &ReplyCatalog,
+    message: &str,
+    locale: &Locale,
+    metadata: &RequestMetadata,
+) -> Result<Localized, ReplyError> {
+    match catalog.reply(message, locale, metadata) {
+        Some(reply) => Ok(reply),
+        None => Err(ReplyError::UnknownMessage(
+            message.to_string(),
+            catalog.unknown_message(message, locale, metadata),
+        )),
     }
 }
+
+/// Stores an answered message along with a [`DomainEvent::MessageReplied`], or a
+/// [`DomainEvent::UnknownMessageReceived`] for unknown messages, recorded with the error served
+/// in place of the reply. The event is published later from the outbox.
+#[tracing::instrument(name = "application.messages.record", skip_all)]
+pub async fn record(
+    ctx: &AppContext,
+    message: &str,
+    result: &Result<Localized, ReplyError>,
+    metadata: &RequestMetadata,
+) -> Result<MessageRecord, PortError> {
+    let (served, known) = match result {
+        Ok(reply) => (reply, true),
+        Err(ReplyError::UnknownMessage(_, error)) => (error, false),
+    };
+
+    let record = MessageRecord {
+        id: ctx.ids.generate(),
+        caller: metadata.caller.clone(),
+        message: message.to_string(),
+        reply: served.text.clone(),
+        locale: served.locale.clone(),
+        known,
+        trace_id: metadata.trace_id.clone(),
+        created_at: ctx.clock.now(),
+    };
+
+    let event = if known {
+        DomainEvent::MessageReplied(record.clone())
+    } else {
+        DomainEvent::UnknownMessageReceived(record.clone())
+    };
+    let event = OutboxEvent {
+        id: ctx.ids.generate(),
+        event,
+        attempts: 0,
+        created_at: record.created_at,
+    };
+
+    ctx.messages.save(&record, &[event]).await?;
+
+    Ok(record)
+}
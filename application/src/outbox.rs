@@ -0,0 +1,79 @@
+//! Publishing the events of the outbox through the [`crate::ports::EventPublisher`]. An event
+//! is only removed once published, so it may be published more than once but is never lost.
+
+use std::time::Duration;
+
+use crate::context::AppContext;
+use crate::ports::PortError;
+
+#[derive(Clone, Debug)]
+pub struct RelaySettings {
+    /// Events published per call to [`relay`].
+    pub batch_size: usize,
+    /// How long leased events are reserved for one relay before being handed out again.
+    pub lease: Duration,
+    pub backoff: Backoff,
+}
+
+/// Delay before retrying an event, doubling with every failed attempt.
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Backoff {
+    /// Delay after the `attempts`-th failed attempt.
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+/// Names of the events a [`relay`] published, and of those it failed to publish.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RelayReport {
+    pub published: Vec<&'static str>,
+    pub failed: Vec<&'static str>,
+}
+
+impl RelayReport {
+    pub fn len(&self) -> usize {
+        self.published.len() + self.failed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Publishes one batch of due events. Events that fail are retried after [`Backoff::delay`].
+#[tracing::instrument(name = "application.outbox.relay", skip_all)]
+pub async fn relay(ctx: &AppContext, settings: &RelaySettings) -> Result<RelayReport, PortError> {
+    let now = ctx.clock.now();
+    let events = ctx
+        .outbox
+        .lease(now, now + settings.lease, settings.batch_size)
+        .await?;
+
+    let mut report = RelayReport::default();
+    for event in events {
+        let name = event.event.name();
+        match ctx.events.publish(&event.event).await {
+            Ok(()) => {
+                ctx.outbox.delivered(&event.id).await?;
+                report.published.push(name);
+            }
+            Err(err) => {
+                tracing::warn!(event = name, id = %event.id, error = %err, "failed to publish event");
+                let retry_at = now + settings.backoff.delay(event.attempts + 1);
+                ctx.outbox
+                    .failed(&event.id, &err.to_string(), retry_at)
+                    .await?;
+                report.failed.push(name);
+            }
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,277 @@
+//! Interfaces the use cases need from the outside world. Adapters implementing them live in
+//! `infrastructure`; tests implement them with fakes.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use tokio::sync::watch;
+
+use crate::catalog::CatalogSnapshot;
+
+/// Failure of the adapter behind a port, e.g. a lost database connection.
+#[derive(thiserror::Error, Debug)]
+#[error("{context}: {source}")]
+pub struct PortError {
+    context: String,
+    source: Box<dyn Error + Send + Sync>,
+}
+
+impl PortError {
+    pub fn new(
+        context: impl Into<String>,
+        source: impl Into<Box<dyn Error + Send + Sync>>,
+    ) -> Self {
+        PortError {
+            context: context.into(),
+            source: source.into(),
+        }
+    }
+}
+
+/// A message answered by the service.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageRecord {
+    pub id: String,
+    pub caller: Option<String>,
+    pub message: String,
+    /// Text served to the caller: the reply, or the unknown-message error when `known` is false.
+    pub reply: String,
+    pub locale: String,
+    pub known: bool,
+    /// Trace the exchange was handled in, when it was traced.
+    pub trace_id: Option<String>,
+    pub created_at: SystemTime,
+}
+
+/// Position in the history, newest first: entries after it are older than `created_at`, or as
+/// old with a smaller `id`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryCursor {
+    pub created_at: SystemTime,
+    pub id: String,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("Invalid history cursor")]
+pub struct InvalidCursor;
+
+/// Cursors are handed to clients as opaque `<microseconds since epoch>:<id>` strings.
+impl fmt::Display for HistoryCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let micros = self
+            .created_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_micros());
+        write!(f, "{micros}:{}", self.id)
+    }
+}
+
+impl FromStr for HistoryCursor {
+    type Err = InvalidCursor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (micros, id) = s.split_once(':').ok_or(InvalidCursor)?;
+        let micros: u64 = micros.parse().map_err(|_| InvalidCursor)?;
+        if id.is_empty() {
+            return Err(InvalidCursor);
+        }
+
+        Ok(HistoryCursor {
+            created_at: SystemTime::UNIX_EPOCH + Duration::from_micros(micros),
+            id: id.to_string(),
+        })
+    }
+}
+
+/// Selects a page of the message history, newest first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryFilter {
+    pub caller: Option<String>,
+    /// Inclusive lower bound of `created_at`.
+    pub from: Option<SystemTime>,
+    /// Exclusive upper bound of `created_at`.
+    pub to: Option<SystemTime>,
+    pub after: Option<HistoryCursor>,
+    pub limit: usize,
+}
+
+/// A key a caller chose to make a request idempotent. Keys of different callers never clash.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IdempotencyKey {
+    /// Empty for anonymous callers.
+    pub caller: String,
+    pub key: String,
+}
+
+/// A response kept to be replayed to the retries of an idempotent request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Outcome of claiming an [`IdempotencyKey`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdempotencyClaim {
+    /// The key was free: the request runs and its response is then stored.
+    Claimed,
+    /// Another request holding the key has not completed yet.
+    InFlight,
+    /// The request already completed with this response.
+    Completed(StoredResponse),
+    /// The key was used for a request with another payload.
+    Mismatch,
+}
+
+/// Something that happened in the application that other systems may react to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DomainEvent {
+    /// A known message was answered.
+    MessageReplied(MessageRecord),
+    /// A message missing from the catalog was received; the record holds the error served.
+    UnknownMessageReceived(MessageRecord),
+}
+
+impl DomainEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DomainEvent::MessageReplied(_) => "message.replied",
+            DomainEvent::UnknownMessageReceived(_) => "message.unknown",
+        }
+    }
+}
+
+/// A [`DomainEvent`] stored in the outbox until it is published.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutboxEvent {
+    pub id: String,
+    pub event: DomainEvent,
+    /// Failed attempts to publish the event so far.
+    pub attempts: u32,
+    pub created_at: SystemTime,
+}
+
+/// Events waiting in the outbox.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OutboxBacklog {
+    pub pending: u64,
+    /// Creation time of the oldest pending event.
+    pub oldest: Option<SystemTime>,
+}
+
+#[async_trait]
+pub trait MessageRepository: Send + Sync {
+    /// Stores `record` and adds `events` to the outbox, in one transaction.
+    async fn save(&self, record: &MessageRecord, events: &[OutboxEvent]) -> Result<(), PortError>;
+
+    async fn get(&self, id: &str) -> Result<Option<MessageRecord>, PortError>;
+
+    /// Records matching `filter`, newest first.
+    async fn list(&self, filter: &HistoryFilter) -> Result<Vec<MessageRecord>, PortError>;
+
+    /// Deletes the records created before `before`, returning how many were deleted.
+    async fn delete_before(&self, before: SystemTime) -> Result<u64, PortError>;
+}
+
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    async fn publish(&self, event: &DomainEvent) -> Result<(), PortError>;
+}
+
+/// Events stored by [`MessageRepository::save`], handed out to be published at least once.
+#[async_trait]
+pub trait Outbox: Send + Sync {
+    /// Leases up to `limit` events due by `now`, oldest first, until `lease_until`. Events
+    /// neither delivered nor failed by then are handed out again.
+    async fn lease(
+        &self,
+        now: SystemTime,
+        lease_until: SystemTime,
+        limit: usize,
+    ) -> Result<Vec<OutboxEvent>, PortError>;
+
+    /// Removes a published event.
+    async fn delivered(&self, id: &str) -> Result<(), PortError>;
+
+    /// Records a failed attempt to publish an event, due again at `retry_at`.
+    async fn failed(&self, id: &str, error: &str, retry_at: SystemTime) -> Result<(), PortError>;
+
+    async fn backlog(&self) -> Result<OutboxBacklog, PortError>;
+}
+
+/// Responses of idempotent requests, kept until they expire.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Claims `key` for a request whose payload hashes to `fingerprint`, until `expires_at`.
+    /// Entries that expired by `now` are treated as free.
+    async fn claim(
+        &self,
+        key: &IdempotencyKey,
+        fingerprint: &str,
+        now: SystemTime,
+        expires_at: SystemTime,
+    ) -> Result<IdempotencyClaim, PortError>;
+
+    /// Stores the response of a claimed request, keeping it until `expires_at`.
+    async fn complete(
+        &self,
+        key: &IdempotencyKey,
+        response: &StoredResponse,
+        expires_at: SystemTime,
+    ) -> Result<(), PortError>;
+
+    /// Frees a claimed key whose request did not complete, so it can be retried.
+    async fn release(&self, key: &IdempotencyKey) -> Result<(), PortError>;
+
+    /// Deletes the entries that expired by `now`, returning how many were deleted.
+    async fn purge_expired(&self, now: SystemTime) -> Result<u64, PortError>;
+}
+
+/// A dependency the service needs to answer requests, checked before it reports itself ready.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Name the check is reported under, e.g. `database`.
+    fn name(&self) -> &str;
+
+    async fn check(&self) -> Result<(), PortError>;
+
+    /// Facts reported along with the status, e.g. which instance holds a lease.
+    async fn details(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+}
+
+/// Remembers which entry of a catalog each message matched, so repeated messages are not
+/// matched against every pattern again.
+pub trait LookupCache: Send + Sync {
+    /// Index of the entry `message` matched, `Some(None)` when it matched none, or `None` when
+    /// it was not looked up yet.
+    fn get(&self, message: &str) -> Option<Option<usize>>;
+
+    fn insert(&self, message: &str, entry: Option<usize>);
+}
+
+/// The reply catalog requests are answered from, which may be replaced while the service runs.
+pub trait CatalogSource: Send + Sync {
+    /// The catalog active right now.
+    fn current(&self) -> Arc<CatalogSnapshot>;
+
+    /// Receiver notified every time a new catalog version becomes active.
+    fn subscribe(&self) -> watch::Receiver<Arc<CatalogSnapshot>>;
+}
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self) -> String;
+}
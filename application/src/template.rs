@@ -0,0 +1,184 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use regex::{Captures, Regex};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("Unclosed placeholder at byte {0}")]
+    Unclosed(usize),
+    #[error("Empty placeholder at byte {0}")]
+    Empty(usize),
+    #[error("Unknown helper \"{0}\"")]
+    UnknownHelper(String),
+    #[error("Unknown variable \"{0}\"")]
+    UnknownVariable(String),
+    #[error("Capture \"${0}\" is not defined by the pattern")]
+    UnknownCapture(String),
+}
+
+/// Information about the request a reply is rendered for.
+#[derive(Clone, Debug, Default)]
+pub struct RequestMetadata {
+    pub caller: Option<String>,
+    /// Trace the request is handled in, when it is traced.
+    pub trace_id: Option<String>,
+}
+
+/// Values a template is rendered with. `locale` is the locale the template is written in.
+pub struct TemplateContext<'a> {
+    pub message: &'a str,
+    pub captures: Option<&'a Captures<'a>>,
+    pub locale: &'a str,
+    pub metadata: &'a RequestMetadata,
+    pub now: SystemTime,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Variable {
+    Message,
+    Locale,
+    Caller,
+    Now,
+    Timestamp,
+    Capture(usize),
+    NamedCapture(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Helper {
+    Upper,
+    Lower,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(Option<Helper>, Variable),
+}
+
+/// A reply template such as `Hello {{ caller }}, order {{ $1 }} was received at {{ now }}`.
+///
+/// Placeholders are `{{ variable }}` or `{{ helper variable }}`. Variables are `message`,
+/// `locale`, `caller`, `now` (RFC 3339), `timestamp` (Unix seconds) and the `$1`/`$name`
+/// captures of a regex pattern; helpers are `upper` and `lower`. Every placeholder is checked
+/// when the template is compiled, so rendering cannot fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Compiles `source`, resolving captures against `pattern` when the reply belongs to a
+    /// regex rule.
+    pub fn compile(source: &str, pattern: Option<&Regex>) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        let mut offset = 0;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+
+            let end = rest[start..]
+                .find("}}")
+                .ok_or(TemplateError::Unclosed(offset + start))?;
+            let expression = rest[start + 2..start + end].trim();
+            if expression.is_empty() {
+                return Err(TemplateError::Empty(offset + start));
+            }
+
+            parts.push(placeholder(expression, pattern)?);
+
+            offset += start + end + 2;
+            rest = &rest[start + end + 2..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        Ok(Template { parts })
+    }
+
+    pub fn render(&self, ctx: &TemplateContext) -> String {
+        let mut output = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => output.push_str(text),
+                Part::Placeholder(helper, variable) => {
+                    let value = resolve(variable, ctx);
+                    match helper {
+                        Some(Helper::Upper) => output.push_str(&value.to_uppercase()),
+                        Some(Helper::Lower) => output.push_str(&value.to_lowercase()),
+                        None => output.push_str(&value),
+                    }
+                }
+            }
+        }
+
+        output
+    }
+}
+
+fn placeholder(expression: &str, pattern: Option<&Regex>) -> Result<Part, TemplateError> {
+    let mut words = expression.split_whitespace();
+    let (helper, name) = match (words.next(), words.next(), words.next()) {
+        (Some(name), None, None) => (None, name),
+        (Some(helper), Some(name), None) => {
+            let helper = match helper {
+                "upper" => Helper::Upper,
+                "lower" => Helper::Lower,
+                other => return Err(TemplateError::UnknownHelper(other.to_string())),
+            };
+            (Some(helper), name)
+        }
+        _ => return Err(TemplateError::UnknownVariable(expression.to_string())),
+    };
+
+    let variable = match name {
+        "message" => Variable::Message,
+        "locale" => Variable::Locale,
+        "caller" => Variable::Caller,
+        "now" => Variable::Now,
+        "timestamp" => Variable::Timestamp,
+        capture if capture.starts_with('$') => capture_variable(&capture[1..], pattern)?,
+        other => return Err(TemplateError::UnknownVariable(other.to_string())),
+    };
+
+    Ok(Part::Placeholder(helper, variable))
+}
+
+fn capture_variable(name: &str, pattern: Option<&Regex>) -> Result<Variable, TemplateError> {
+    let unknown = || TemplateError::UnknownCapture(name.to_string());
+    let pattern = pattern.ok_or_else(unknown)?;
+
+    match name.parse::<usize>() {
+        Ok(index) if index < pattern.captures_len() => Ok(Variable::Capture(index)),
+        Ok(_) => Err(unknown()),
+        Err(_) if pattern.capture_names().flatten().any(|n| n == name) => {
+            Ok(Variable::NamedCapture(name.to_string()))
+        }
+        Err(_) => Err(unknown()),
+    }
+}
+
+fn resolve(variable: &Variable, ctx: &TemplateContext) -> String {
+    let capture = |m: Option<regex::Match>| m.map(|m| m.as_str().to_string()).unwrap_or_default();
+
+    match variable {
+        Variable::Message => ctx.message.to_string(),
+        Variable::Locale => ctx.locale.to_string(),
+        Variable::Caller => ctx.metadata.caller.clone().unwrap_or_default(),
+        Variable::Now => humantime::format_rfc3339_seconds(ctx.now).to_string(),
+        Variable::Timestamp => ctx
+            .now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string(),
+        Variable::Capture(index) => capture(ctx.captures.and_then(|c| c.get(*index))),
+        Variable::NamedCapture(name) => capture(ctx.captures.and_then(|c| c.name(name))),
+    }
+}
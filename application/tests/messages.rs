@@ -0,0 +1,460 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use application::{
+    catalog::{CatalogSnapshot, ReplyCatalog},
+    context::AppContext,
+    history,
+    locale::Locale,
+    messages,
+    outbox::{self, Backoff, RelayReport, RelaySettings},
+    ports::{
+        CatalogSource, Clock, DomainEvent, EventPublisher, HistoryFilter, IdGenerator,
+        IdempotencyClaim, IdempotencyKey, IdempotencyStore, MessageRecord, MessageRepository,
+        Outbox, OutboxBacklog, OutboxEvent, PortError, StoredResponse,
+    },
+    template::RequestMetadata,
+};
+use async_trait::async_trait;
+use tokio::sync::watch;
+
+/// Stores records and, as the outbox, their events along with the time they are due.
+#[derive(Default)]
+struct FakeRepository {
+    records: Mutex<Vec<MessageRecord>>,
+    outbox: Mutex<Vec<(OutboxEvent, SystemTime)>>,
+    fail: bool,
+}
+
+#[async_trait]
+impl MessageRepository for FakeRepository {
+    async fn save(&self, record: &MessageRecord, events: &[OutboxEvent]) -> Result<(), PortError> {
+        if self.fail {
+            return Err(PortError::new("save message", "connection lost"));
+        }
+        self.records.lock().unwrap().push(record.clone());
+        self.outbox
+            .lock()
+            .unwrap()
+            .extend(events.iter().map(|event| (event.clone(), event.created_at)));
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<MessageRecord>, PortError> {
+        Ok(self
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|record| record.id == id)
+            .cloned())
+    }
+
+    async fn list(&self, filter: &HistoryFilter) -> Result<Vec<MessageRecord>, PortError> {
+        let mut records = self.records.lock().unwrap().clone();
+        records.reverse();
+        if let Some(after) = &filter.after {
+            records.retain(|record| record.id < after.id);
+        }
+        records.truncate(filter.limit);
+        Ok(records)
+    }
+
+    async fn delete_before(&self, before: SystemTime) -> Result<u64, PortError> {
+        let mut records = self.records.lock().unwrap();
+        let count = records.len();
+        records.retain(|record| record.created_at >= before);
+        Ok((count - records.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl Outbox for FakeRepository {
+    async fn lease(
+        &self,
+        now: SystemTime,
+        lease_until: SystemTime,
+        limit: usize,
+    ) -> Result<Vec<OutboxEvent>, PortError> {
+        Ok(self
+            .outbox
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .filter(|(_, due)| *due <= now)
+            .take(limit)
+            .map(|(event, due)| {
+                *due = lease_until;
+                event.clone()
+            })
+            .collect())
+    }
+
+    async fn delivered(&self, id: &str) -> Result<(), PortError> {
+        self.outbox
+            .lock()
+            .unwrap()
+            .retain(|(event, _)| event.id != id);
+        Ok(())
+    }
+
+    async fn failed(&self, id: &str, _: &str, retry_at: SystemTime) -> Result<(), PortError> {
+        for (event, due) in self.outbox.lock().unwrap().iter_mut() {
+            if event.id == id {
+                event.attempts += 1;
+                *due = retry_at;
+            }
+        }
+        Ok(())
+    }
+
+    async fn backlog(&self) -> Result<OutboxBacklog, PortError> {
+        let outbox = self.outbox.lock().unwrap();
+        Ok(OutboxBacklog {
+            pending: outbox.len() as u64,
+            oldest: outbox.iter().map(|(event, _)| event.created_at).min(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct FakePublisher {
+    events: Mutex<Vec<DomainEvent>>,
+    failing: AtomicBool,
+}
+
+#[async_trait]
+impl EventPublisher for FakePublisher {
+    async fn publish(&self, event: &DomainEvent) -> Result<(), PortError> {
+        if self.failing.load(Ordering::Relaxed) {
+            return Err(PortError::new("publish event", "broker unavailable"));
+        }
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+/// Answers from the built-in catalog, which is never reloaded.
+struct BuiltinCatalog(watch::Sender<Arc<CatalogSnapshot>>);
+
+impl Default for BuiltinCatalog {
+    fn default() -> Self {
+        BuiltinCatalog(watch::Sender::new(Arc::new(CatalogSnapshot {
+            catalog: ReplyCatalog::default(),
+            source: None,
+            version: "builtin".to_string(),
+            loaded_at: now(),
+        })))
+    }
+}
+
+impl CatalogSource for BuiltinCatalog {
+    fn current(&self) -> Arc<CatalogSnapshot> {
+        self.0.borrow().clone()
+    }
+
+    fn subscribe(&self) -> watch::Receiver<Arc<CatalogSnapshot>> {
+        self.0.subscribe()
+    }
+}
+
+/// Use cases under test never replay responses.
+struct NoIdempotency;
+
+#[async_trait]
+impl IdempotencyStore for NoIdempotency {
+    async fn claim(
+        &self,
+        _: &IdempotencyKey,
+        _: &str,
+        _: SystemTime,
+        _: SystemTime,
+    ) -> Result<IdempotencyClaim, PortError> {
+        Ok(IdempotencyClaim::Claimed)
+    }
+
+    async fn complete(
+        &self,
+        _: &IdempotencyKey,
+        _: &StoredResponse,
+        _: SystemTime,
+    ) -> Result<(), PortError> {
+        Ok(())
+    }
+
+    async fn release(&self, _: &IdempotencyKey) -> Result<(), PortError> {
+        Ok(())
+    }
+
+    async fn purge_expired(&self, _: SystemTime) -> Result<u64, PortError> {
+        Ok(0)
+    }
+}
+
+struct FixedClock(SystemTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+#[derive(Default)]
+struct SequentialIds(AtomicUsize);
+
+impl IdGenerator for SequentialIds {
+    fn generate(&self) -> String {
+        format!("id-{}", self.0.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+}
+
+struct Fakes {
+    context: AppContext,
+    repository: Arc<FakeRepository>,
+    publisher: Arc<FakePublisher>,
+}
+
+fn fakes(repository: FakeRepository) -> Fakes {
+    let repository = Arc::new(repository);
+    let publisher = Arc::new(FakePublisher::default());
+
+    Fakes {
+        context: AppContext {
+            messages: repository.clone(),
+            events: publisher.clone(),
+            outbox: repository.clone(),
+            clock: Arc::new(FixedClock(now())),
+            ids: Arc::new(SequentialIds::default()),
+            idempotency: Arc::new(NoIdempotency),
+            catalog: Arc::new(BuiltinCatalog::default()),
+            readiness: Vec::new(),
+        },
+        repository,
+        publisher,
+    }
+}
+
+fn now() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+}
+
+fn metadata() -> RequestMetadata {
+    RequestMetadata {
+        caller: Some("alice".to_string()),
+        trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+    }
+}
+
+async fn answer(context: &AppContext, message: &str) -> Result<MessageRecord, PortError> {
+    let result = messages::reply(
+        &ReplyCatalog::default(),
+        message,
+        &Locale::default(),
+        &metadata(),
+    );
+
+    messages::record(context, message, &result, &metadata()).await
+}
+
+#[tokio::test]
+async fn record_saves_reply_with_its_event() {
+    let fakes = fakes(FakeRepository::default());
+
+    let record = answer(&fakes.context, "ping").await.unwrap();
+
+    assert_eq!(
+        MessageRecord {
+            id: "id-1".to_string(),
+            caller: Some("alice".to_string()),
+            message: "ping".to_string(),
+            reply: "pong".to_string(),
+            locale: "en".to_string(),
+            known: true,
+            trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+            created_at: now(),
+        },
+        record
+    );
+    assert_eq!(
+        Some(record.clone()),
+        fakes.context.messages.get("id-1").await.unwrap()
+    );
+    assert_eq!(
+        vec![(
+            OutboxEvent {
+                id: "id-2".to_string(),
+                event: DomainEvent::MessageReplied(record),
+                attempts: 0,
+                created_at: now(),
+            },
+            now()
+        )],
+        *fakes.repository.outbox.lock().unwrap()
+    );
+    assert!(fakes.publisher.events.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn record_keeps_unknown_messages_with_the_served_error() {
+    let fakes = fakes(FakeRepository::default());
+
+    let record = answer(&fakes.context, "unknown").await.unwrap();
+
+    assert!(!record.known);
+    assert_eq!("Unknown message \"unknown\"", record.reply);
+    assert_eq!(1, fakes.repository.records.lock().unwrap().len());
+    assert_eq!(
+        DomainEvent::UnknownMessageReceived(record),
+        fakes.repository.outbox.lock().unwrap()[0].0.event
+    );
+}
+
+#[tokio::test]
+async fn record_fails_without_event_when_saving_fails() {
+    let fakes = fakes(FakeRepository {
+        fail: true,
+        ..FakeRepository::default()
+    });
+
+    let err = answer(&fakes.context, "ping").await.unwrap_err();
+
+    assert_eq!("save message: connection lost", err.to_string());
+    assert!(fakes.repository.outbox.lock().unwrap().is_empty());
+}
+
+fn relay_settings() -> RelaySettings {
+    RelaySettings {
+        batch_size: 10,
+        lease: Duration::from_secs(30),
+        backoff: Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+        },
+    }
+}
+
+#[tokio::test]
+async fn relay_publishes_and_removes_events() {
+    let fakes = fakes(FakeRepository::default());
+    let replied = answer(&fakes.context, "ping").await.unwrap();
+    let unknown = answer(&fakes.context, "bye").await.unwrap();
+
+    let report = outbox::relay(&fakes.context, &relay_settings())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        RelayReport {
+            published: vec!["message.replied", "message.unknown"],
+            failed: vec![],
+        },
+        report
+    );
+    assert_eq!(
+        vec![
+            DomainEvent::MessageReplied(replied),
+            DomainEvent::UnknownMessageReceived(unknown)
+        ],
+        *fakes.publisher.events.lock().unwrap()
+    );
+    assert_eq!(
+        OutboxBacklog::default(),
+        fakes.context.outbox.backlog().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn relay_keeps_failed_events_for_a_retry_with_backoff() {
+    let fakes = fakes(FakeRepository::default());
+    answer(&fakes.context, "ping").await.unwrap();
+    fakes.publisher.failing.store(true, Ordering::Relaxed);
+
+    for backoff in [1, 2] {
+        let report = outbox::relay(&fakes.context, &relay_settings())
+            .await
+            .unwrap();
+        assert_eq!(vec!["message.replied"], report.failed);
+
+        let due = &mut fakes.repository.outbox.lock().unwrap()[0].1;
+        assert_eq!(now() + Duration::from_secs(backoff), *due);
+        // let the backoff elapse
+        *due = now();
+    }
+
+    let (event, _) = fakes.repository.outbox.lock().unwrap()[0].clone();
+    assert_eq!(2, event.attempts);
+
+    fakes.publisher.failing.store(false, Ordering::Relaxed);
+    let report = outbox::relay(&fakes.context, &relay_settings())
+        .await
+        .unwrap();
+    assert_eq!(vec!["message.replied"], report.published);
+    assert!(fakes.repository.outbox.lock().unwrap().is_empty());
+}
+
+#[test]
+fn backoff_doubles_up_to_the_maximum() {
+    let backoff = relay_settings().backoff;
+
+    let delays: Vec<_> = [1, 2, 3, 6, 7, 40]
+        .into_iter()
+        .map(|attempts| backoff.delay(attempts).as_secs())
+        .collect();
+
+    assert_eq!(vec![1, 2, 4, 32, 60, 60], delays);
+}
+
+#[tokio::test]
+async fn history_pages_end_with_the_last_record() {
+    let fakes = fakes(FakeRepository::default());
+    for message in ["ping", "hello", "ping"] {
+        answer(&fakes.context, message).await.unwrap();
+    }
+    let filter = |after| HistoryFilter {
+        caller: None,
+        from: None,
+        to: None,
+        after,
+        limit: 2,
+    };
+
+    let first = history::list(&fakes.context, filter(None)).await.unwrap();
+    assert_eq!(2, first.records.len());
+    let next = first.next.expect("a second page");
+    assert_eq!("id-3", next.id);
+    assert_eq!(now(), next.created_at);
+
+    let second = history::list(&fakes.context, filter(Some(next)))
+        .await
+        .unwrap();
+    assert_eq!(1, second.records.len());
+    assert!(second.next.is_none());
+}
+
+#[tokio::test]
+async fn purge_deletes_records_past_the_retention() {
+    let fakes = fakes(FakeRepository::default());
+    let recent = answer(&fakes.context, "ping").await.unwrap();
+    fakes
+        .repository
+        .records
+        .lock()
+        .unwrap()
+        .push(MessageRecord {
+            id: "old".to_string(),
+            created_at: now() - Duration::from_secs(61),
+            ..recent.clone()
+        });
+
+    let deleted = history::purge(&fakes.context, Duration::from_secs(60))
+        .await
+        .unwrap();
+
+    assert_eq!(1, deleted);
+    assert_eq!(vec![recent], *fakes.repository.records.lock().unwrap());
+}
@@ -0,0 +1,70 @@
+use std::{
+    convert::Infallible,
+    future::{ready, Ready},
+};
+
+use actix_web::{
+    dev::Payload,
+    http::header::{AcceptLanguage, Header, HeaderName, Preference, Quality},
+    FromRequest, HttpRequest,
+};
+use application::{locale::Locale, template::RequestMetadata};
+use infrastructure::telemetry;
+use tracing::Span;
+
+/// Header identifying the principal making the request.
+pub const CALLER: HeaderName = HeaderName::from_static("x-caller");
+
+/// Request metadata available to reply templates: the [`CALLER`] header, and the trace the
+/// request is handled in.
+#[derive(Debug)]
+pub struct Metadata(pub RequestMetadata);
+
+impl FromRequest for Metadata {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let caller = req
+            .headers()
+            .get(CALLER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        ready(Ok(Metadata(RequestMetadata {
+            caller,
+            trace_id: telemetry::trace_id(&Span::current()),
+        })))
+    }
+}
+
+/// Languages listed in `Accept-Language`, ranked by q-factor. Wildcards and languages with
+/// `q=0` are left out; a missing or malformed header accepts any language.
+#[derive(Debug)]
+pub struct AcceptedLocale(pub Locale);
+
+impl FromRequest for AcceptedLocale {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let accepted = AcceptLanguage::parse(req)
+            .map(|AcceptLanguage(languages)| {
+                AcceptLanguage(
+                    languages
+                        .into_iter()
+                        .filter(|language| language.quality > Quality::ZERO)
+                        .collect(),
+                )
+                .ranked()
+            })
+            .unwrap_or_default();
+
+        let tags = accepted.into_iter().filter_map(|language| match language {
+            Preference::Specific(tag) => Some(tag.to_string()),
+            Preference::Any => None,
+        });
+
+        ready(Ok(AcceptedLocale(Locale::new(tags))))
+    }
+}
@@ -0,0 +1,5 @@
+mod metadata;
+mod validated;
+
+pub use metadata::*;
+pub use validated::*;
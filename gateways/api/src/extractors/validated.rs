@@ -0,0 +1,106 @@
+use std::{borrow::Cow, collections::HashMap, ops::Deref};
+
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use serde::Serialize;
+use serde_json::Value;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+use crate::response::bad_request;
+
+/// Runs the `validator` rules of the extracted value before the handler is called, e.g.
+/// `Validated<web::Json<ReplyRequest>>`. Every failing rule is reported in the `details` of a
+/// `400 Bad Request` response.
+#[derive(Debug)]
+pub struct Validated<T>(pub T);
+
+impl<T> Validated<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Validated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> FromRequest for Validated<T>
+where
+    T: FromRequest + Deref + 'static,
+    T::Target: Validate,
+    T::Error: Into<actix_web::Error>,
+{
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let fut = T::from_request(req, payload);
+
+        Box::pin(async move {
+            let value = fut.await.map_err(Into::into)?;
+
+            match value.validate() {
+                Ok(()) => Ok(Validated(value)),
+                Err(errors) => Err(bad_request("Validation failed", &field_errors(&errors))),
+            }
+        })
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub params: HashMap<String, Value>,
+}
+
+/// Flattens nested validation errors into one entry per failing rule, using `parent.child`
+/// and `list[index]` paths for the field names.
+pub fn field_errors(errors: &ValidationErrors) -> Vec<FieldError> {
+    let mut result = Vec::new();
+    collect(errors, "", &mut result);
+    result.sort_by(|a, b| a.field.cmp(&b.field).then_with(|| a.code.cmp(&b.code)));
+    result
+}
+
+fn collect(errors: &ValidationErrors, prefix: &str, result: &mut Vec<FieldError>) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{prefix}.{field}")
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(errors) => result.extend(errors.iter().map(|err| {
+                FieldError {
+                    field: path.clone(),
+                    code: err.code.to_string(),
+                    message: err
+                        .message
+                        .clone()
+                        .unwrap_or_else(|| Cow::Owned(format!("failed the \"{}\" rule", err.code)))
+                        .to_string(),
+                    params: err
+                        .params
+                        .iter()
+                        .filter(|(name, _)| *name != "value")
+                        .map(|(name, value)| (name.to_string(), value.clone()))
+                        .collect(),
+                }
+            })),
+            ValidationErrorsKind::Struct(errors) => collect(errors, &path, result),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect(errors, &format!("{path}[{index}]"), result);
+                }
+            }
+        }
+    }
+}
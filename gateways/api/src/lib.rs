@@ -1,5 +1,13 @@
+pub mod extractors;
+pub mod localization;
 pub mod middlewares;
+pub mod openapi;
+pub mod payload;
 pub mod routes;
 pub mod server;
+pub mod settings;
+pub mod streaming;
+pub mod versioning;
+pub mod websocket;
 
 pub mod response;
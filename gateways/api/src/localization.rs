@@ -0,0 +1,54 @@
+use application::{catalog::ReplyCatalog, locale::Localized};
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family},
+    registry::Registry,
+};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct LocaleFallbackLabel {
+    pub requested: String,
+    pub served: String,
+}
+
+/// Counts replies served in a different locale than the one the caller preferred. Requested
+/// locales the catalog has no text in are counted as `other`, so callers cannot grow the
+/// label set.
+#[derive(Clone)]
+pub struct LocaleFallbacks {
+    fallbacks: Family<LocaleFallbackLabel, Counter>,
+}
+
+impl LocaleFallbacks {
+    pub fn new(registry: &mut Registry) -> Self {
+        let fallbacks = Family::<LocaleFallbackLabel, Counter>::default();
+
+        registry.register(
+            "locale_fallback_count",
+            "Number of replies served in a fallback locale",
+            fallbacks.clone(),
+        );
+
+        LocaleFallbacks { fallbacks }
+    }
+
+    /// Counts `localized`, rendered from `catalog`, if it was served in a fallback locale.
+    pub fn record(&self, catalog: &ReplyCatalog, localized: &Localized) {
+        let Some(requested) = &localized.requested else {
+            return;
+        };
+
+        if localized.is_fallback() {
+            self.fallbacks
+                .get_or_create(&LocaleFallbackLabel {
+                    requested: if catalog.has_locale(requested) {
+                        requested.to_lowercase()
+                    } else {
+                        "other".to_string()
+                    },
+                    served: localized.locale.to_lowercase(),
+                })
+                .inc();
+        }
+    }
+}
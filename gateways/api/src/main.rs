@@ -1,12 +1,139 @@
-use api::server;
-use infrastructure::{self, telemetry};
+use api::{
+    routes::graphql,
+    server,
+    settings::{self, get_config},
+    versioning,
+};
+use std::{sync::Arc, time::Duration};
+
+use application::catalog::ReplyCatalog;
+use application::context::AppContext;
+use application::outbox::{Backoff, RelaySettings};
+use infrastructure::{
+    self,
+    cache::{CacheSettings, Eviction},
+    catalog::CatalogStore,
+    db::{self, sqlite},
+    lease::{FileLeaseStore, LeaseStore, Leases},
+    memory::{self, InMemoryLeaseStore},
+    outbox::OutboxRelay,
+    retention,
+    scheduler::{Schedule, Scheduler},
+    telemetry,
+};
 use prometheus_client::registry::Registry;
 
-mod settings;
-use settings::get_config;
+/// Settings of a cache, `None` when it is disabled.
+fn cache_settings(cache: settings::Cache) -> Option<CacheSettings> {
+    (cache.max_entries > 0).then(|| CacheSettings {
+        max_entries: cache.max_entries,
+        ttl: Duration::from_secs(cache.ttl_sec),
+        stale_while_revalidate: Duration::from_secs(cache.stale_sec),
+        eviction: match cache.eviction {
+            settings::CacheEviction::Lru => Eviction::Lru,
+            settings::CacheEviction::Lfu => Eviction::Lfu,
+        },
+    })
+}
+
+/// Where messages are stored, selected by `storage.backend`.
+enum Storage {
+    Memory,
+    Postgres(db::Database),
+    Sqlite(sqlite::SqliteDatabase),
+}
+
+impl Storage {
+    /// Connects to the selected backend. SQLite databases belong to a single node, so they are
+    /// migrated right away; PostgreSQL is migrated by the `migrate` command.
+    async fn connect(
+        storage: &settings::Storage,
+        database: &settings::Database,
+        registry: &mut Registry,
+    ) -> eyre::Result<Self> {
+        match storage.backend {
+            settings::StorageBackend::Memory => Ok(Storage::Memory),
+            settings::StorageBackend::Postgres => {
+                let url = database.url.clone().ok_or_else(|| {
+                    eyre::eyre!("database.url is required by the postgres storage backend")
+                })?;
+
+                Ok(Storage::Postgres(db::Database::connect(
+                    &db::Settings {
+                        url,
+                        max_connections: database.max_connections,
+                        min_connections: database.min_connections,
+                        acquire_timeout: Duration::from_secs(database.acquire_timeout_sec),
+                        idle_timeout: (database.idle_timeout_sec > 0)
+                            .then(|| Duration::from_secs(database.idle_timeout_sec)),
+                        slow_statement_threshold: Duration::from_millis(database.slow_statement_ms),
+                    },
+                    registry,
+                )?))
+            }
+            settings::StorageBackend::Sqlite => {
+                let options = &storage.sqlite;
+                let file = sqlite::SqliteDatabase::connect(
+                    &sqlite::Settings {
+                        path: options.path.clone().into(),
+                        readers: options.readers,
+                        busy_timeout: Duration::from_millis(options.busy_timeout_ms),
+                        slow_statement_threshold: Duration::from_millis(options.slow_statement_ms),
+                    },
+                    registry,
+                )
+                .await?;
+                file.migrate().await?;
+
+                Ok(Storage::Sqlite(file))
+            }
+        }
+    }
+
+    async fn migrate(&self) -> eyre::Result<()> {
+        match self {
+            Storage::Memory => eyre::bail!("the memory storage backend has nothing to migrate"),
+            Storage::Postgres(database) => database.migrate().await,
+            Storage::Sqlite(database) => database.migrate().await,
+        }
+    }
+
+    fn context(&self) -> AppContext {
+        match self {
+            Storage::Memory => memory::context(),
+            Storage::Postgres(database) => db::context(database),
+            Storage::Sqlite(database) => sqlite::context(database),
+        }
+    }
+
+    /// Leases shared by the instances using this storage; in memory, they are only held by
+    /// this process.
+    fn leases(&self) -> Arc<dyn LeaseStore> {
+        match self {
+            Storage::Memory => Arc::new(InMemoryLeaseStore::default()),
+            Storage::Postgres(database) => Arc::new(database.leases()),
+            Storage::Sqlite(database) => Arc::new(database.leases()),
+        }
+    }
+}
+
+/// What the binary was asked to do: serve by default, or `migrate` the database and exit.
+enum Command {
+    Serve,
+    Migrate,
+}
+
+fn command() -> eyre::Result<Command> {
+    match std::env::args().nth(1).as_deref() {
+        None => Ok(Command::Serve),
+        Some("migrate") => Ok(Command::Migrate),
+        Some(other) => eyre::bail!("unknown command \"{other}\", expected `migrate`"),
+    }
+}
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
+    let command = command()?;
     let settings = get_config()?;
 
     let _guard = telemetry::setup(telemetry::Settings {
@@ -21,22 +148,160 @@ async fn main() -> eyre::Result<()> {
         service_name: settings.app.service_name,
     });
 
-    let registry = Registry::default();
+    let mut registry = Registry::default();
+
+    let storage = Storage::connect(&settings.storage, &settings.database, &mut registry).await?;
+
+    if let Command::Migrate = command {
+        storage.migrate().await?;
+        tracing::info!("Database migrations applied");
+        return Ok(());
+    }
+
+    let catalog = Arc::new(match &settings.catalog.path {
+        Some(path) => CatalogStore::from_file(path)?,
+        None => CatalogStore::new(ReplyCatalog::default()),
+    });
+    if settings.catalog.path.is_some() {
+        catalog
+            .clone()
+            .watch(Duration::from_secs(settings.catalog.reload_interval_sec));
+    }
+
+    let lease_store: Arc<dyn LeaseStore> = match settings.lease.backend {
+        settings::LeaseBackend::Storage => storage.leases(),
+        settings::LeaseBackend::File => Arc::new(FileLeaseStore::new(&settings.lease.dir)?),
+    };
+    let leases = Arc::new(Leases::new(
+        lease_store,
+        settings.lease.holder.unwrap_or_else(|| {
+            let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string());
+            format!("{host}-{}", std::process::id())
+        }),
+        Duration::from_secs(settings.lease.ttl_sec),
+        &mut registry,
+    ));
+
+    let mut context = storage.context();
+    context.catalog = catalog.clone();
+    context.readiness.push(leases.clone());
+
+    let grpc_server = if settings.grpc.enabled {
+        Some(grpc::server::Server::setup(
+            grpc::server::Settings {
+                host: settings.grpc.host,
+                port: settings.grpc.port,
+                context: context.clone(),
+            },
+            &mut registry,
+        )?)
+    } else {
+        None
+    };
+
+    let relay = OutboxRelay::new(
+        RelaySettings {
+            batch_size: settings.outbox.batch_size,
+            lease: Duration::from_secs(settings.outbox.lease_sec),
+            backoff: Backoff {
+                initial: Duration::from_millis(settings.outbox.retry_initial_ms),
+                max: Duration::from_secs(settings.outbox.retry_max_sec),
+            },
+        },
+        &mut registry,
+    );
+    let retention_schedule = match &settings.history.purge_cron {
+        Some(expression) => Schedule::cron(expression)
+            .map_err(|err| eyre::eyre!("invalid history.purge_cron {expression:?}: {err}"))?,
+        None => Schedule::Every(Duration::from_secs(settings.history.purge_interval_sec)),
+    };
+    let mut scheduler = Scheduler::new(&mut registry).with_leases(leases);
 
     let server = server::Server::setup(server::Settings {
         app: server::AppSettings {
             host: settings.app.host,
             port: settings.app.port,
             request_timeout_sec: settings.app.request_timeout_sec,
+            compression_min_size_bytes: settings.app.compression_min_size_bytes,
+            decompression_limit_bytes: settings.app.decompression_limit_bytes,
+            payload_limits: settings.app.payload_limits.into(),
+            route_payload_limits: settings
+                .app
+                .route_payload_limits
+                .into_iter()
+                .map(|(path, limits)| (path, limits.into()))
+                .collect(),
+            docs_ui: settings.app.docs_ui.into(),
+            api_versions: settings
+                .app
+                .api_versions
+                .iter()
+                .map(|version| {
+                    versioning::ApiVersion::from_unix_timestamps(
+                        &version.name,
+                        version.deprecated_at,
+                        version.sunset_at,
+                    )
+                })
+                .collect(),
+            default_api_version: settings.app.default_api_version,
+            max_batch_size: settings.app.max_batch_size,
+            idempotency_ttl_sec: settings.app.idempotency_ttl_sec,
+            websocket: settings.app.websocket.into(),
+            graphql: graphql::GraphQLConfig {
+                max_depth: settings.app.graphql.max_depth,
+                max_complexity: settings.app.graphql.max_complexity,
+                persisted_query_cache_size: settings.app.graphql.persisted_query_cache_size,
+                max_batch_operations: settings.app.graphql.max_batch_operations,
+            },
+            upstreams: settings
+                .upstreams
+                .into_iter()
+                .map(|(name, upstream)| (name, upstream.into()))
+                .collect(),
+            catalog_cache: cache_settings(settings.cache.catalog),
+            upstream_cache: cache_settings(settings.cache.upstreams),
         },
         metrics: server::MetricSettings {
             host: settings.metric.host,
             port: settings.metric.port,
             registry,
         },
+        catalog,
+        context: context.clone(),
     })?;
 
-    server.run().await?;
+    scheduler.add(
+        relay
+            .job(
+                context.clone(),
+                Duration::from_millis(settings.outbox.poll_interval_ms),
+            )
+            .timeout(Duration::from_secs(settings.outbox.relay_timeout_sec))
+            .singleton(),
+    );
+    scheduler.add(
+        retention::job(
+            context,
+            (settings.history.retention_sec > 0)
+                .then(|| Duration::from_secs(settings.history.retention_sec)),
+            retention_schedule,
+        )
+        .timeout(Duration::from_secs(settings.history.purge_timeout_sec))
+        .jitter(Duration::from_millis(settings.history.purge_jitter_ms))
+        .singleton(),
+    );
+    scheduler.spawn(server.shutdown());
+
+    match grpc_server {
+        Some(grpc_server) => {
+            let shutdown = server.shutdown();
+            let (served, served_grpc) = tokio::join!(server.run(), grpc_server.run(shutdown));
+            served?;
+            served_grpc?;
+        }
+        None => server.run().await?,
+    }
 
     Ok(())
 }
@@ -0,0 +1,224 @@
+use std::{
+    future::{ready, Ready},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use actix_http::encoding::Encoder;
+use actix_web::{
+    body::{BodySize, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{self, AcceptEncoding, ContentEncoding, Encoding, Header, HeaderValue},
+    web::Bytes,
+    Error,
+};
+use futures_util::future::LocalBoxFuture;
+use pin_project_lite::pin_project;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{family::Family, histogram::Histogram},
+    registry::Registry,
+};
+
+use crate::streaming::EVENT_STREAM;
+
+const SUPPORTED_ENCODINGS: &[Encoding] = &[
+    Encoding::zstd(),
+    Encoding::brotli(),
+    Encoding::gzip(),
+    Encoding::identity(),
+];
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CompressionLabel {
+    pub encoding: String,
+}
+
+/// Negotiates the response encoding from `Accept-Encoding` and compresses bodies whose size is
+/// at least `min_size` bytes. Requests without `Accept-Encoding` are always answered with the
+/// identity encoding.
+#[derive(Clone)]
+pub struct Compression {
+    min_size: u64,
+    compression_ratio: Family<CompressionLabel, Histogram>,
+}
+
+impl Compression {
+    pub fn new(registry: &mut Registry, min_size: u64) -> Self {
+        let compression_ratio = Family::<CompressionLabel, Histogram>::new_with_constructor(|| {
+            let buckets = [0.05, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0];
+            Histogram::new(buckets.into_iter())
+        });
+
+        registry.register(
+            "response_compression_ratio",
+            "Ratio between the compressed and the original response body size",
+            compression_ratio.clone(),
+        );
+
+        Compression {
+            min_size,
+            compression_ratio,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Compression
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<MeteredBody<Encoder<B>>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CompressionMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CompressionMiddleware {
+            service,
+            min_size: self.min_size,
+            compression_ratio: Arc::new(self.compression_ratio.clone()),
+        }))
+    }
+}
+
+pub struct CompressionMiddleware<S> {
+    service: S,
+    min_size: u64,
+    compression_ratio: Arc<Family<CompressionLabel, Histogram>>,
+}
+
+impl<S, B> Service<ServiceRequest> for CompressionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<MeteredBody<Encoder<B>>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let encoding = negotiate(&req);
+        let min_size = self.min_size;
+        let compression_ratio = self.compression_ratio.clone();
+
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+
+            Ok(res.map_body(move |head, body| {
+                let original_size = match body.size() {
+                    BodySize::Sized(size) => Some(size),
+                    _ => None,
+                };
+
+                // compressors hold output back until enough input is buffered, which would
+                // delay the events of a stream
+                let is_event_stream = head
+                    .headers()
+                    .get(header::CONTENT_TYPE)
+                    .is_some_and(|value| value.as_bytes().starts_with(EVENT_STREAM.as_bytes()));
+
+                let encoding = match original_size {
+                    Some(size) if size < min_size => ContentEncoding::Identity,
+                    _ if is_event_stream => ContentEncoding::Identity,
+                    _ => encoding,
+                };
+
+                head.headers_mut()
+                    .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+                let body = Encoder::response(encoding, head, body);
+
+                // the encoder may decline to compress (e.g. the handler has set its own
+                // Content-Encoding), so only measure bodies that were actually encoded
+                let metric = match (original_size, head.headers().get(header::CONTENT_ENCODING)) {
+                    (Some(original_size), Some(value))
+                        if encoding != ContentEncoding::Identity
+                            && value == encoding.to_header_value() =>
+                    {
+                        Some(RatioMetric {
+                            original_size,
+                            encoding,
+                            compression_ratio,
+                        })
+                    }
+                    _ => None,
+                };
+
+                MeteredBody {
+                    body,
+                    metric,
+                    written: 0,
+                }
+            }))
+        })
+    }
+}
+
+fn negotiate(req: &ServiceRequest) -> ContentEncoding {
+    let Ok(accept_encoding) = AcceptEncoding::parse(req.request()) else {
+        return ContentEncoding::Identity;
+    };
+
+    match accept_encoding.negotiate(SUPPORTED_ENCODINGS.iter()) {
+        Some(Encoding::Known(encoding)) => encoding,
+        _ => ContentEncoding::Identity,
+    }
+}
+
+struct RatioMetric {
+    original_size: u64,
+    encoding: ContentEncoding,
+    compression_ratio: Arc<Family<CompressionLabel, Histogram>>,
+}
+
+pin_project! {
+    pub struct MeteredBody<B> {
+        #[pin]
+        body: B,
+        metric: Option<RatioMetric>,
+        written: u64,
+    }
+}
+
+impl<B: MessageBody> MessageBody for MeteredBody<B> {
+    type Error = B::Error;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.project();
+
+        let poll = this.body.poll_next(cx);
+
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => *this.written += chunk.len() as u64,
+            Poll::Ready(None) => {
+                if let Some(metric) = this.metric.take() {
+                    metric
+                        .compression_ratio
+                        .get_or_create(&CompressionLabel {
+                            encoding: metric.encoding.as_str().to_string(),
+                        })
+                        .observe(*this.written as f64 / metric.original_size as f64);
+                }
+            }
+            _ => {}
+        }
+
+        poll
+    }
+}
@@ -0,0 +1,124 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    dev::{
+        forward_ready, Decompress, Payload, Service, ServiceRequest, ServiceResponse, Transform,
+    },
+    error::PayloadError,
+    http::header,
+    Error, HttpMessage,
+};
+use futures_util::{future::LocalBoxFuture, StreamExt};
+use serde::Serialize;
+
+use crate::response::unsupported_media_type;
+
+/// Content codings request bodies may be sent with.
+const SUPPORTED_ENCODINGS: [&str; 5] = ["gzip", "deflate", "br", "zstd", "identity"];
+
+#[derive(Serialize)]
+struct EncodingDetail {
+    field: &'static str,
+    message: &'static str,
+}
+
+/// Decodes request bodies sent with a `Content-Encoding` before they reach the extractors and
+/// fails with a payload overflow once the decoded body grows past `limit` bytes, so a small
+/// compressed payload cannot expand into an arbitrarily large one. Bodies in any other encoding
+/// are rejected with a 415.
+#[derive(Clone)]
+pub struct Decompression {
+    limit: usize,
+}
+
+impl Decompression {
+    pub fn new(limit: usize) -> Self {
+        Decompression { limit }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Decompression
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = DecompressionMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DecompressionMiddleware {
+            service,
+            limit: self.limit,
+        }))
+    }
+}
+
+pub struct DecompressionMiddleware<S> {
+    service: S,
+    limit: usize,
+}
+
+impl<S, B> Service<ServiceRequest> for DecompressionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        if let Some(encoding) = req.headers().get(header::CONTENT_ENCODING) {
+            let supported = encoding.to_str().is_ok_and(|encoding| {
+                SUPPORTED_ENCODINGS
+                    .iter()
+                    .any(|supported| encoding.trim().eq_ignore_ascii_case(supported))
+            });
+            if !supported {
+                let err = unsupported_media_type(
+                    format!(
+                        "Unsupported content encoding {}",
+                        String::from_utf8_lossy(encoding.as_bytes())
+                    ),
+                    &[EncodingDetail {
+                        field: "content-encoding",
+                        message: "must be one of gzip, deflate, br, zstd or identity",
+                    }],
+                );
+                return Box::pin(async move { Err(err) });
+            }
+
+            let limit = self.limit;
+            let decoded = Decompress::from_headers(req.take_payload(), req.headers());
+
+            let mut size = 0;
+            let limited = decoded.map(move |chunk| {
+                let chunk = chunk?;
+                size += chunk.len();
+                if size > limit {
+                    return Err(PayloadError::Overflow);
+                }
+                Ok(chunk)
+            });
+
+            // the body handed to the extractors is already decoded, so drop the headers that
+            // describe the encoded one
+            let headers = req.headers_mut();
+            headers.remove(header::CONTENT_ENCODING);
+            headers.remove(header::CONTENT_LENGTH);
+
+            req.set_payload(Payload::Stream {
+                payload: Box::pin(limited),
+            });
+        }
+
+        Box::pin(self.service.call(req))
+    }
+}
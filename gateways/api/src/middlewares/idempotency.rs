@@ -0,0 +1,282 @@
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::PayloadError,
+    http::{header::HeaderName, Method, StatusCode},
+    web::{self, BytesMut},
+    Error, HttpMessage, HttpResponse,
+};
+use application::{
+    context::AppContext,
+    idempotency,
+    ports::{IdempotencyClaim, IdempotencyKey, StoredResponse},
+};
+use futures_util::{future::LocalBoxFuture, StreamExt};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::extractors::CALLER;
+use crate::response::{bad_request, conflict, internal_server_error, unprocessable_entity};
+use crate::versioning::ApiVersion;
+
+/// Header a client sets to make a `POST` safe to retry.
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Header set on responses replayed from a previous request.
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_KEY_LENGTH: usize = 255;
+
+#[derive(Serialize)]
+struct KeyDetail {
+    field: &'static str,
+    message: &'static str,
+}
+
+/// Hash identifying the request a key was used for: its method, the API version it was served
+/// by, the route it matched below the version and its body. A key reused for another route is
+/// rejected like one reused with another payload, whether the version is in the path or not.
+pub fn fingerprint(method: &Method, version: &str, route: &str, body: &[u8]) -> String {
+    Sha256::new()
+        .chain_update(method.as_str())
+        .chain_update(b"\n")
+        .chain_update(version)
+        .chain_update(b"\n")
+        .chain_update(route)
+        .chain_update(b"\n")
+        .chain_update(body)
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Request extension set by a handler that failed to record the request in the history, whose
+/// response is not the one a retry would get.
+#[derive(Clone, Copy, Debug)]
+pub struct Unrecorded;
+
+/// Runs a `POST` carrying an [`IDEMPOTENCY_KEY`] once per key and caller: the key is claimed
+/// for `in_flight`, at least as long as the request may run, then the response is stored for
+/// `ttl` and replayed to retries. A retry arriving while the original request is still running
+/// is rejected with `409 Conflict`, and a key reused with another payload or on another route
+/// with `422 Unprocessable Entity`. The key of a request marked [`Unrecorded`] is released
+/// instead, for a retry to run it again. Responses are kept in the [`AppContext`] idempotency
+/// store; bodies larger than `limit` bytes are rejected with a payload overflow.
+#[derive(Clone)]
+pub struct Idempotency {
+    ttl: Duration,
+    in_flight: Duration,
+    limit: usize,
+}
+
+impl Idempotency {
+    pub fn new(ttl: Duration, in_flight: Duration, limit: usize) -> Self {
+        Idempotency {
+            ttl,
+            in_flight,
+            limit,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Idempotency
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = IdempotencyMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotencyMiddleware {
+            service: Rc::new(service),
+            ttl: self.ttl,
+            in_flight: self.in_flight,
+            limit: self.limit,
+        }))
+    }
+}
+
+pub struct IdempotencyMiddleware<S> {
+    service: Rc<S>,
+    ttl: Duration,
+    in_flight: Duration,
+    limit: usize,
+}
+
+impl<S, B> Service<ServiceRequest> for IdempotencyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let context = req.app_data::<web::Data<AppContext>>().cloned();
+        let key = req.headers().get(IDEMPOTENCY_KEY).cloned();
+
+        let (Some(context), Some(key), &Method::POST) = (context, key, req.method()) else {
+            return Box::pin(async move { Ok(service.call(req).await?.map_into_boxed_body()) });
+        };
+
+        let ttl = self.ttl;
+        let in_flight = self.in_flight;
+        let limit = self.limit;
+
+        Box::pin(async move {
+            let key = match key.to_str() {
+                Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key.to_string(),
+                _ => {
+                    return Err(bad_request(
+                        "Invalid idempotency key",
+                        &[KeyDetail {
+                            field: "idempotency-key",
+                            message: "must be 1 to 255 visible ASCII characters",
+                        }],
+                    ))
+                }
+            };
+            let key = IdempotencyKey {
+                caller: req
+                    .headers()
+                    .get(CALLER)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string(),
+                key,
+            };
+
+            let mut payload = req.take_payload();
+            let mut body = BytesMut::new();
+            while let Some(chunk) = payload.next().await {
+                let chunk = chunk?;
+                if body.len() + chunk.len() > limit {
+                    return Err(PayloadError::Overflow.into());
+                }
+                body.extend_from_slice(&chunk);
+            }
+            let body = body.freeze();
+            let route = req
+                .match_pattern()
+                .unwrap_or_else(|| req.path().to_string());
+            let version = req.extensions().get::<Arc<ApiVersion>>().cloned();
+            let (version, route) = match &version {
+                Some(version) => (
+                    version.name.as_str(),
+                    route
+                        .strip_prefix(&version.path())
+                        .filter(|route| route.starts_with('/'))
+                        .unwrap_or(&route),
+                ),
+                None => ("", route.as_str()),
+            };
+            let fingerprint = fingerprint(req.method(), version, route, &body);
+
+            match idempotency::claim(&context, &key, &fingerprint, in_flight)
+                .await
+                .map_err(internal_server_error)?
+            {
+                IdempotencyClaim::Claimed => {}
+                IdempotencyClaim::InFlight => {
+                    return Err(conflict(
+                        "A request with this idempotency key is still in progress",
+                    ))
+                }
+                IdempotencyClaim::Mismatch => {
+                    return Err(unprocessable_entity(
+                        "The idempotency key was used with a different payload or route",
+                    ))
+                }
+                IdempotencyClaim::Completed(stored) => {
+                    return Ok(req.into_response(replay(stored)));
+                }
+            }
+
+            req.set_payload(Payload::from(body));
+
+            // frees the key if the request fails, is cancelled or is not recorded
+            let mut claim = Claim {
+                context: context.clone(),
+                key: Some(key.clone()),
+            };
+
+            let res = service.call(req).await?;
+            let (req, res) = res.into_parts();
+            if req.extensions().contains::<Unrecorded>() {
+                return Ok(ServiceResponse::new(req, res.map_into_boxed_body()));
+            }
+            let (head, res_body) = res.into_parts();
+            let res_body = body::to_bytes(res_body).await.map_err(|err| {
+                let err: Box<dyn std::error::Error> = err.into();
+                internal_server_error(err)
+            })?;
+
+            let stored = StoredResponse {
+                status: head.status().as_u16(),
+                headers: head
+                    .headers()
+                    .iter()
+                    .filter_map(|(name, value)| {
+                        Some((name.to_string(), value.to_str().ok()?.to_string()))
+                    })
+                    .collect(),
+                body: res_body.to_vec(),
+            };
+            match idempotency::complete(&context, &key, &stored, ttl).await {
+                Ok(()) => claim.key = None,
+                Err(err) => tracing::warn!("failed to store idempotent response: {err}"),
+            }
+
+            let res = head.set_body(res_body).map_into_boxed_body();
+            Ok(ServiceResponse::new(req, res))
+        })
+    }
+}
+
+fn replay(stored: StoredResponse) -> HttpResponse {
+    let mut res = HttpResponse::build(
+        StatusCode::from_u16(stored.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+    );
+    for (name, value) in &stored.headers {
+        res.append_header((name.as_str(), value.as_str()));
+    }
+    res.insert_header((IDEMPOTENT_REPLAYED, "true"))
+        .body(stored.body)
+}
+
+/// A claimed key, released in the background unless the response was stored.
+struct Claim {
+    context: web::Data<AppContext>,
+    key: Option<IdempotencyKey>,
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let context = self.context.clone();
+            actix_web::rt::spawn(async move {
+                if let Err(err) = idempotency::release(&context, &key).await {
+                    tracing::warn!("failed to release idempotency key: {err}");
+                }
+            });
+        }
+    }
+}
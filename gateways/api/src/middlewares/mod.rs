@@ -1,4 +1,7 @@
+pub mod compression;
+pub mod decompression;
 pub mod error_header;
+pub mod idempotency;
 pub mod metrics;
 pub mod timeout;
 pub mod tracing;
@@ -1,14 +1,26 @@
 use std::{
-    future::{ready, Ready},
+    future::{ready, Future, Ready},
+    pin::Pin,
+    task::{Context, Poll},
     time::Duration,
 };
 
 use actix_web::{
+    body::{BodySize, MessageBody},
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::StatusCode,
+    web::Bytes,
     Error,
 };
 use futures_util::future::LocalBoxFuture;
+use pin_project_lite::pin_project;
+use tokio::time::{Instant, Sleep};
 
+/// Fails requests whose first byte takes longer than `duration` to be produced, and aborts
+/// response bodies that then stay idle for longer than `duration` between two chunks.
+/// Streaming responses can therefore stay open as long as they keep sending data. Upgraded
+/// connections (e.g. WebSockets) are only limited until the upgrade and then rely on their own
+/// heartbeats.
 #[derive(Clone)]
 pub struct Timeout {
     duration: Duration,
@@ -24,9 +36,9 @@ impl<S, B> Transform<S, ServiceRequest> for Timeout
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
-    B: 'static,
+    B: MessageBody + 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<TimeoutBody<B>>;
     type Error = Error;
     type InitError = ();
     type Transform = TimeoutMiddleware<S>;
@@ -49,9 +61,9 @@ impl<S, B> Service<ServiceRequest> for TimeoutMiddleware<S>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
-    B: 'static,
+    B: MessageBody + 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<TimeoutBody<B>>;
     type Error = Error;
     type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
 
@@ -59,13 +71,73 @@ where
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let duration = self.duration;
+        let deadline = Instant::now() + duration;
         let fut = self.service.call(req);
 
         Box::pin(async move {
-            match tokio::time::timeout(duration, fut).await {
-                Ok(r) => r,
+            match tokio::time::timeout_at(deadline, fut).await {
+                Ok(res) => Ok(res?.map_body(|head, body| TimeoutBody {
+                    body,
+                    deadline: tokio::time::sleep_until(deadline),
+                    idle: (head.status != StatusCode::SWITCHING_PROTOCOLS).then_some(duration),
+                })),
                 Err(_) => Err(actix_web::error::ErrorRequestTimeout("timeout")),
             }
         })
     }
 }
+
+#[derive(Debug)]
+pub struct BodyTimeout;
+
+impl std::fmt::Display for BodyTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("response body timed out")
+    }
+}
+
+impl std::error::Error for BodyTimeout {}
+
+pin_project! {
+    /// Response body that fails once no chunk was produced before its deadline. The first
+    /// deadline is shared with the response head; each chunk pushes it `idle` further away.
+    /// Without `idle` the body is never timed out.
+    pub struct TimeoutBody<B> {
+        #[pin]
+        body: B,
+        #[pin]
+        deadline: Sleep,
+        idle: Option<Duration>,
+    }
+}
+
+impl<B: MessageBody> MessageBody for TimeoutBody<B> {
+    type Error = Box<dyn std::error::Error>;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let mut this = self.project();
+
+        match this.body.poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(idle) = this.idle {
+                    this.deadline.as_mut().reset(Instant::now() + *idle);
+                }
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err.into()))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending if this.idle.is_none() => Poll::Pending,
+            Poll::Pending => match this.deadline.poll(cx) {
+                Poll::Ready(()) => Poll::Ready(Some(Err(Box::new(BodyTimeout)))),
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use actix_web::{web, HttpResponse, Responder};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+use crate::{response, routes, server};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "{{project-name}}"),
+    paths(
+        server::healthcheck,
+        routes::readiness,
+        routes::reply,
+        routes::reply_batch,
+        routes::reply_stream,
+        routes::history
+    ),
+    components(schemas(
+        response::ErrorResponse,
+        routes::ReplyRequest,
+        routes::ReplyResponse,
+        routes::BatchReplyRequest,
+        routes::ReplyResult,
+        routes::BatchReplyResponse,
+        routes::ReadinessResponse,
+        routes::CheckResult,
+        routes::HistoryEntry,
+        routes::HistoryResponse
+    ))
+)]
+pub struct ApiDoc;
+
+/// Page rendering the OpenAPI document, served at `/docs` when enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocsUi {
+    Disabled,
+    SwaggerUi,
+}
+
+/// Registers `/openapi.json` and, when a UI is enabled, the `/docs` page. The Swagger UI
+/// assets are embedded in the binary, so the page loads no third-party script.
+pub fn configure(ui: DocsUi) -> impl Fn(&mut web::ServiceConfig) + Clone {
+    move |cfg| {
+        cfg.route("/openapi.json", web::get().to(openapi_json));
+
+        if ui == DocsUi::SwaggerUi {
+            // the page loads its assets relative to `/docs/`
+            cfg.service(web::redirect("/docs", "/docs/"))
+                .service(SwaggerUi::new("/docs/{_:.*}").config(Config::new(["/openapi.json"])));
+        }
+    }
+}
+
+async fn openapi_json() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(ApiDoc::openapi().to_pretty_json().unwrap())
+}
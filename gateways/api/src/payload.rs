@@ -0,0 +1,206 @@
+use std::fmt::Display;
+
+use actix_web::{
+    dev::ServiceResponse,
+    error::{self, JsonPayloadError, PayloadError, UrlencodedError},
+    http::{header, StatusCode},
+    middleware::ErrorHandlerResponse,
+    web, HttpRequest, Resource, ResponseError,
+};
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family},
+    registry::Registry,
+};
+use serde::Serialize;
+
+use crate::middlewares::error_header::add_error_header;
+use crate::response::{bad_request, payload_too_large, unsupported_media_type};
+
+/// Maximum accepted body size, in bytes, for each kind of payload extractor.
+#[derive(Clone, Debug)]
+pub struct PayloadLimits {
+    pub json: usize,
+    pub form: usize,
+    pub payload: usize,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RejectedPayloadLabel {
+    pub path: String,
+    pub kind: String,
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+struct RejectionDetail {
+    reason: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<usize>,
+}
+
+impl RejectionDetail {
+    fn new(reason: &'static str) -> Self {
+        RejectionDetail {
+            reason,
+            limit: None,
+            line: None,
+            column: None,
+        }
+    }
+
+    fn with_limit(reason: &'static str, limit: usize) -> Self {
+        RejectionDetail {
+            limit: Some(limit),
+            ..RejectionDetail::new(reason)
+        }
+    }
+}
+
+/// Builds the extractor configurations for a set of [`PayloadLimits`] and turns their errors
+/// into structured responses, counting every rejected payload by kind and reason.
+#[derive(Clone)]
+pub struct PayloadRejections {
+    rejected_payloads: Family<RejectedPayloadLabel, Counter>,
+}
+
+impl PayloadRejections {
+    pub fn new(registry: &mut Registry) -> Self {
+        let rejected_payloads = Family::<RejectedPayloadLabel, Counter>::default();
+
+        registry.register(
+            "rejected_payload_count",
+            "Number of request payloads rejected by the extractors",
+            rejected_payloads.clone(),
+        );
+
+        PayloadRejections { rejected_payloads }
+    }
+
+    pub fn json_config(&self, limit: usize) -> web::JsonConfig {
+        let rejections = self.clone();
+        web::JsonConfig::default()
+            .limit(limit)
+            .error_handler(move |err, req| rejections.reject_json(err, req))
+    }
+
+    pub fn form_config(&self, limit: usize) -> web::FormConfig {
+        let rejections = self.clone();
+        web::FormConfig::default()
+            .limit(limit)
+            .error_handler(move |err, req| rejections.reject_form(err, req))
+    }
+
+    pub fn payload_config(&self, limit: usize) -> web::PayloadConfig {
+        web::PayloadConfig::new(limit)
+    }
+
+    /// Overrides the global limits for a single resource.
+    pub fn limit(&self, resource: Resource, limits: &PayloadLimits) -> Resource {
+        resource
+            .app_data(self.json_config(limits.json))
+            .app_data(self.form_config(limits.form))
+            .app_data(self.payload_config(limits.payload))
+    }
+
+    /// Error handler for `413 Payload Too Large` responses. Raw payload extractors have no
+    /// error hook of their own, so their overflow errors are rewritten here; everything else
+    /// is passed on to [`add_error_header`].
+    pub fn handle_payload_too_large<B>(
+        &self,
+        res: ServiceResponse<B>,
+    ) -> actix_web::Result<ErrorHandlerResponse<B>> {
+        let is_raw_overflow = res
+            .response()
+            .error()
+            .and_then(|err| err.as_error::<PayloadError>())
+            .is_some_and(|err| matches!(err, PayloadError::Overflow));
+
+        if !is_raw_overflow {
+            return add_error_header(res);
+        }
+
+        self.count(res.request(), "payload", "overflow");
+
+        let mut response = payload_too_large(
+            "Request payload is too large",
+            &[RejectionDetail::new("overflow")],
+        )
+        .error_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+
+        Ok(ErrorHandlerResponse::Response(
+            res.into_response(response).map_into_right_body(),
+        ))
+    }
+
+    fn reject_json(&self, err: JsonPayloadError, req: &HttpRequest) -> error::Error {
+        let (reason, detail) = match &err {
+            JsonPayloadError::OverflowKnownLength { limit, .. }
+            | JsonPayloadError::Overflow { limit } => {
+                ("overflow", RejectionDetail::with_limit("overflow", *limit))
+            }
+            JsonPayloadError::ContentType => ("content_type", RejectionDetail::new("content_type")),
+            JsonPayloadError::Deserialize(err) => {
+                let reason = if err.is_data() { "invalid" } else { "syntax" };
+                let detail = RejectionDetail {
+                    line: Some(err.line()),
+                    column: Some(err.column()),
+                    ..RejectionDetail::new(reason)
+                };
+                (reason, detail)
+            }
+            JsonPayloadError::Payload(PayloadError::Overflow) => {
+                ("overflow", RejectionDetail::new("overflow"))
+            }
+            _ => ("payload", RejectionDetail::new("payload")),
+        };
+
+        self.count(req, "json", reason);
+        reject(err.status_code(), &err, detail)
+    }
+
+    fn reject_form(&self, err: UrlencodedError, req: &HttpRequest) -> error::Error {
+        let (reason, detail) = match &err {
+            UrlencodedError::Overflow { limit, .. } => {
+                ("overflow", RejectionDetail::with_limit("overflow", *limit))
+            }
+            UrlencodedError::ContentType => ("content_type", RejectionDetail::new("content_type")),
+            UrlencodedError::Parse(_) | UrlencodedError::Encoding => {
+                ("syntax", RejectionDetail::new("syntax"))
+            }
+            UrlencodedError::Payload(PayloadError::Overflow) => {
+                ("overflow", RejectionDetail::new("overflow"))
+            }
+            _ => ("payload", RejectionDetail::new("payload")),
+        };
+
+        self.count(req, "form", reason);
+        reject(err.status_code(), &err, detail)
+    }
+
+    fn count(&self, req: &HttpRequest, kind: &str, reason: &str) {
+        self.rejected_payloads
+            .get_or_create(&RejectedPayloadLabel {
+                path: req.path().to_string(),
+                kind: kind.to_string(),
+                reason: reason.to_string(),
+            })
+            .inc();
+    }
+}
+
+fn reject<T: Display>(status: StatusCode, err: &T, detail: RejectionDetail) -> error::Error {
+    match status {
+        StatusCode::PAYLOAD_TOO_LARGE => payload_too_large(err, &[detail]),
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => unsupported_media_type(err, &[detail]),
+        _ => bad_request(err, &[detail]),
+    }
+}
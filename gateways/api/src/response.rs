@@ -2,18 +2,48 @@ use std::fmt::Display;
 
 use actix_web::error;
 use serde::Serialize;
-use serde_json::json;
+use serde_json::{json, Value};
+use utoipa::ToSchema;
+
+/// Body of every error response produced by the API.
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ErrorResponse {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<Object>>)]
+    pub details: Option<Vec<Value>>,
+}
+
+impl ErrorResponse {
+    pub fn new<T: Display>(err: T) -> Self {
+        ErrorResponse {
+            message: format!("{err}"),
+            details: None,
+        }
+    }
+
+    pub fn with_details<T: Display, F: Serialize>(err: T, details: &[F]) -> Self {
+        ErrorResponse {
+            message: format!("{err}"),
+            details: Some(details.iter().map(|d| json!(d)).collect()),
+        }
+    }
+}
 
 pub fn internal_server_error<T: Display>(err: T) -> error::Error {
-    error::ErrorInternalServerError(json!({
-        "message": format!("{err}")
-    }))
+    error::ErrorInternalServerError(json!(ErrorResponse::new(err)))
 }
 
 pub fn not_found<T: Display>(err: T) -> error::Error {
-    error::ErrorNotFound(json!({
-        "message": format!("{err}")
-    }))
+    error::ErrorNotFound(json!(ErrorResponse::new(err)))
+}
+
+pub fn conflict<T: Display>(err: T) -> error::Error {
+    error::ErrorConflict(json!(ErrorResponse::new(err)))
+}
+
+pub fn unprocessable_entity<T: Display>(err: T) -> error::Error {
+    error::ErrorUnprocessableEntity(json!(ErrorResponse::new(err)))
 }
 
 pub fn bad_request<T, F>(err: T, details: &[F]) -> error::Error
@@ -21,8 +51,21 @@ where
     T: Display,
     F: Serialize,
 {
-    error::ErrorBadRequest(json!({
-        "message": format!("{err}"),
-        "details": details
-    }))
+    error::ErrorBadRequest(json!(ErrorResponse::with_details(err, details)))
+}
+
+pub fn payload_too_large<T, F>(err: T, details: &[F]) -> error::Error
+where
+    T: Display,
+    F: Serialize,
+{
+    error::ErrorPayloadTooLarge(json!(ErrorResponse::with_details(err, details)))
+}
+
+pub fn unsupported_media_type<T, F>(err: T, details: &[F]) -> error::Error
+where
+    T: Display,
+    F: Serialize,
+{
+    error::ErrorUnsupportedMediaType(json!(ErrorResponse::with_details(err, details)))
 }
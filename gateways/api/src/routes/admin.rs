@@ -0,0 +1,142 @@
+use std::{sync::Arc, time::UNIX_EPOCH};
+
+use actix_web::{web, HttpResponse};
+use application::{catalog::CatalogEntry, ports::CatalogSource};
+use infrastructure::cache::{CacheAdmin, EntryInfo};
+use serde::{Deserialize, Serialize};
+
+use crate::response::ErrorResponse;
+
+#[derive(Serialize)]
+struct CatalogResponse<'a> {
+    source: Option<String>,
+    version: &'a str,
+    loaded_at: u64,
+    default_locale: &'a str,
+    entries: Vec<&'a CatalogEntry>,
+}
+
+/// Lists the active reply catalog entries, in match order, and the file version they came from.
+pub async fn catalog(catalog: web::Data<dyn CatalogSource>) -> HttpResponse {
+    let snapshot = catalog.current();
+
+    HttpResponse::Ok().json(CatalogResponse {
+        source: snapshot
+            .source
+            .as_ref()
+            .map(|path| path.display().to_string()),
+        version: &snapshot.version,
+        loaded_at: snapshot
+            .loaded_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        default_locale: snapshot.catalog.default_locale(),
+        entries: snapshot.catalog.entries().collect(),
+    })
+}
+
+#[derive(Serialize)]
+struct CacheResponse<'a> {
+    name: &'a str,
+    eviction: &'static str,
+    max_entries: usize,
+    ttl_ms: u128,
+    stale_while_revalidate_ms: u128,
+    size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entries: Option<Vec<EntryResponse>>,
+}
+
+impl<'a> CacheResponse<'a> {
+    fn new(cache: &'a dyn CacheAdmin, entries: Vec<EntryInfo>, listed: bool) -> Self {
+        let settings = cache.settings();
+        CacheResponse {
+            name: cache.name(),
+            eviction: settings.eviction.as_str(),
+            max_entries: settings.max_entries,
+            ttl_ms: settings.ttl.as_millis(),
+            stale_while_revalidate_ms: settings.stale_while_revalidate.as_millis(),
+            size: entries.len(),
+            entries: listed.then(|| entries.into_iter().map(EntryResponse::from).collect()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct EntryResponse {
+    key: String,
+    age_ms: u128,
+    state: &'static str,
+    hits: u64,
+}
+
+impl From<EntryInfo> for EntryResponse {
+    fn from(entry: EntryInfo) -> Self {
+        EntryResponse {
+            key: entry.key,
+            age_ms: entry.age.as_millis(),
+            state: entry.state,
+            hits: entry.hits,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PurgeQuery {
+    key: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PurgeResponse {
+    purged: usize,
+}
+
+fn find<'a>(caches: &'a [Arc<dyn CacheAdmin>], name: &str) -> Option<&'a dyn CacheAdmin> {
+    caches
+        .iter()
+        .find(|cache| cache.name() == name)
+        .map(|cache| cache.as_ref())
+}
+
+fn unknown_cache(name: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorResponse::new(format!("Unknown cache \"{name}\"")))
+}
+
+/// Lists the caches with their settings and sizes.
+pub async fn caches(caches: web::Data<Vec<Arc<dyn CacheAdmin>>>) -> HttpResponse {
+    HttpResponse::Ok().json(
+        caches
+            .iter()
+            .map(|cache| CacheResponse::new(cache.as_ref(), cache.entries(), false))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Lists the entries of a cache, from the next evicted to the last.
+pub async fn cache(
+    caches: web::Data<Vec<Arc<dyn CacheAdmin>>>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    match find(&caches, &name) {
+        Some(cache) => HttpResponse::Ok().json(CacheResponse::new(cache, cache.entries(), true)),
+        None => unknown_cache(&name),
+    }
+}
+
+/// Removes the entry of a cache given by the `key` parameter, or all of them without one.
+pub async fn purge_cache(
+    caches: web::Data<Vec<Arc<dyn CacheAdmin>>>,
+    name: web::Path<String>,
+    query: web::Query<PurgeQuery>,
+) -> HttpResponse {
+    let Some(cache) = find(&caches, &name) else {
+        return unknown_cache(&name);
+    };
+
+    let purged = match &query.key {
+        Some(key) => usize::from(cache.purge_key(key)),
+        None => cache.purge(),
+    };
+    HttpResponse::Ok().json(PurgeResponse { purged })
+}
@@ -0,0 +1,100 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use application::context::AppContext;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::Instrument;
+use utoipa::ToSchema;
+
+use crate::extractors::{AcceptedLocale, Metadata};
+use crate::localization::LocaleFallbacks;
+use crate::middlewares::idempotency::Unrecorded;
+use crate::response::{bad_request, ErrorResponse};
+use crate::routes::{ReplyRequest, ReplyResult};
+
+/// Limits applied to `POST /reply:batch`.
+#[derive(Clone, Copy, Debug)]
+pub struct BatchLimits {
+    pub max_size: usize,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct BatchReplyRequest {
+    #[schema(min_items = 1)]
+    requests: Vec<ReplyRequest>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct BatchReplyResponse {
+    results: Vec<ReplyResult>,
+}
+
+/// Reply to several messages at once
+///
+/// Results are returned in the order of the requests. A failing request is reported in its
+/// own result and does not fail the batch.
+#[utoipa::path(
+    post,
+    path = "/v1/reply:batch",
+    request_body = BatchReplyRequest,
+    params(
+        ("x-caller" = Option<String>, Header, description = "Principal making the request"),
+        ("accept-language" = Option<String>, Header, description = "Preferred reply locales, falling back to the catalog default"),
+        ("idempotency-key" = Option<String>, Header, description = "Replays the response of the first request sent with this key by the caller"),
+    ),
+    responses(
+        (status = 200, description = "Result of every request, in order", body = BatchReplyResponse),
+        (status = 400, description = "Malformed payload, empty or oversized batch", body = ErrorResponse),
+        (status = 409, description = "A request with the same idempotency key is in progress", body = ErrorResponse),
+        (status = 413, description = "Payload is too large", body = ErrorResponse),
+        (status = 422, description = "The idempotency key was used with another payload or route", body = ErrorResponse),
+    ),
+    tag = "messages"
+)]
+#[tracing::instrument(
+    name = "gateways.api.routes.reply_batch",
+    skip(req, request, context, fallbacks, limits),
+    fields(size = request.requests.len())
+)]
+pub async fn reply_batch(
+    req: HttpRequest,
+    request: web::Json<BatchReplyRequest>,
+    metadata: Metadata,
+    locale: AcceptedLocale,
+    context: web::Data<AppContext>,
+    fallbacks: web::Data<LocaleFallbacks>,
+    limits: web::Data<BatchLimits>,
+) -> actix_web::Result<HttpResponse> {
+    let requests = &request.requests;
+    if requests.is_empty() || requests.len() > limits.max_size {
+        return Err(bad_request(
+            format!("Batch must hold between 1 and {} requests", limits.max_size),
+            &[json!({ "size": requests.len(), "limit": limits.max_size })],
+        ));
+    }
+
+    let snapshot = context.catalog.current();
+
+    let mut results = Vec::with_capacity(requests.len());
+    for (index, request) in requests.iter().enumerate() {
+        let span = tracing::info_span!("gateways.api.routes.reply_batch.item", index);
+
+        results.push(
+            ReplyResult::new(
+                &context,
+                &snapshot.catalog,
+                request,
+                &locale.0,
+                &metadata.0,
+                &fallbacks,
+            )
+            .instrument(span)
+            .await,
+        );
+    }
+
+    if results.iter().any(ReplyResult::unrecorded) {
+        req.extensions_mut().insert(Unrecorded);
+    }
+
+    Ok(HttpResponse::Ok().json(BatchReplyResponse { results }))
+}
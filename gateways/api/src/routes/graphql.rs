@@ -0,0 +1,268 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use application::{
+    context::AppContext,
+    locale::Locale,
+    messages::{self, ReplyError},
+    template::RequestMetadata,
+};
+use async_graphql::{
+    extensions::{
+        apollo_persisted_queries::{ApolloPersistedQueries, LruCacheStorage},
+        Tracing,
+    },
+    http::parse_query_string,
+    BatchRequest, BatchResponse, Context, EmptyMutation, EmptySubscription, ErrorExtensions,
+    Object, Schema, SimpleObject,
+};
+use serde_json::json;
+use tracing::Instrument;
+use validator::Validate;
+
+use crate::extractors::{field_errors, AcceptedLocale, Metadata};
+use crate::localization::LocaleFallbacks;
+use crate::response::bad_request;
+use crate::routes::{BatchLimits, ReplyRequest};
+
+/// Settings of `/graphql`.
+#[derive(Clone, Debug)]
+pub struct GraphQLConfig {
+    pub max_depth: usize,
+    pub max_complexity: usize,
+    /// Number of persisted queries kept; the least recently used ones are dropped first.
+    pub persisted_query_cache_size: usize,
+    /// Operations accepted in one batched `POST /graphql`.
+    pub max_batch_operations: usize,
+}
+
+pub type ReplySchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Schema and settings shared by the `/graphql` handlers.
+pub struct GraphQL {
+    schema: ReplySchema,
+    max_batch_operations: usize,
+}
+
+impl GraphQL {
+    pub fn new(
+        config: &GraphQLConfig,
+        context: AppContext,
+        fallbacks: LocaleFallbacks,
+        limits: BatchLimits,
+    ) -> Self {
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+            .limit_depth(config.max_depth)
+            .limit_complexity(config.max_complexity)
+            .extension(Tracing)
+            .extension(ApolloPersistedQueries::new(LruCacheStorage::new(
+                config.persisted_query_cache_size,
+            )))
+            .data(context)
+            .data(fallbacks)
+            .data(limits)
+            .finish();
+
+        GraphQL {
+            schema,
+            max_batch_operations: config.max_batch_operations,
+        }
+    }
+}
+
+/// A reply and the locale it is written in.
+#[derive(SimpleObject)]
+pub struct Reply {
+    message: String,
+    locale: String,
+}
+
+/// Why a message of a batch could not be answered. `code` is the one a single `reply` query
+/// reports in its error extensions.
+#[derive(SimpleObject)]
+pub struct ReplyFailure {
+    code: String,
+    message: String,
+}
+
+/// Outcome of one message of a batch: either `reply` or `error` is set.
+#[derive(SimpleObject)]
+pub struct BatchReplyResult {
+    reply: Option<Reply>,
+    error: Option<ReplyFailure>,
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Reply to a known message
+    async fn reply(&self, ctx: &Context<'_>, message: String) -> async_graphql::Result<Reply> {
+        answer(ctx, message).await
+    }
+
+    /// Reply to several messages at once
+    ///
+    /// Results are returned in the order of the messages. A failing message is reported in
+    /// its own result and does not fail the batch.
+    #[graphql(complexity = "messages.len() * child_complexity")]
+    async fn batch_reply(
+        &self,
+        ctx: &Context<'_>,
+        messages: Vec<String>,
+    ) -> async_graphql::Result<Vec<BatchReplyResult>> {
+        let limits = ctx.data::<BatchLimits>()?;
+        if messages.is_empty() || messages.len() > limits.max_size {
+            return Err(async_graphql::Error::new(format!(
+                "Batch must hold between 1 and {} messages",
+                limits.max_size
+            ))
+            .extend_with(|_, extensions| {
+                extensions.set("code", "BAD_USER_INPUT");
+                extensions.set("size", messages.len());
+                extensions.set("limit", limits.max_size);
+            }));
+        }
+
+        let mut results = Vec::with_capacity(messages.len());
+        for (index, message) in messages.into_iter().enumerate() {
+            let span = tracing::info_span!("gateways.api.routes.graphql.batch_item", index);
+
+            results.push(match answer(ctx, message).instrument(span).await {
+                Ok(reply) => BatchReplyResult {
+                    reply: Some(reply),
+                    error: None,
+                },
+                Err(err) => BatchReplyResult {
+                    reply: None,
+                    error: Some(ReplyFailure {
+                        code: match err.extensions.as_ref().and_then(|e| e.get("code")) {
+                            Some(async_graphql::Value::String(code)) => code.clone(),
+                            _ => "INTERNAL".to_string(),
+                        },
+                        message: err.message,
+                    }),
+                },
+            });
+        }
+
+        Ok(results)
+    }
+}
+
+/// Validates, answers and records `message` with the locale and metadata of the HTTP request,
+/// reporting failures with a `code` extension.
+async fn answer(ctx: &Context<'_>, message: String) -> async_graphql::Result<Reply> {
+    let request = ReplyRequest { message };
+    if let Err(errors) = request.validate() {
+        let details = json!(field_errors(&errors));
+        return Err(
+            async_graphql::Error::new("Validation failed").extend_with(|_, extensions| {
+                extensions.set("code", "BAD_USER_INPUT");
+                if let Ok(details) = async_graphql::Value::from_json(details) {
+                    extensions.set("details", details);
+                }
+            }),
+        );
+    }
+
+    let context = ctx.data::<AppContext>()?;
+    let fallbacks = ctx.data::<LocaleFallbacks>()?;
+
+    let metadata = ctx.data::<RequestMetadata>()?;
+
+    let snapshot = context.catalog.current();
+    let result = messages::reply(
+        &snapshot.catalog,
+        &request.message,
+        ctx.data::<Locale>()?,
+        metadata,
+    );
+
+    messages::record(context, &request.message, &result, metadata)
+        .await
+        .map_err(|err| {
+            async_graphql::Error::new(err.to_string())
+                .extend_with(|_, extensions| extensions.set("code", "INTERNAL"))
+        })?;
+
+    match result {
+        Ok(reply) => {
+            fallbacks.record(&snapshot.catalog, &reply);
+            Ok(Reply {
+                message: reply.text,
+                locale: reply.locale,
+            })
+        }
+        Err(err) => match &err {
+            ReplyError::UnknownMessage(_, localized) => {
+                fallbacks.record(&snapshot.catalog, localized);
+                let locale = localized.locale.clone();
+                Err(
+                    async_graphql::Error::new(err.to_string()).extend_with(move |_, extensions| {
+                        extensions.set("code", "UNKNOWN_MESSAGE");
+                        extensions.set("locale", locale.as_str());
+                    }),
+                )
+            }
+        },
+    }
+}
+
+/// Execute GraphQL operations sent as JSON, one or a batch of up to `max_batch_operations`
+#[tracing::instrument(name = "gateways.api.routes.graphql", skip_all)]
+pub async fn graphql(
+    request: web::Json<BatchRequest>,
+    metadata: Metadata,
+    locale: AcceptedLocale,
+    graphql: web::Data<GraphQL>,
+) -> actix_web::Result<HttpResponse> {
+    if let BatchRequest::Batch(operations) = &*request {
+        let limit = graphql.max_batch_operations;
+        if operations.len() > limit {
+            return Err(bad_request(
+                format!("Batch must hold at most {limit} operations"),
+                &[json!({ "size": operations.len(), "limit": limit })],
+            ));
+        }
+    }
+
+    let request = request.into_inner().data(locale.0).data(metadata.0);
+
+    Ok(respond(graphql.schema.execute_batch(request).await))
+}
+
+/// Execute a GraphQL query sent in the query string, as persisted queries usually are
+#[tracing::instrument(name = "gateways.api.routes.graphql", skip_all)]
+pub async fn graphql_get(
+    req: HttpRequest,
+    metadata: Metadata,
+    locale: AcceptedLocale,
+    graphql: web::Data<GraphQL>,
+) -> actix_web::Result<HttpResponse> {
+    if req.query_string().is_empty() {
+        return Err(bad_request(
+            "Missing GraphQL query",
+            &[json!({ "reason": "query" })],
+        ));
+    }
+
+    let request = parse_query_string(req.query_string())
+        .map_err(|err| bad_request(err, &[json!({ "reason": "query" })]))?
+        .data(locale.0)
+        .data(metadata.0);
+
+    Ok(respond(
+        graphql
+            .schema
+            .execute_batch(BatchRequest::Single(request))
+            .await,
+    ))
+}
+
+fn respond(response: BatchResponse) -> HttpResponse {
+    let mut builder = HttpResponse::Ok();
+    for (name, value) in response.http_headers_iter() {
+        builder.append_header((name.as_str(), value.as_bytes()));
+    }
+
+    builder.json(response)
+}
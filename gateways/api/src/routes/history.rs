@@ -0,0 +1,148 @@
+use std::time::SystemTime;
+
+use actix_web::{web, HttpResponse};
+use application::{
+    context::AppContext,
+    history,
+    ports::{HistoryCursor, HistoryFilter, MessageRecord},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+use crate::extractors::Validated;
+use crate::response::{internal_server_error, ErrorResponse};
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+#[derive(Deserialize, Validate, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    /// Only exchanges of this caller
+    #[param(example = "alice")]
+    caller: Option<String>,
+    /// Only exchanges at or after this RFC 3339 timestamp
+    #[validate(custom(function = "validate_timestamp"))]
+    #[param(example = "2024-01-01T00:00:00Z")]
+    from: Option<String>,
+    /// Only exchanges before this RFC 3339 timestamp
+    #[validate(custom(function = "validate_timestamp"))]
+    #[param(example = "2024-02-01T00:00:00Z")]
+    to: Option<String>,
+    /// `next_cursor` of the previous page
+    #[validate(custom(function = "validate_cursor"))]
+    cursor: Option<String>,
+    /// Number of exchanges per page, 20 by default
+    #[validate(range(min = 1, max = 100))]
+    #[param(minimum = 1, maximum = 100, example = 20)]
+    limit: Option<usize>,
+}
+
+fn validate_timestamp(value: &str) -> Result<(), ValidationError> {
+    parse_timestamp(value)
+        .map(|_| ())
+        .ok_or_else(|| ValidationError::new("rfc3339").with_message("Invalid timestamp".into()))
+}
+
+fn validate_cursor(value: &str) -> Result<(), ValidationError> {
+    value
+        .parse::<HistoryCursor>()
+        .map(|_| ())
+        .map_err(|err| ValidationError::new("cursor").with_message(err.to_string().into()))
+}
+
+fn parse_timestamp(value: &str) -> Option<SystemTime> {
+    humantime::parse_rfc3339_weak(value).ok()
+}
+
+/// A recorded exchange: the reply served, or the error when the message was unknown.
+#[derive(Serialize, ToSchema, Debug)]
+pub struct HistoryEntry {
+    id: String,
+    #[schema(example = "ping")]
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "pong")]
+    reply: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[schema(example = "en")]
+    locale: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "alice")]
+    caller: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
+    /// RFC 3339 timestamp
+    #[schema(example = "2024-01-01T12:00:00.000000Z")]
+    created_at: String,
+}
+
+impl From<MessageRecord> for HistoryEntry {
+    fn from(record: MessageRecord) -> Self {
+        let (reply, error) = if record.known {
+            (Some(record.reply), None)
+        } else {
+            (None, Some(record.reply))
+        };
+
+        HistoryEntry {
+            id: record.id,
+            message: record.message,
+            reply,
+            error,
+            locale: record.locale,
+            caller: record.caller,
+            trace_id: record.trace_id,
+            created_at: humantime::format_rfc3339_micros(record.created_at).to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct HistoryResponse {
+    items: Vec<HistoryEntry>,
+    /// Cursor of the next page, absent on the last one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+/// List past exchanges
+///
+/// Exchanges are listed newest first, one page at a time: pass the `next_cursor` of a page as
+/// the `cursor` of the following request, with the same filters.
+#[utoipa::path(
+    get,
+    path = "/v1/history",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "A page of exchanges", body = HistoryResponse),
+        (status = 400, description = "Invalid query", body = ErrorResponse),
+    ),
+    tag = "messages"
+)]
+#[tracing::instrument(name = "gateways.api.routes.history", skip(context))]
+pub async fn history(
+    query: Validated<web::Query<HistoryQuery>>,
+    context: web::Data<AppContext>,
+) -> actix_web::Result<HttpResponse> {
+    let query = query.into_inner().into_inner();
+
+    let page = history::list(
+        &context,
+        HistoryFilter {
+            caller: query.caller,
+            from: query.from.as_deref().and_then(parse_timestamp),
+            to: query.to.as_deref().and_then(parse_timestamp),
+            after: query.cursor.and_then(|cursor| cursor.parse().ok()),
+            limit: query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+        },
+    )
+    .await
+    .map_err(internal_server_error)?;
+
+    Ok(HttpResponse::Ok().json(HistoryResponse {
+        items: page.records.into_iter().map(HistoryEntry::from).collect(),
+        next_cursor: page.next.map(|cursor| cursor.to_string()),
+    }))
+}
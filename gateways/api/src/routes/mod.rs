@@ -1,3 +1,15 @@
+pub mod admin;
+mod batch;
+pub mod graphql;
+mod history;
+mod readiness;
 mod reply;
+mod stream;
+mod ws;
 
+pub use batch::*;
+pub use history::*;
+pub use readiness::*;
 pub use reply::*;
+pub use stream::*;
+pub use ws::*;
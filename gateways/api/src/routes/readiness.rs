@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+use actix_web::{web, HttpResponse};
+use application::context::AppContext;
+use futures_util::future::join_all;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Outcome of one readiness check.
+#[derive(Serialize, ToSchema, Debug)]
+pub struct CheckResult {
+    #[schema(example = "database")]
+    name: String,
+    #[schema(example = "up")]
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Facts reported by the check, e.g. the instance holding each lease.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    #[schema(example = json!({"instance": "api-1", "retention": "api-2"}))]
+    details: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ReadinessResponse {
+    #[schema(example = "ready")]
+    status: &'static str,
+    checks: Vec<CheckResult>,
+}
+
+/// Report whether the service can handle requests
+///
+/// Runs every dependency check concurrently; the service is ready when all of them pass.
+#[utoipa::path(
+    get,
+    path = "/v1/readiness",
+    responses(
+        (status = 200, description = "Service is ready", body = ReadinessResponse),
+        (status = 503, description = "A dependency is unavailable", body = ReadinessResponse),
+    ),
+    tag = "health"
+)]
+#[tracing::instrument(name = "gateways.api.routes.readiness", skip_all)]
+pub async fn readiness(context: web::Data<AppContext>) -> HttpResponse {
+    let checks = join_all(context.readiness.iter().map(|check| async move {
+        let (checked, details) = futures_util::join!(check.check(), check.details());
+        match checked {
+            Ok(()) => CheckResult {
+                name: check.name().to_string(),
+                status: "up",
+                error: None,
+                details,
+            },
+            Err(err) => {
+                tracing::warn!(check = check.name(), error = %err, "readiness check failed");
+                CheckResult {
+                    name: check.name().to_string(),
+                    status: "down",
+                    error: Some(err.to_string()),
+                    details,
+                }
+            }
+        }
+    }))
+    .await;
+
+    let ready = checks.iter().all(|check| check.error.is_none());
+    let mut response = if ready {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+
+    response.json(ReadinessResponse {
+        status: if ready { "ready" } else { "unavailable" },
+        checks,
+    })
+}
@@ -1,23 +1,170 @@
-use actix_web::{web, HttpResponse};
-use application::messages::{self, ReplyError};
-use serde::Deserialize;
-use serde_json::json;
+use actix_web::{http::header, web, HttpMessage, HttpRequest, HttpResponse};
+use application::{
+    catalog::ReplyCatalog,
+    context::AppContext,
+    locale::Locale,
+    messages::{self, ReplyError},
+    template::RequestMetadata,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
 
-use crate::response::internal_server_error;
+use crate::extractors::{field_errors, AcceptedLocale, Metadata, Validated};
+use crate::localization::LocaleFallbacks;
+use crate::middlewares::idempotency::Unrecorded;
+use crate::response::{internal_server_error, ErrorResponse};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Validate, ToSchema, Debug)]
 pub struct ReplyRequest {
-    message: String,
+    #[validate(length(min = 1, max = 256))]
+    #[schema(min_length = 1, max_length = 256, example = "ping")]
+    pub(super) message: String,
 }
 
-#[tracing::instrument(name = "gateways.api.routes.reply")]
-pub async fn reply(request: web::Json<ReplyRequest>) -> actix_web::Result<HttpResponse> {
-    match messages::reply(&request.message) {
-        Ok(message) => Ok(HttpResponse::Ok().json(json!({
-            "message": message
-        }))),
-        Err(err) => Err(match err {
-            ReplyError::UnknownMessage(_) => internal_server_error(err),
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ReplyResponse {
+    #[schema(example = "pong")]
+    pub(super) message: String,
+}
+
+/// Outcome of a request answered without failing the surrounding response, as in a batch or
+/// a WebSocket session: the reply, or the error `POST /reply` would have answered with.
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ReplyResult {
+    #[schema(example = 200)]
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "pong")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "en")]
+    locale: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorResponse>,
+    /// The request could not be recorded in the history.
+    #[serde(skip)]
+    unrecorded: bool,
+}
+
+impl ReplyResult {
+    /// Validates, answers and records `request` in the history of `context`, recording locale
+    /// fallbacks.
+    pub async fn new(
+        context: &AppContext,
+        catalog: &ReplyCatalog,
+        request: &ReplyRequest,
+        locale: &Locale,
+        metadata: &RequestMetadata,
+        fallbacks: &LocaleFallbacks,
+    ) -> Self {
+        if let Err(errors) = request.validate() {
+            return ReplyResult::error(
+                400,
+                ErrorResponse::with_details("Validation failed", &field_errors(&errors)),
+            );
+        }
+
+        let result = messages::reply(catalog, &request.message, locale, metadata);
+        if let Err(err) = messages::record(context, &request.message, &result, metadata).await {
+            return ReplyResult {
+                unrecorded: true,
+                ..ReplyResult::error(500, ErrorResponse::new(err))
+            };
+        }
+
+        match result {
+            Ok(reply) => {
+                fallbacks.record(catalog, &reply);
+                ReplyResult {
+                    status: 200,
+                    message: Some(reply.text),
+                    locale: Some(reply.locale),
+                    error: None,
+                    unrecorded: false,
+                }
+            }
+            Err(err) => match &err {
+                ReplyError::UnknownMessage(_, localized) => {
+                    fallbacks.record(catalog, localized);
+                    ReplyResult {
+                        locale: Some(localized.locale.clone()),
+                        ..ReplyResult::error(500, ErrorResponse::new(err))
+                    }
+                }
+            },
+        }
+    }
+
+    pub fn error(status: u16, error: ErrorResponse) -> Self {
+        ReplyResult {
+            status,
+            message: None,
+            locale: None,
+            error: Some(error),
+            unrecorded: false,
+        }
+    }
+
+    /// Whether recording the request in the history failed, unlike the other errors not the
+    /// answer a retry would get.
+    pub fn unrecorded(&self) -> bool {
+        self.unrecorded
+    }
+}
+
+/// Reply to a known message
+#[utoipa::path(
+    post,
+    path = "/v1/reply",
+    request_body = ReplyRequest,
+    params(
+        ("x-caller" = Option<String>, Header, description = "Principal making the request"),
+        ("accept-language" = Option<String>, Header, description = "Preferred reply locales, falling back to the catalog default"),
+        ("idempotency-key" = Option<String>, Header, description = "Replays the response of the first request sent with this key by the caller"),
+    ),
+    responses(
+        (status = 200, description = "Reply to the message", body = ReplyResponse,
+            headers(("content-language" = String, description = "Locale the reply is written in"))),
+        (status = 400, description = "Malformed or invalid payload", body = ErrorResponse),
+        (status = 409, description = "A request with the same idempotency key is in progress", body = ErrorResponse),
+        (status = 413, description = "Payload is too large", body = ErrorResponse),
+        (status = 422, description = "The idempotency key was used with another payload or route", body = ErrorResponse),
+        (status = 500, description = "Unknown message", body = ErrorResponse),
+    ),
+    tag = "messages"
+)]
+#[tracing::instrument(name = "gateways.api.routes.reply", skip(req, fallbacks, context))]
+pub async fn reply(
+    req: HttpRequest,
+    request: Validated<web::Json<ReplyRequest>>,
+    metadata: Metadata,
+    locale: AcceptedLocale,
+    fallbacks: web::Data<LocaleFallbacks>,
+    context: web::Data<AppContext>,
+) -> actix_web::Result<HttpResponse> {
+    let snapshot = context.catalog.current();
+    let result = messages::reply(&snapshot.catalog, &request.message, &locale.0, &metadata.0);
+
+    if let Err(err) = messages::record(&context, &request.message, &result, &metadata.0).await {
+        req.extensions_mut().insert(Unrecorded);
+        return Err(internal_server_error(err));
+    }
+
+    match result {
+        Ok(reply) => {
+            fallbacks.record(&snapshot.catalog, &reply);
+            Ok(HttpResponse::Ok()
+                .insert_header((header::CONTENT_LANGUAGE, reply.locale))
+                .json(ReplyResponse {
+                    message: reply.text,
+                }))
+        }
+        Err(err) => Err(match &err {
+            ReplyError::UnknownMessage(_, localized) => {
+                fallbacks.record(&snapshot.catalog, localized);
+                internal_server_error(err)
+            }
         }),
     }
 }
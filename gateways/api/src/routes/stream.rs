@@ -0,0 +1,186 @@
+use std::{convert::Infallible, sync::Arc};
+
+use actix_web::{http::header, web, HttpResponse};
+use application::{
+    catalog::CatalogSnapshot,
+    context::AppContext,
+    locale::{Locale, Localized},
+    messages::{self, ReplyError},
+    ports::PortError,
+    template::RequestMetadata,
+};
+use futures_util::{stream, StreamExt};
+use serde::Deserialize;
+use tokio::{
+    sync::watch,
+    time::{self, Instant, Interval},
+};
+use utoipa::IntoParams;
+use validator::Validate;
+
+use crate::extractors::{AcceptedLocale, Metadata, Validated};
+use crate::localization::LocaleFallbacks;
+use crate::response::{internal_server_error, ErrorResponse};
+use crate::routes::ReplyResponse;
+use crate::streaming::{Event, OpenStream, StreamConfig, StreamMetrics, EVENT_STREAM};
+
+#[derive(Deserialize, Validate, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct StreamQuery {
+    /// Message to reply to
+    #[validate(length(min = 1, max = 256))]
+    #[param(min_length = 1, max_length = 256, example = "ping")]
+    message: String,
+}
+
+/// Stream the reply to a message
+///
+/// Sends a `reply` event (or an `error` event for an unknown message) right away and a new one
+/// whenever a catalog reload changes the reply. Keep-alive comments are sent while the reply
+/// does not change. Each event `id` is the catalog version it was rendered from. Every reply
+/// sent is recorded in the message history; the stream ends when one cannot be.
+#[utoipa::path(
+    get,
+    path = "/v1/reply/stream",
+    params(
+        StreamQuery,
+        ("x-caller" = Option<String>, Header, description = "Principal making the request"),
+        ("accept-language" = Option<String>, Header, description = "Preferred reply locales, falling back to the catalog default"),
+    ),
+    responses(
+        (status = 200, description = "Stream of `reply` and `error` events", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Invalid query", body = ErrorResponse),
+        (status = 500, description = "The reply could not be recorded", body = ErrorResponse),
+    ),
+    tag = "messages"
+)]
+#[tracing::instrument(
+    name = "gateways.api.routes.reply_stream",
+    skip(context, fallbacks, metrics, config)
+)]
+pub async fn reply_stream(
+    query: Validated<web::Query<StreamQuery>>,
+    metadata: Metadata,
+    locale: AcceptedLocale,
+    context: web::Data<AppContext>,
+    fallbacks: web::Data<LocaleFallbacks>,
+    metrics: web::Data<StreamMetrics>,
+    config: web::Data<StreamConfig>,
+) -> actix_web::Result<HttpResponse> {
+    let keep_alive = config.keep_alive;
+
+    let mut state = ReplyStream {
+        message: query.into_inner().into_inner().message,
+        locale: locale.0,
+        metadata: metadata.0,
+        catalog: context.catalog.subscribe(),
+        context,
+        fallbacks: fallbacks.into_inner(),
+        metrics: metrics.get_ref().clone(),
+        keep_alive: time::interval_at(Instant::now() + keep_alive, keep_alive),
+        last: None,
+        _open: metrics.open(),
+    };
+
+    let first = state.open().await.map_err(internal_server_error)?;
+    let events = stream::once(async move { first })
+        .chain(stream::unfold(state, |mut state| async move {
+            let event = state.next().await?;
+            Some((event, state))
+        }))
+        .map(Ok::<_, Infallible>);
+
+    Ok(HttpResponse::Ok()
+        .content_type(EVENT_STREAM)
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(events))
+}
+
+struct ReplyStream {
+    message: String,
+    locale: Locale,
+    metadata: RequestMetadata,
+    catalog: watch::Receiver<Arc<CatalogSnapshot>>,
+    context: web::Data<AppContext>,
+    fallbacks: Arc<LocaleFallbacks>,
+    metrics: StreamMetrics,
+    keep_alive: Interval,
+    /// Data of the last event sent, to skip reloads that leave the reply unchanged.
+    last: Option<String>,
+    _open: OpenStream,
+}
+
+impl ReplyStream {
+    /// First event of the stream, recorded before the response starts.
+    async fn open(&mut self) -> Result<web::Bytes, PortError> {
+        let snapshot = self.catalog.borrow_and_update().clone();
+        let (event, result) = self.render(&snapshot);
+        self.emit(&snapshot, event, result).await
+    }
+
+    /// Next chunk of the stream, or `None` once the catalog source is gone or a changed reply
+    /// could not be recorded.
+    async fn next(&mut self) -> Option<web::Bytes> {
+        loop {
+            tokio::select! {
+                changed = self.catalog.changed() => {
+                    changed.ok()?;
+                    let snapshot = self.catalog.borrow_and_update().clone();
+                    let (event, result) = self.render(&snapshot);
+                    if self.last.as_deref() != Some(event.data()) {
+                        return match self.emit(&snapshot, event, result).await {
+                            Ok(event) => Some(event),
+                            Err(err) => {
+                                tracing::error!("failed to record a streamed reply: {err}");
+                                None
+                            }
+                        };
+                    }
+                }
+                _ = self.keep_alive.tick() => return Some(Event::keep_alive()),
+            }
+        }
+    }
+
+    /// Records the reply `event` was rendered from, then marks the event sent.
+    async fn emit(
+        &mut self,
+        snapshot: &CatalogSnapshot,
+        event: Event,
+        result: Result<Localized, ReplyError>,
+    ) -> Result<web::Bytes, PortError> {
+        messages::record(&self.context, &self.message, &result, &self.metadata).await?;
+        match &result {
+            Ok(reply) => self.fallbacks.record(&snapshot.catalog, reply),
+            Err(ReplyError::UnknownMessage(_, localized)) => {
+                self.fallbacks.record(&snapshot.catalog, localized)
+            }
+        }
+
+        self.metrics.record(&event);
+        self.last = Some(event.data().to_string());
+        self.keep_alive.reset();
+        Ok(event.to_bytes())
+    }
+
+    fn render(&self, snapshot: &CatalogSnapshot) -> (Event, Result<Localized, ReplyError>) {
+        let result = messages::reply(
+            &snapshot.catalog,
+            &self.message,
+            &self.locale,
+            &self.metadata,
+        );
+
+        let event = match &result {
+            Ok(reply) => Event::json(
+                "reply",
+                &ReplyResponse {
+                    message: reply.text.clone(),
+                },
+            ),
+            Err(err) => Event::json("error", &ErrorResponse::new(err)),
+        };
+
+        (event.with_id(&snapshot.version), result)
+    }
+}
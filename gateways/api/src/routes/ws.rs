@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, ProtocolError, Session};
+use application::{context::AppContext, locale::Locale, template::RequestMetadata};
+use infrastructure::shutdown::Shutdown;
+use tokio::time::{self, Instant};
+use tracing::Instrument;
+
+use crate::extractors::{AcceptedLocale, Metadata};
+use crate::localization::LocaleFallbacks;
+use crate::response::ErrorResponse;
+use crate::routes::{ReplyRequest, ReplyResult};
+use crate::websocket::{Initiator, RateLimiter, WebSocketConfig, WebSocketMetrics, WebSockets};
+
+/// Interactive reply session. Every text frame is answered with a text frame holding the JSON
+/// [`ReplyResult`] of that message.
+pub async fn websocket(
+    req: HttpRequest,
+    body: web::Payload,
+    metadata: Metadata,
+    locale: AcceptedLocale,
+    context: web::Data<AppContext>,
+    fallbacks: web::Data<LocaleFallbacks>,
+    websockets: web::Data<WebSockets>,
+) -> actix_web::Result<HttpResponse> {
+    let (response, session, stream) = actix_ws::handle(&req, body)?;
+
+    let span = tracing::info_span!(
+        "gateways.api.routes.websocket.session",
+        caller = metadata.0.caller.as_deref(),
+        close_code = tracing::field::Empty,
+    );
+
+    let session = ReplySession {
+        session,
+        locale: locale.0,
+        metadata: metadata.0,
+        context: context.into_inner(),
+        fallbacks: fallbacks.into_inner(),
+        metrics: websockets.metrics.clone(),
+        limiter: RateLimiter::new(
+            websockets.config.rate_limit_per_sec,
+            websockets.config.rate_limit_burst,
+        ),
+        config: websockets.config.clone(),
+        shutdown: websockets.shutdown.clone(),
+    };
+
+    actix_web::rt::spawn(
+        session
+            .run(stream.max_frame_size(websockets.config.max_frame_bytes))
+            .instrument(span),
+    );
+
+    Ok(response)
+}
+
+struct ReplySession {
+    session: Session,
+    locale: Locale,
+    metadata: RequestMetadata,
+    context: Arc<AppContext>,
+    fallbacks: Arc<LocaleFallbacks>,
+    metrics: WebSocketMetrics,
+    limiter: RateLimiter,
+    config: WebSocketConfig,
+    shutdown: Shutdown,
+}
+
+/// How a session ended.
+enum Close {
+    /// The server closes the session with this reason.
+    Server(CloseReason),
+    /// The client sent a close frame, which is echoed back.
+    Client(Option<CloseReason>),
+    /// The connection dropped without a close frame.
+    Lost,
+}
+
+impl ReplySession {
+    async fn run(mut self, mut stream: MessageStream) {
+        let _active = self.metrics.connect();
+        tracing::debug!("websocket session opened");
+
+        let interval = self.config.heartbeat_interval;
+        let mut heartbeat = time::interval_at(Instant::now() + interval, interval);
+        let mut last_seen = Instant::now();
+
+        let close = loop {
+            tokio::select! {
+                message = stream.recv() => {
+                    last_seen = Instant::now();
+                    match self.receive(message).await {
+                        Some(close) => break close,
+                        None => continue,
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() > self.config.client_timeout {
+                        break Close::Server(reason(CloseCode::Policy, "heartbeat timeout"));
+                    }
+                    if self.session.ping(b"").await.is_err() {
+                        break Close::Lost;
+                    }
+                    self.metrics.frame_out("ping");
+                }
+                _ = self.shutdown.wait() => {
+                    break Close::Server(reason(CloseCode::Away, "server is shutting down"));
+                }
+            }
+        };
+
+        let (code, initiator, reply) = match close {
+            Close::Server(reason) => (reason.code, Initiator::Server, Some(Some(reason))),
+            Close::Client(reason) => (
+                reason
+                    .as_ref()
+                    .map_or(CloseCode::Normal, |reason| reason.code),
+                Initiator::Client,
+                Some(reason),
+            ),
+            Close::Lost => (CloseCode::Abnormal, Initiator::Client, None),
+        };
+
+        tracing::Span::current().record("close_code", u16::from(code));
+        self.metrics.close(code, initiator);
+
+        if let Some(reason) = reply {
+            self.metrics.frame_out("close");
+            let _ = self.session.close(reason).await;
+        }
+
+        tracing::debug!("websocket session closed");
+    }
+
+    /// Handles a frame from the client, returning how to close the session if it must end.
+    async fn receive(&mut self, message: Option<Result<Message, ProtocolError>>) -> Option<Close> {
+        let message = match message {
+            None => return Some(Close::Lost),
+            Some(Err(ProtocolError::Overflow)) => {
+                return Some(Close::Server(reason(CloseCode::Size, "frame is too large")))
+            }
+            Some(Err(err)) => {
+                return Some(Close::Server(reason(CloseCode::Protocol, &err.to_string())))
+            }
+            Some(Ok(message)) => message,
+        };
+
+        match message {
+            Message::Text(text) => {
+                self.metrics.frame_in("text");
+                let result = self.reply(text.to_string()).await;
+                let json = serde_json::to_string(&result).unwrap_or_default();
+                if self.session.text(json).await.is_err() {
+                    return Some(Close::Lost);
+                }
+                self.metrics.frame_out("text");
+            }
+            Message::Ping(bytes) => {
+                self.metrics.frame_in("ping");
+                if self.session.pong(&bytes).await.is_err() {
+                    return Some(Close::Lost);
+                }
+                self.metrics.frame_out("pong");
+            }
+            Message::Pong(_) => self.metrics.frame_in("pong"),
+            Message::Close(reason) => {
+                self.metrics.frame_in("close");
+                return Some(Close::Client(reason));
+            }
+            Message::Binary(_) => {
+                self.metrics.frame_in("binary");
+                return Some(Close::Server(reason(
+                    CloseCode::Unsupported,
+                    "binary frames are not supported",
+                )));
+            }
+            Message::Continuation(_) => {
+                self.metrics.frame_in("continuation");
+                return Some(Close::Server(reason(
+                    CloseCode::Unsupported,
+                    "fragmented messages are not supported",
+                )));
+            }
+            Message::Nop => {}
+        }
+
+        None
+    }
+
+    #[tracing::instrument(name = "gateways.api.routes.websocket.message", skip(self))]
+    async fn reply(&mut self, message: String) -> ReplyResult {
+        if !self.limiter.try_acquire() {
+            return ReplyResult::error(429, ErrorResponse::new("Rate limit exceeded"));
+        }
+
+        ReplyResult::new(
+            &self.context,
+            &self.context.catalog.current().catalog,
+            &ReplyRequest { message },
+            &self.locale,
+            &self.metadata,
+            &self.fallbacks,
+        )
+        .await
+    }
+}
+
+fn reason(code: CloseCode, description: &str) -> CloseReason {
+    CloseReason {
+        code,
+        description: Some(description.to_string()),
+    }
+}
@@ -1,22 +1,100 @@
+use actix_web::http::StatusCode;
 use actix_web::middleware::ErrorHandlers;
-use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Responder};
+use application::context::AppContext;
+use infrastructure::cache::{Cache, CacheAdmin, CacheMetrics, CacheSettings};
+use infrastructure::catalog::CatalogStore;
+use infrastructure::http_client::{HttpClient, UpstreamSettings};
+use infrastructure::shutdown::{self, Shutdown};
 use prometheus_client::{encoding::text::encode, registry::Registry};
 use serde_json::json;
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::{net::TcpListener, time::Duration};
 use tracing::log;
 
+use crate::localization::LocaleFallbacks;
+use crate::middlewares::compression::Compression;
+use crate::middlewares::decompression::Decompression;
 use crate::middlewares::error_header::add_error_header;
+use crate::middlewares::idempotency::Idempotency;
 use crate::middlewares::timeout::Timeout;
+use crate::openapi::{self, DocsUi};
+use crate::payload::{PayloadLimits, PayloadRejections};
+use crate::routes::graphql::{GraphQL, GraphQLConfig};
+use crate::streaming::{StreamConfig, StreamMetrics};
+use crate::versioning::{ApiVersion, Versioning};
+use crate::websocket::{WebSocketConfig, WebSocketMetrics, WebSockets};
 use crate::{
     middlewares::{metrics::Metrics, tracing::Tracing},
-    routes::reply,
+    routes::{
+        admin, graphql, history, readiness, reply, reply_batch, reply_stream, websocket,
+        BatchLimits,
+    },
 };
 
-async fn healthcheck() -> impl Responder {
+/// Report whether the service is up
+#[utoipa::path(
+    get,
+    path = "/v1/healthcheck",
+    responses((status = 200, description = "Service is healthy")),
+    tag = "health"
+)]
+pub async fn healthcheck() -> impl Responder {
     HttpResponse::Ok().json(json!({}))
 }
 
+/// Payload limits of the routes that accept a body, resolved for one API version.
+#[derive(Clone)]
+struct RouteLimits {
+    reply: PayloadLimits,
+    reply_batch: PayloadLimits,
+}
+
+/// Handler set mounted under every API version.
+fn api_routes(
+    rejections: &PayloadRejections,
+    limits: &RouteLimits,
+    idempotency_ttl: Duration,
+    request_timeout: Duration,
+) -> impl FnOnce(&mut web::ServiceConfig) {
+    let reply_resource = rejections
+        .limit(
+            web::resource("/reply").route(web::post().to(reply)),
+            &limits.reply,
+        )
+        .wrap(Idempotency::new(
+            idempotency_ttl,
+            request_timeout,
+            limits.reply.json,
+        ));
+    let reply_batch_resource = rejections
+        .limit(
+            web::resource("/reply:batch").route(web::post().to(reply_batch)),
+            &limits.reply_batch,
+        )
+        .wrap(Idempotency::new(
+            idempotency_ttl,
+            request_timeout,
+            limits.reply_batch.json,
+        ));
+
+    move |cfg| {
+        cfg.route("/healthcheck", web::get().to(healthcheck))
+            .route("/readiness", web::get().to(readiness))
+            .service(reply_resource)
+            .service(reply_batch_resource)
+            .route("/reply/stream", web::get().to(reply_stream))
+            .route("/history", web::get().to(history))
+            .service(
+                web::resource("/graphql")
+                    .route(web::post().to(graphql::graphql))
+                    .route(web::get().to(graphql::graphql_get)),
+            )
+            .route("/ws", web::get().to(websocket));
+    }
+}
+
 async fn metrics_handler(state: web::Data<Mutex<AppState>>) -> impl Responder {
     let state = state.lock().unwrap();
     let mut body = String::new();
@@ -33,12 +111,84 @@ pub struct AppState {
 pub struct Settings {
     pub app: AppSettings,
     pub metrics: MetricSettings,
+    /// Store of the catalog replies come from. It replaces the catalog of `context`, so that
+    /// the lookups of `catalog_cache` are kept for it.
+    pub catalog: Arc<CatalogStore>,
+    /// Ports the use cases run against.
+    pub context: AppContext,
 }
 
 pub struct AppSettings {
     pub host: String,
     pub port: u16,
     pub request_timeout_sec: u64,
+    pub compression_min_size_bytes: u64,
+    pub decompression_limit_bytes: usize,
+    pub payload_limits: PayloadLimits,
+    /// Per-route overrides of `payload_limits`, keyed by the full path of a route accepting a
+    /// body: `/{version}/reply` or `/{version}/reply:batch`. Any other key fails setup.
+    pub route_payload_limits: HashMap<String, PayloadLimits>,
+    pub docs_ui: DocsUi,
+    /// Versions the API is mounted under, as `/{version}/...` and as unversioned paths selected
+    /// through `Accept-Version` or the `version` parameter of the `Accept` media type.
+    pub api_versions: Vec<ApiVersion>,
+    /// Version served to unversioned requests that do not ask for one.
+    pub default_api_version: String,
+    /// Maximum number of requests accepted by `POST /reply:batch`.
+    pub max_batch_size: usize,
+    /// How long responses to requests with an `Idempotency-Key` are replayed to retries.
+    pub idempotency_ttl_sec: u64,
+    pub websocket: WebSocketConfig,
+    pub graphql: GraphQLConfig,
+    /// Services handlers call through the [`HttpClient`] in the app data, by name.
+    pub upstreams: HashMap<String, UpstreamSettings>,
+    /// Cache of the catalog entries messages match, disabled when `None`.
+    pub catalog_cache: Option<CacheSettings>,
+    /// Cache of the upstream responses fetched with [`HttpClient::get_cached`], disabled when
+    /// `None`.
+    pub upstream_cache: Option<CacheSettings>,
+}
+
+impl AppSettings {
+    /// Payload limits of the routes of every version, failing on overrides of a route that
+    /// does not accept a body, so a mistyped path is not silently ignored.
+    fn route_limits(&self, versions: &[ApiVersion]) -> eyre::Result<Vec<RouteLimits>> {
+        let paths = |version: &ApiVersion| {
+            [
+                format!("{}/reply", version.path()),
+                format!("{}/reply:batch", version.path()),
+            ]
+        };
+
+        if let Some(path) = self
+            .route_payload_limits
+            .keys()
+            .find(|path| !versions.iter().flat_map(paths).any(|known| &known == *path))
+        {
+            eyre::bail!(
+                "route_payload_limits has a limit for {path}, which is not a route accepting a \
+                 body: expected {}",
+                versions
+                    .iter()
+                    .flat_map(paths)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        Ok(versions
+            .iter()
+            .map(|version| {
+                let [reply, reply_batch] = paths(version).map(|path| {
+                    self.route_payload_limits
+                        .get(&path)
+                        .unwrap_or(&self.payload_limits)
+                        .clone()
+                });
+                RouteLimits { reply, reply_batch }
+            })
+            .collect())
+    }
 }
 
 pub struct MetricSettings {
@@ -49,6 +199,8 @@ pub struct MetricSettings {
 
 pub struct Server {
     port: u16,
+    metrics_port: u16,
+    shutdown: Shutdown,
     server: actix_web::dev::Server,
     metrics_server: actix_web::dev::Server,
 }
@@ -62,27 +214,148 @@ impl Server {
         ))?;
 
         let port = listener.local_addr()?.port();
+        let metrics_port = metrics_listener.local_addr()?.port();
 
         let mut registry = settings.metrics.registry;
         let metrics_middleware = Metrics::new(&mut registry);
-        let timeout_middleware =
-            Timeout::new(Duration::from_secs(settings.app.request_timeout_sec));
+        let request_timeout = Duration::from_secs(settings.app.request_timeout_sec);
+        let timeout_middleware = Timeout::new(request_timeout);
+        let stream_config = web::Data::new(StreamConfig::for_timeout(request_timeout));
+        let stream_metrics = web::Data::new(StreamMetrics::new(&mut registry));
+        let shutdown = Shutdown::new();
+        let websockets = web::Data::new(WebSockets {
+            config: settings.app.websocket.clone(),
+            metrics: WebSocketMetrics::new(&mut registry),
+            shutdown: shutdown.clone(),
+        });
+        let compression_middleware =
+            Compression::new(&mut registry, settings.app.compression_min_size_bytes);
+        let decompression_middleware = Decompression::new(settings.app.decompression_limit_bytes);
+        let metrics_compression_middleware = compression_middleware.clone();
+        let payload_rejections = PayloadRejections::new(&mut registry);
+        let payload_limits = settings.app.payload_limits.clone();
+        let versioning = Versioning::new(
+            &mut registry,
+            settings.app.api_versions.clone(),
+            settings.app.default_api_version.clone(),
+        );
+        let route_limits = settings.app.route_limits(versioning.versions())?;
+        let batch_limits = BatchLimits {
+            max_size: settings.app.max_batch_size,
+        };
+        let idempotency_ttl = Duration::from_secs(settings.app.idempotency_ttl_sec);
+        let docs = openapi::configure(settings.app.docs_ui);
+        let locale_fallbacks = LocaleFallbacks::new(&mut registry);
+        let cache_metrics = CacheMetrics::new(&mut registry);
+        let mut caches: Vec<Arc<dyn CacheAdmin>> = Vec::new();
+        if let Some(cache) = &settings.app.catalog_cache {
+            let lookups = Arc::new(Cache::new(
+                "catalog_lookups",
+                cache.clone(),
+                cache_metrics.clone(),
+            ));
+            settings.catalog.cache_lookups(lookups.clone());
+            caches.push(lookups);
+        }
+        let mut http_client = HttpClient::new(settings.app.upstreams.clone(), &mut registry)?;
+        if let Some(cache) = &settings.app.upstream_cache {
+            let responses = Arc::new(Cache::new(
+                "upstream_responses",
+                cache.clone(),
+                cache_metrics,
+            ));
+            http_client = http_client.with_cache(responses.clone());
+            caches.push(responses);
+        }
+        let http_client = Arc::new(http_client);
+        let mut context = settings.context;
+        context.catalog = settings.catalog;
+        if !settings.app.upstreams.is_empty() {
+            context.readiness.push(http_client.clone());
+        }
+        let http_client = web::Data::from(http_client);
+        let graphql = web::Data::new(GraphQL::new(
+            &settings.app.graphql,
+            context.clone(),
+            locale_fallbacks.clone(),
+            batch_limits,
+        ));
+        let locale_fallbacks = web::Data::new(locale_fallbacks);
+        let batch_limits = web::Data::new(batch_limits);
+        let admin_catalog = web::Data::from(context.catalog.clone());
+        let context = web::Data::new(context);
+        let caches = web::Data::new(caches);
 
         let state = AppState { registry };
         let state = web::Data::new(Mutex::new(state));
 
         let server = HttpServer::new(move || {
-            App::new()
+            let rejections = payload_rejections.clone();
+
+            let unmatched = versioning.clone();
+
+            let mut app = App::new()
+                .app_data(context.clone())
+                .app_data(http_client.clone())
+                .app_data(locale_fallbacks.clone())
+                .app_data(batch_limits.clone())
+                .app_data(stream_config.clone())
+                .app_data(stream_metrics.clone())
+                .app_data(websockets.clone())
+                .app_data(graphql.clone())
+                .app_data(payload_rejections.json_config(payload_limits.json))
+                .app_data(payload_rejections.form_config(payload_limits.form))
+                .app_data(payload_rejections.payload_config(payload_limits.payload))
                 .wrap(Tracing::middleware())
                 .wrap(metrics_middleware.clone())
-                .wrap(ErrorHandlers::new().default_handler(add_error_header))
-                .service(
-                    web::scope("/v1")
-                        .wrap(timeout_middleware.clone())
-                        .route("/healthcheck", web::get().to(healthcheck))
-                        .route("/reply", web::post().to(reply)),
+                .wrap(
+                    ErrorHandlers::new()
+                        .default_handler(add_error_header)
+                        .handler(StatusCode::PAYLOAD_TOO_LARGE, move |res| {
+                            rejections.handle_payload_too_large(res)
+                        }),
                 )
+                .wrap(decompression_middleware.clone())
+                .wrap(compression_middleware.clone())
+                .configure(docs.clone());
+
+            let versions = versioning.versions().iter().zip(&route_limits);
+
+            for (version, limits) in versions.clone() {
+                app = app.service(
+                    web::scope(&version.path())
+                        .wrap(timeout_middleware.clone())
+                        .wrap(versioning.middleware(version))
+                        .configure(api_routes(
+                            &payload_rejections,
+                            limits,
+                            idempotency_ttl,
+                            request_timeout,
+                        )),
+                );
+            }
+
+            for (version, limits) in versions {
+                app = app.service(
+                    web::scope("")
+                        .guard(versioning.guard(version))
+                        .wrap(timeout_middleware.clone())
+                        .wrap(versioning.middleware(version))
+                        .configure(api_routes(
+                            &payload_rejections,
+                            limits,
+                            idempotency_ttl,
+                            request_timeout,
+                        )),
+                );
+            }
+
+            app.default_service(web::to(move |req: HttpRequest| {
+                let result = unmatched.unmatched(&req);
+                async move { result }
+            }))
         })
+        .disable_signals()
         .listen(listener)
         .inspect(|_| {
             log::info!(
@@ -96,8 +369,16 @@ impl Server {
         let metrics_server = HttpServer::new(move || {
             App::new()
                 .app_data(state.clone())
+                .wrap(metrics_compression_middleware.clone())
+                .app_data(admin_catalog.clone())
+                .app_data(caches.clone())
                 .route("/metrics", web::get().to(metrics_handler))
+                .route("/admin/catalog", web::get().to(admin::catalog))
+                .route("/admin/caches", web::get().to(admin::caches))
+                .route("/admin/caches/{name}", web::get().to(admin::cache))
+                .route("/admin/caches/{name}", web::delete().to(admin::purge_cache))
         })
+        .disable_signals()
         .listen(metrics_listener)
         .inspect(|_| {
             log::info!(
@@ -110,6 +391,8 @@ impl Server {
 
         let server = Server {
             port,
+            metrics_port,
+            shutdown,
             server,
             metrics_server,
         };
@@ -121,7 +404,32 @@ impl Server {
         self.port
     }
 
+    pub fn metrics_port(&self) -> u16 {
+        self.metrics_port
+    }
+
+    /// Signal that stops the server gracefully when triggered. `SIGINT` and `SIGTERM` trigger
+    /// it too.
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
     pub async fn run(self) -> Result<(), std::io::Error> {
+        let signalled = self.shutdown.clone();
+        tokio::spawn(async move {
+            shutdown::signal().await;
+            log::info!("Shutdown signal received");
+            signalled.trigger();
+        });
+
+        let handles = (self.server.handle(), self.metrics_server.handle());
+        let stopping = self.shutdown.clone();
+        tokio::spawn(async move {
+            stopping.wait().await;
+            let (server, metrics_server) = handles;
+            futures_util::join!(server.stop(true), metrics_server.stop(true));
+        });
+
         let result = futures_util::join!(self.metrics_server, self.server);
 
         match result {
@@ -1,6 +1,15 @@
+use std::{collections::HashMap, time::Duration};
+
+use application::outbox::Backoff;
 use eyre::Context;
+use infrastructure::{
+    http_client::UpstreamSettings,
+    resilience::{BulkheadSettings, CircuitBreakerSettings},
+};
 use serde_aux::field_attributes::deserialize_number_from_string;
 
+use crate::{openapi, payload, websocket};
+
 #[derive(serde::Deserialize, Clone)]
 pub struct Telemetry {
     pub host: String,
@@ -18,6 +27,86 @@ pub struct Application {
     pub service_name: String,
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub request_timeout_sec: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub compression_min_size_bytes: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub decompression_limit_bytes: usize,
+    pub payload_limits: PayloadLimits,
+    #[serde(default)]
+    pub route_payload_limits: HashMap<String, PayloadLimits>,
+    pub docs_ui: DocsUi,
+    #[serde(default = "default_api_versions")]
+    pub api_versions: Vec<ApiVersion>,
+    pub default_api_version: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_batch_size: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idempotency_ttl_sec: u64,
+    pub websocket: WebSocket,
+    pub graphql: GraphQL,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct GraphQL {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_depth: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_complexity: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub persisted_query_cache_size: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_batch_operations: usize,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct WebSocket {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_frame_bytes: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub heartbeat_interval_sec: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub client_timeout_sec: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub rate_limit_per_sec: f64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub rate_limit_burst: u32,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct ApiVersion {
+    pub name: String,
+    /// Unix timestamp (seconds) from which the version is deprecated.
+    #[serde(default)]
+    pub deprecated_at: Option<u64>,
+    /// Unix timestamp (seconds) after which the version is no longer served.
+    #[serde(default)]
+    pub sunset_at: Option<u64>,
+}
+
+fn default_api_versions() -> Vec<ApiVersion> {
+    vec![ApiVersion {
+        name: "v1".to_string(),
+        deprecated_at: None,
+        sunset_at: None,
+    }]
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DocsUi {
+    #[serde(rename = "none")]
+    Disabled,
+    #[serde(rename = "swagger")]
+    SwaggerUi,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct PayloadLimits {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub json_bytes: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub form_bytes: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub payload_bytes: usize,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -27,15 +116,292 @@ pub struct Metric {
     pub port: u16,
 }
 
+#[derive(serde::Deserialize, Clone)]
+pub struct Grpc {
+    /// Serve the gRPC gateway from this binary too, sharing its catalog, metrics and shutdown.
+    pub enabled: bool,
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct History {
+    /// Exchanges older than this are deleted; `0` keeps them forever.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retention_sec: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub purge_interval_sec: u64,
+    /// Cron expression with a seconds field, e.g. `0 0 3 * * *`; replaces `purge_interval_sec`
+    /// when set.
+    pub purge_cron: Option<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub purge_timeout_sec: u64,
+    /// Upper bound of the random delay added to each purge.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub purge_jitter_ms: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Outbox {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub poll_interval_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub relay_timeout_sec: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub batch_size: usize,
+    /// How long a relay reserves the events it is publishing.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lease_sec: u64,
+    /// Delay before the first retry of an event, doubled on every further failure.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_initial_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_max_sec: u64,
+}
+
+/// A service handlers call, e.g. `upstreams.billing.base_url`. Only `base_url` is required.
+#[derive(serde::Deserialize, Clone)]
+pub struct Upstream {
+    pub base_url: String,
+    /// Limit of each attempt.
+    #[serde(
+        default = "default_upstream_timeout_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub timeout_ms: u64,
+    /// Limit of the whole request, retries included.
+    #[serde(
+        default = "default_upstream_deadline_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub deadline_ms: u64,
+    /// Attempts of idempotent requests; `1` disables retries.
+    #[serde(
+        default = "default_upstream_max_attempts",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_attempts: u32,
+    #[serde(
+        default = "default_upstream_retry_initial_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub retry_initial_ms: u64,
+    #[serde(
+        default = "default_upstream_retry_max_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub retry_max_ms: u64,
+    /// Consecutive failed attempts opening the circuit, which then rejects requests.
+    #[serde(
+        default = "default_upstream_circuit_failure_threshold",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub circuit_failure_threshold: u32,
+    /// How long the circuit stays open before trial requests are let through.
+    #[serde(
+        default = "default_upstream_circuit_open_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub circuit_open_ms: u64,
+    /// Trial requests that must succeed to close the circuit again.
+    #[serde(
+        default = "default_upstream_circuit_half_open_requests",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub circuit_half_open_requests: u32,
+    /// Bulkhead: requests in flight at once, beyond which requests wait for a slot.
+    #[serde(
+        default = "default_upstream_max_concurrent_requests",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_concurrent_requests: usize,
+    /// How long a request waits for a slot before being rejected.
+    #[serde(
+        default = "default_upstream_queue_timeout_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub queue_timeout_ms: u64,
+}
+
+fn default_upstream_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_upstream_deadline_ms() -> u64 {
+    3000
+}
+
+fn default_upstream_max_attempts() -> u32 {
+    3
+}
+
+fn default_upstream_retry_initial_ms() -> u64 {
+    50
+}
+
+fn default_upstream_retry_max_ms() -> u64 {
+    1000
+}
+
+fn default_upstream_circuit_failure_threshold() -> u32 {
+    5
+}
+
+fn default_upstream_circuit_open_ms() -> u64 {
+    30_000
+}
+
+fn default_upstream_circuit_half_open_requests() -> u32 {
+    1
+}
+
+fn default_upstream_max_concurrent_requests() -> usize {
+    64
+}
+
+fn default_upstream_queue_timeout_ms() -> u64 {
+    50
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum LeaseBackend {
+    /// The table of the storage backend, shared by the instances using the same database.
+    #[serde(rename = "storage")]
+    Storage,
+    /// Lock files, shared by the instances of a single host.
+    #[serde(rename = "file")]
+    File,
+}
+
+/// Leases electing the instance that runs each singleton job.
+#[derive(serde::Deserialize, Clone)]
+pub struct Lease {
+    pub backend: LeaseBackend,
+    /// Directory of the lock files of the `file` backend.
+    pub dir: String,
+    /// Name of this instance in the leases; defaults to the host name and process id.
+    pub holder: Option<String>,
+    /// How long a lease outlives its last renewal, e.g. when its holder crashes.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub ttl_sec: u64,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CacheEviction {
+    #[serde(rename = "lru")]
+    Lru,
+    #[serde(rename = "lfu")]
+    Lfu,
+}
+
+/// An in-process cache, disabled when `max_entries` is 0.
+#[derive(serde::Deserialize, Clone)]
+pub struct Cache {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_entries: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub ttl_sec: u64,
+    /// How long an expired entry is still served while it is reloaded.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub stale_sec: u64,
+    pub eviction: CacheEviction,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Caches {
+    /// Entries of the reply catalog matched by messages.
+    pub catalog: Cache,
+    /// Successful responses of upstreams to cached GET requests.
+    pub upstreams: Cache,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    #[serde(rename = "memory")]
+    Memory,
+    #[serde(rename = "postgres")]
+    Postgres,
+    #[serde(rename = "sqlite")]
+    Sqlite,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Storage {
+    pub backend: StorageBackend,
+    pub sqlite: Sqlite,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Sqlite {
+    /// Database file, created when missing.
+    pub path: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub readers: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub busy_timeout_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub slow_statement_ms: u64,
+}
+
+/// PostgreSQL connection, used by the `postgres` storage backend.
+#[derive(serde::Deserialize, Clone)]
+pub struct Database {
+    pub url: Option<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_connections: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_connections: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub acquire_timeout_sec: u64,
+    /// Idle connections above `min_connections` are closed after this long; `0` keeps them.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idle_timeout_sec: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub slow_statement_ms: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Catalog {
+    /// `.toml`, `.json` or `.yaml` file with the reply catalog; the built-in catalog is used
+    /// when unset.
+    pub path: Option<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub reload_interval_sec: u64,
+}
+
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
     pub app: Application,
     pub metric: Metric,
     pub telemetry: Telemetry,
+    pub catalog: Catalog,
+    pub grpc: Grpc,
+    pub storage: Storage,
+    pub database: Database,
+    pub history: History,
+    pub outbox: Outbox,
+    pub lease: Lease,
+    pub cache: Caches,
+    #[serde(default)]
+    pub upstreams: HashMap<String, Upstream>,
 }
 
+/// Settings file read when `APP_CONFIG_FILE` is unset; its extension picks the format.
+const DEFAULT_CONFIG_FILE: &str = "config/app";
+
+/// Loads the settings from the file named by `APP_CONFIG_FILE`, or `config/app.{toml,json,yaml}`
+/// when present, overridden by `APP_`-prefixed environment variables. Environment variables
+/// cannot name keys containing `_` below a map, such as `upstreams.<name>.base_url`, so maps
+/// are configured in the file.
 pub fn get_config() -> eyre::Result<Settings> {
+    let file = match std::env::var("APP_CONFIG_FILE") {
+        Ok(path) => config::File::with_name(&path),
+        Err(_) => config::File::with_name(DEFAULT_CONFIG_FILE).required(false),
+    };
+
     let settings = config::Config::builder()
+        .add_source(file)
         .add_source(
             config::Environment::with_prefix("APP")
                 .prefix_separator("_")
@@ -47,15 +413,76 @@ pub fn get_config() -> eyre::Result<Settings> {
         .set_default("app.environment", Environment::Development.as_str())?
         .set_default("app.service_name", "{{project-name}}")?
         .set_default("app.request_timeout_sec", 2)?
+        .set_default("app.compression_min_size_bytes", 1024)?
+        .set_default("app.decompression_limit_bytes", 2 * 1024 * 1024)?
+        .set_default("app.payload_limits.json_bytes", 64 * 1024)?
+        .set_default("app.payload_limits.form_bytes", 16 * 1024)?
+        .set_default("app.payload_limits.payload_bytes", 256 * 1024)?
+        .set_default("app.docs_ui", "none")?
+        .set_default("app.default_api_version", "v1")?
+        .set_default("app.max_batch_size", 100)?
+        .set_default("app.idempotency_ttl_sec", 24 * 60 * 60)?
+        .set_default("app.websocket.max_frame_bytes", 64 * 1024)?
+        .set_default("app.websocket.heartbeat_interval_sec", 5)?
+        .set_default("app.websocket.client_timeout_sec", 15)?
+        .set_default("app.websocket.rate_limit_per_sec", 10)?
+        .set_default("app.websocket.rate_limit_burst", 20)?
+        .set_default("app.graphql.max_depth", 8)?
+        .set_default("app.graphql.max_complexity", 256)?
+        .set_default("app.graphql.persisted_query_cache_size", 1024)?
+        .set_default("app.graphql.max_batch_operations", 10)?
         // Metric default settings
         .set_default("metric.host", "127.0.0.1")?
         .set_default("metric.port", 7001)?
+        // gRPC default settings
+        .set_default("grpc.enabled", false)?
+        .set_default("grpc.host", "127.0.0.1")?
+        .set_default("grpc.port", 7002)?
+        // Storage default settings
+        .set_default("storage.backend", "memory")?
+        .set_default("storage.sqlite.path", "app.db")?
+        .set_default("storage.sqlite.readers", 4)?
+        .set_default("storage.sqlite.busy_timeout_ms", 5000)?
+        .set_default("storage.sqlite.slow_statement_ms", 1000)?
+        // History default settings
+        .set_default("history.retention_sec", 30 * 24 * 60 * 60)?
+        .set_default("history.purge_interval_sec", 60 * 60)?
+        .set_default("history.purge_timeout_sec", 5 * 60)?
+        .set_default("history.purge_jitter_ms", 10_000)?
+        // Outbox default settings
+        .set_default("outbox.poll_interval_ms", 1000)?
+        .set_default("outbox.relay_timeout_sec", 60)?
+        .set_default("outbox.batch_size", 100)?
+        .set_default("outbox.lease_sec", 30)?
+        .set_default("outbox.retry_initial_ms", 1000)?
+        .set_default("outbox.retry_max_sec", 5 * 60)?
+        // Lease default settings
+        .set_default("lease.backend", "storage")?
+        .set_default("lease.dir", "leases")?
+        .set_default("lease.ttl_sec", 30)?
+        // Cache default settings
+        .set_default("cache.catalog.max_entries", 10_000)?
+        .set_default("cache.catalog.ttl_sec", 300)?
+        .set_default("cache.catalog.stale_sec", 0)?
+        .set_default("cache.catalog.eviction", "lfu")?
+        .set_default("cache.upstreams.max_entries", 1000)?
+        .set_default("cache.upstreams.ttl_sec", 30)?
+        .set_default("cache.upstreams.stale_sec", 30)?
+        .set_default("cache.upstreams.eviction", "lru")?
+        // Database default settings
+        .set_default("database.max_connections", 10)?
+        .set_default("database.min_connections", 0)?
+        .set_default("database.acquire_timeout_sec", 5)?
+        .set_default("database.idle_timeout_sec", 600)?
+        .set_default("database.slow_statement_ms", 1000)?
+        // Catalog default settings
+        .set_default("catalog.reload_interval_sec", 5)?
         // Telemetry default settings
         .set_default("telemetry.host", "127.0.0.1")?
         .set_default("telemetry.port", 4317)?
         .set_default("telemetry.sampler_param", 1.0)?
         .build()
-        .wrap_err("error loading configuration from env variables")?;
+        .wrap_err("error loading configuration")?;
 
     settings
         .try_deserialize::<Settings>()
@@ -94,3 +521,58 @@ impl TryFrom<String> for Environment {
         }
     }
 }
+
+impl From<PayloadLimits> for payload::PayloadLimits {
+    fn from(limits: PayloadLimits) -> Self {
+        payload::PayloadLimits {
+            json: limits.json_bytes,
+            form: limits.form_bytes,
+            payload: limits.payload_bytes,
+        }
+    }
+}
+
+impl From<WebSocket> for websocket::WebSocketConfig {
+    fn from(settings: WebSocket) -> Self {
+        websocket::WebSocketConfig {
+            max_frame_bytes: settings.max_frame_bytes,
+            heartbeat_interval: Duration::from_secs(settings.heartbeat_interval_sec),
+            client_timeout: Duration::from_secs(settings.client_timeout_sec),
+            rate_limit_per_sec: settings.rate_limit_per_sec,
+            rate_limit_burst: settings.rate_limit_burst,
+        }
+    }
+}
+
+impl From<Upstream> for UpstreamSettings {
+    fn from(upstream: Upstream) -> Self {
+        UpstreamSettings {
+            base_url: upstream.base_url,
+            timeout: Duration::from_millis(upstream.timeout_ms),
+            deadline: Duration::from_millis(upstream.deadline_ms),
+            max_attempts: upstream.max_attempts,
+            backoff: Backoff {
+                initial: Duration::from_millis(upstream.retry_initial_ms),
+                max: Duration::from_millis(upstream.retry_max_ms),
+            },
+            circuit_breaker: CircuitBreakerSettings {
+                failure_threshold: upstream.circuit_failure_threshold,
+                open_duration: Duration::from_millis(upstream.circuit_open_ms),
+                half_open_calls: upstream.circuit_half_open_requests,
+            },
+            bulkhead: BulkheadSettings {
+                max_concurrent_calls: upstream.max_concurrent_requests,
+                max_wait: Duration::from_millis(upstream.queue_timeout_ms),
+            },
+        }
+    }
+}
+
+impl From<DocsUi> for openapi::DocsUi {
+    fn from(ui: DocsUi) -> Self {
+        match ui {
+            DocsUi::Disabled => openapi::DocsUi::Disabled,
+            DocsUi::SwaggerUi => openapi::DocsUi::SwaggerUi,
+        }
+    }
+}
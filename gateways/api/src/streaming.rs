@@ -0,0 +1,142 @@
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+    registry::Registry,
+};
+use serde::Serialize;
+
+pub const EVENT_STREAM: &str = "text/event-stream";
+const KEEP_ALIVE: &[u8] = b": keep-alive\n\n";
+
+/// Settings of Server-Sent Events responses.
+#[derive(Clone, Copy, Debug)]
+pub struct StreamConfig {
+    /// Interval between keep-alive comments. It must be shorter than the request timeout,
+    /// which also limits how long a stream may stay idle.
+    pub keep_alive: Duration,
+}
+
+impl StreamConfig {
+    /// Sends keep-alive comments at half the request timeout.
+    pub fn for_timeout(timeout: Duration) -> Self {
+        StreamConfig {
+            keep_alive: timeout / 2,
+        }
+    }
+}
+
+/// A single Server-Sent Event.
+pub struct Event {
+    name: &'static str,
+    id: Option<String>,
+    data: String,
+}
+
+impl Event {
+    pub fn json<T: Serialize>(name: &'static str, data: &T) -> Self {
+        Event {
+            name,
+            id: None,
+            data: serde_json::to_string(data).unwrap_or_default(),
+        }
+    }
+
+    pub fn with_id(self, id: impl Into<String>) -> Self {
+        Event {
+            id: Some(id.into()),
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut event = format!("event: {}\n", self.name);
+        if let Some(id) = &self.id {
+            event.push_str(&format!("id: {id}\n"));
+        }
+        for line in self.data.lines() {
+            event.push_str(&format!("data: {line}\n"));
+        }
+        event.push('\n');
+
+        Bytes::from(event)
+    }
+
+    pub fn keep_alive() -> Bytes {
+        Bytes::from_static(KEEP_ALIVE)
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct EventLabel {
+    pub event: String,
+}
+
+/// Tracks open event streams and the events sent through them.
+#[derive(Clone)]
+pub struct StreamMetrics {
+    open_streams: Gauge,
+    events: Family<EventLabel, Counter>,
+}
+
+impl StreamMetrics {
+    pub fn new(registry: &mut Registry) -> Self {
+        let open_streams = Gauge::default();
+        let events = Family::<EventLabel, Counter>::default();
+
+        registry.register(
+            "sse_open_streams",
+            "Number of Server-Sent Events streams currently open",
+            open_streams.clone(),
+        );
+
+        registry.register(
+            "sse_event_count",
+            "Number of Server-Sent Events sent",
+            events.clone(),
+        );
+
+        StreamMetrics {
+            open_streams,
+            events,
+        }
+    }
+
+    /// Counts a stream as open until the returned guard is dropped, which happens when the
+    /// stream ends or the client disconnects.
+    pub fn open(&self) -> OpenStream {
+        self.open_streams.inc();
+        OpenStream {
+            metrics: self.clone(),
+        }
+    }
+
+    pub fn record(&self, event: &Event) {
+        self.events
+            .get_or_create(&EventLabel {
+                event: event.name().to_string(),
+            })
+            .inc();
+    }
+}
+
+pub struct OpenStream {
+    metrics: StreamMetrics,
+}
+
+impl Drop for OpenStream {
+    fn drop(&mut self) {
+        self.metrics.open_streams.dec();
+        tracing::debug!("event stream closed");
+    }
+}
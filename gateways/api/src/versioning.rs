@@ -0,0 +1,277 @@
+use std::{
+    future::{ready, Ready},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    guard::{Guard, GuardContext},
+    http::header::{self, HeaderMap, HeaderName, HeaderValue, HttpDate},
+    Error, HttpMessage, HttpRequest, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family},
+    registry::Registry,
+};
+
+use crate::response::bad_request;
+
+pub const ACCEPT_VERSION: HeaderName = HeaderName::from_static("accept-version");
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// A version under which the API handler set is mounted, e.g. `v1`. Deprecated versions keep
+/// being served but announce it through the `Deprecation` and `Sunset` response headers.
+#[derive(Clone, Debug)]
+pub struct ApiVersion {
+    pub name: String,
+    pub deprecated_at: Option<SystemTime>,
+    pub sunset_at: Option<SystemTime>,
+}
+
+impl ApiVersion {
+    pub fn new(name: &str) -> Self {
+        ApiVersion {
+            name: name.to_string(),
+            deprecated_at: None,
+            sunset_at: None,
+        }
+    }
+
+    pub fn from_unix_timestamps(
+        name: &str,
+        deprecated_at: Option<u64>,
+        sunset_at: Option<u64>,
+    ) -> Self {
+        let to_time = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        ApiVersion {
+            name: name.to_string(),
+            deprecated_at: deprecated_at.map(to_time),
+            sunset_at: sunset_at.map(to_time),
+        }
+    }
+
+    pub fn path(&self) -> String {
+        format!("/{}", self.name)
+    }
+}
+
+/// How the version of a request was chosen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selector {
+    Path,
+    Header,
+    MediaType,
+    Default,
+}
+
+impl Selector {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Selector::Path => "path",
+            Selector::Header => "header",
+            Selector::MediaType => "media_type",
+            Selector::Default => "default",
+        }
+    }
+}
+
+/// Returns the version asked for through the `Accept-Version` header or, failing that, through
+/// a `version` parameter of the `Accept` media type. Both `2` and `v2` name the version `v2`.
+pub fn requested_version(headers: &HeaderMap) -> Option<(String, Selector)> {
+    if let Some(value) = headers.get(ACCEPT_VERSION).and_then(|v| v.to_str().ok()) {
+        return Some((normalize(value), Selector::Header));
+    }
+
+    headers
+        .get_all(header::ACCEPT)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .flat_map(|media_type| media_type.split(';').skip(1))
+        .filter_map(|param| param.trim().strip_prefix("version="))
+        .map(|version| (normalize(version.trim_matches('"')), Selector::MediaType))
+        .next()
+}
+
+fn normalize(version: &str) -> String {
+    let version = version.trim();
+    if version.starts_with('v') {
+        version.to_string()
+    } else {
+        format!("v{version}")
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct VersionLabel {
+    pub version: String,
+    pub selected_by: String,
+}
+
+#[derive(Clone)]
+pub struct Versioning {
+    versions: Vec<ApiVersion>,
+    default: String,
+    version_count: Family<VersionLabel, Counter>,
+}
+
+impl Versioning {
+    pub fn new(registry: &mut Registry, versions: Vec<ApiVersion>, default: String) -> Self {
+        let version_count = Family::<VersionLabel, Counter>::default();
+
+        registry.register(
+            "api_version_request_count",
+            "Number of requests served by each API version",
+            version_count.clone(),
+        );
+
+        Versioning {
+            versions,
+            default,
+            version_count,
+        }
+    }
+
+    pub fn versions(&self) -> &[ApiVersion] {
+        &self.versions
+    }
+
+    /// Matches unversioned paths whose requested (or default) version is `version`.
+    pub fn guard(&self, version: &ApiVersion) -> VersionGuard {
+        VersionGuard {
+            name: version.name.clone(),
+            is_default: version.name == self.default,
+        }
+    }
+
+    /// Adds the deprecation headers of `version` and counts its usage.
+    pub fn middleware(&self, version: &ApiVersion) -> VersionUsage {
+        VersionUsage {
+            version: Arc::new(version.clone()),
+            version_count: self.version_count.clone(),
+        }
+    }
+
+    /// Fallback for requests no route matched: a `400 Bad Request` when the requested version
+    /// is not served, otherwise the usual empty `404 Not Found`.
+    pub fn unmatched(&self, req: &HttpRequest) -> actix_web::Result<HttpResponse> {
+        match requested_version(req.headers()) {
+            Some((requested, _)) if !self.versions.iter().any(|v| v.name == requested) => {
+                let supported: Vec<_> = self.versions.iter().map(|v| v.name.as_str()).collect();
+                Err(bad_request(
+                    format!("Unsupported API version \"{requested}\""),
+                    &supported,
+                ))
+            }
+            _ => Ok(HttpResponse::NotFound().finish()),
+        }
+    }
+}
+
+pub struct VersionGuard {
+    name: String,
+    is_default: bool,
+}
+
+impl Guard for VersionGuard {
+    fn check(&self, ctx: &GuardContext<'_>) -> bool {
+        match requested_version(&ctx.head().headers) {
+            Some((requested, _)) => requested == self.name,
+            None => self.is_default,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct VersionUsage {
+    version: Arc<ApiVersion>,
+    version_count: Family<VersionLabel, Counter>,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for VersionUsage
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = VersionUsageMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(VersionUsageMiddleware {
+            service,
+            version: self.version.clone(),
+            version_count: Arc::new(self.version_count.clone()),
+        }))
+    }
+}
+
+pub struct VersionUsageMiddleware<S> {
+    service: S,
+    version: Arc<ApiVersion>,
+    version_count: Arc<Family<VersionLabel, Counter>>,
+}
+
+impl<S, B> Service<ServiceRequest> for VersionUsageMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let selector = if req.path().starts_with(&format!("{}/", self.version.path())) {
+            Selector::Path
+        } else {
+            requested_version(req.headers())
+                .map(|(_, selector)| selector)
+                .unwrap_or(Selector::Default)
+        };
+
+        self.version_count
+            .get_or_create(&VersionLabel {
+                version: self.version.name.clone(),
+                selected_by: selector.as_str().to_string(),
+            })
+            .inc();
+
+        // read by the handlers and middlewares of the version, such as the idempotency keys
+        req.extensions_mut().insert(self.version.clone());
+        let version = self.version.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            let headers = res.headers_mut();
+
+            if let Some(deprecated_at) = version.deprecated_at {
+                let timestamp = deprecated_at
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                if let Ok(value) = HeaderValue::from_str(&format!("@{timestamp}")) {
+                    headers.insert(DEPRECATION, value);
+                }
+            }
+
+            if let Some(sunset_at) = version.sunset_at {
+                if let Ok(value) = HeaderValue::from_str(&HttpDate::from(sunset_at).to_string()) {
+                    headers.insert(SUNSET, value);
+                }
+            }
+
+            Ok(res)
+        })
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use actix_ws::CloseCode;
+use infrastructure::shutdown::Shutdown;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+    registry::Registry,
+};
+use tokio::time::Instant;
+
+/// Settings of WebSocket sessions.
+#[derive(Clone, Debug)]
+pub struct WebSocketConfig {
+    /// Largest frame accepted from a client; bigger frames close the session with `1009`.
+    pub max_frame_bytes: usize,
+    /// Interval between the pings sent to the client.
+    pub heartbeat_interval: Duration,
+    /// Sessions that receive nothing, pongs included, for this long are closed.
+    pub client_timeout: Duration,
+    /// Messages a session may send per second, on average.
+    pub rate_limit_per_sec: f64,
+    /// Messages a session may send in a burst above `rate_limit_per_sec`.
+    pub rate_limit_burst: u32,
+}
+
+/// State shared by the WebSocket sessions of a server.
+pub struct WebSockets {
+    pub config: WebSocketConfig,
+    pub metrics: WebSocketMetrics,
+    pub shutdown: Shutdown,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct FrameLabel {
+    pub direction: String,
+    pub kind: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CloseLabel {
+    pub code: u16,
+    pub initiator: String,
+}
+
+/// Which side of a session sent the close frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Initiator {
+    Client,
+    Server,
+}
+
+impl Initiator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Initiator::Client => "client",
+            Initiator::Server => "server",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct WebSocketMetrics {
+    active_connections: Gauge,
+    frames: Family<FrameLabel, Counter>,
+    closes: Family<CloseLabel, Counter>,
+}
+
+impl WebSocketMetrics {
+    pub fn new(registry: &mut Registry) -> Self {
+        let active_connections = Gauge::default();
+        let frames = Family::<FrameLabel, Counter>::default();
+        let closes = Family::<CloseLabel, Counter>::default();
+
+        registry.register(
+            "websocket_active_connections",
+            "Number of WebSocket sessions currently open",
+            active_connections.clone(),
+        );
+
+        registry.register(
+            "websocket_frame_count",
+            "Number of WebSocket frames received and sent",
+            frames.clone(),
+        );
+
+        registry.register(
+            "websocket_close_count",
+            "Number of WebSocket sessions closed, by close code",
+            closes.clone(),
+        );
+
+        WebSocketMetrics {
+            active_connections,
+            frames,
+            closes,
+        }
+    }
+
+    /// Counts a session as active until the returned guard is dropped.
+    pub fn connect(&self) -> ActiveConnection {
+        self.active_connections.inc();
+        ActiveConnection {
+            active_connections: self.active_connections.clone(),
+        }
+    }
+
+    pub fn frame_in(&self, kind: &str) {
+        self.frame("in", kind);
+    }
+
+    pub fn frame_out(&self, kind: &str) {
+        self.frame("out", kind);
+    }
+
+    pub fn close(&self, code: CloseCode, initiator: Initiator) {
+        self.closes
+            .get_or_create(&CloseLabel {
+                code: code.into(),
+                initiator: initiator.as_str().to_string(),
+            })
+            .inc();
+    }
+
+    fn frame(&self, direction: &str, kind: &str) {
+        self.frames
+            .get_or_create(&FrameLabel {
+                direction: direction.to_string(),
+                kind: kind.to_string(),
+            })
+            .inc();
+    }
+}
+
+pub struct ActiveConnection {
+    active_connections: Gauge,
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        self.active_connections.dec();
+    }
+}
+
+/// Token bucket limiting the messages of a single session.
+pub struct RateLimiter {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate_per_sec: f64, burst: u32) -> Self {
+        let capacity = f64::from(burst.max(1));
+        RateLimiter {
+            rate: rate_per_sec,
+            capacity,
+            tokens: capacity,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    /// Takes a token, returning whether one was available.
+    pub fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use serde_json::{json, Value};
+
+mod common;
+
+fn spawn_app(max_batch_size: usize) -> u16 {
+    let mut settings = common::settings();
+    settings.app.max_batch_size = max_batch_size;
+
+    common::spawn_app(settings).port
+}
+
+async fn reply_batch(port: u16, body: Value) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("http://localhost:{port}/v1/reply:batch"))
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn batch_returns_results_in_order() {
+    let port = spawn_app(10);
+
+    let response = reply_batch(
+        port,
+        json!({ "requests": [
+            { "message": "ping" },
+            { "message": "bye" },
+            { "message": "" },
+            { "message": "hello" },
+        ]}),
+    )
+    .await;
+
+    assert_eq!(200, response.status().as_u16());
+
+    let body: Value = response.json().await.unwrap();
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(4, results.len());
+
+    assert_eq!(200, results[0]["status"]);
+    assert_eq!("pong", results[0]["message"]);
+    assert_eq!("en", results[0]["locale"]);
+
+    assert_eq!(500, results[1]["status"]);
+    assert_eq!("Unknown message \"bye\"", results[1]["error"]["message"]);
+    assert!(results[1].get("message").is_none());
+
+    assert_eq!(400, results[2]["status"]);
+    assert_eq!("Validation failed", results[2]["error"]["message"]);
+    assert_eq!("message", results[2]["error"]["details"][0]["field"]);
+
+    assert_eq!(200, results[3]["status"]);
+    assert_eq!("world", results[3]["message"]);
+}
+
+#[tokio::test]
+async fn batch_larger_than_limit_is_rejected() {
+    let port = spawn_app(2);
+
+    let requests: Vec<_> = (0..3).map(|_| json!({ "message": "ping" })).collect();
+    let response = reply_batch(port, json!({ "requests": requests })).await;
+
+    assert_eq!(400, response.status().as_u16());
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(3, body["details"][0]["size"]);
+    assert_eq!(2, body["details"][0]["limit"]);
+}
+
+#[tokio::test]
+async fn empty_batch_is_rejected() {
+    let port = spawn_app(2);
+
+    let response = reply_batch(port, json!({ "requests": [] })).await;
+
+    assert_eq!(400, response.status().as_u16());
+}
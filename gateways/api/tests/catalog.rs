@@ -0,0 +1,263 @@
+use std::{io::Write, sync::Arc, time::Duration};
+
+use application::ports::CatalogSource;
+use infrastructure::{
+    cache::{CacheSettings, Eviction},
+    catalog::CatalogStore,
+};
+use serde_json::Value;
+
+mod common;
+
+use common::TestApp;
+
+fn spawn_app(catalog: Arc<CatalogStore>) -> TestApp {
+    spawn_app_with_cache(catalog, None)
+}
+
+fn spawn_app_with_cache(
+    catalog: Arc<CatalogStore>,
+    catalog_cache: Option<CacheSettings>,
+) -> TestApp {
+    let mut settings = common::settings();
+    settings.app.catalog_cache = catalog_cache;
+    settings.catalog = catalog;
+
+    common::spawn_app(settings)
+}
+
+async fn reply(app: &TestApp, message: &str) -> Option<String> {
+    let response = reqwest::Client::new()
+        .post(format!("http://localhost:{}/v1/reply", app.port))
+        .json(&serde_json::json!({ "message": message }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    if !response.status().is_success() {
+        return None;
+    }
+
+    let body: Value = response.json().await.unwrap();
+    body["message"].as_str().map(str::to_string)
+}
+
+const CATALOG: &str = r#"
+[[entries]]
+pattern = "hello"
+match = "case_insensitive"
+reply = "world"
+
+[[entries]]
+pattern = "^order \\d+$"
+match = "regex"
+reply = "order received"
+
+[[entries]]
+pattern = "order*"
+match = "glob"
+reply = "which order?"
+priority = -1
+"#;
+
+#[tokio::test]
+async fn replies_come_from_catalog_file_and_reload() {
+    let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    file.write_all(CATALOG.as_bytes()).unwrap();
+
+    let catalog = Arc::new(CatalogStore::from_file(file.path()).unwrap());
+    let app = spawn_app(catalog.clone());
+
+    assert_eq!(Some("world".to_string()), reply(&app, "HeLLo").await);
+    assert_eq!(
+        Some("order received".to_string()),
+        reply(&app, "order 42").await
+    );
+    assert_eq!(
+        Some("which order?".to_string()),
+        reply(&app, "order pizza").await
+    );
+    assert_eq!(None, reply(&app, "ping").await);
+
+    let version = catalog.current().version.clone();
+
+    std::fs::write(
+        file.path(),
+        "[[entries]]\npattern = \"ping\"\nreply = \"pong\"\n",
+    )
+    .unwrap();
+    assert!(catalog.reload().unwrap());
+
+    assert_eq!(Some("pong".to_string()), reply(&app, "ping").await);
+    assert_eq!(None, reply(&app, "hello").await);
+
+    let admin: Value = reqwest::get(format!(
+        "http://localhost:{}/admin/catalog",
+        app.metrics_port
+    ))
+    .await
+    .expect("Failed to execute request.")
+    .json()
+    .await
+    .unwrap();
+
+    assert_ne!(version, admin["version"]);
+    assert_eq!("ping", admin["entries"][0]["pattern"]);
+}
+
+#[tokio::test]
+async fn invalid_catalog_keeps_previous_version() {
+    let mut file = tempfile::Builder::new().suffix(".yaml").tempfile().unwrap();
+    file.write_all(b"entries:\n  - pattern: hello\n    reply: world\n")
+        .unwrap();
+
+    let catalog = CatalogStore::from_file(file.path()).unwrap();
+
+    std::fs::write(
+        file.path(),
+        "entries:\n  - pattern: \"(\"\n    match: regex\n    reply: broken\n",
+    )
+    .unwrap();
+
+    assert!(catalog.reload().is_err());
+    assert!(catalog.current().catalog.find("hello").is_some());
+}
+
+#[tokio::test]
+async fn templated_replies_use_captures_and_metadata() {
+    let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    file.write_all(
+        br#"{"default_locale": "pt-BR", "entries": [{
+            "pattern": "^order (?P<id>\\d+)$",
+            "match": "regex",
+            "reply": "{{ upper caller }}: order {{ $id }} ({{ $0 }}) in {{ locale }}"
+        }]}"#,
+    )
+    .unwrap();
+
+    let catalog = Arc::new(CatalogStore::from_file(file.path()).unwrap());
+    let app = spawn_app(catalog);
+
+    let response = reqwest::Client::new()
+        .post(format!("http://localhost:{}/v1/reply", app.port))
+        .header("x-caller", "alice")
+        .header("accept-language", "pt-BR, en;q=0.8")
+        .json(&serde_json::json!({ "message": "order 42" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!("ALICE: order 42 (order 42) in pt-BR", body["message"]);
+}
+
+#[tokio::test]
+async fn invalid_template_fails_reload() {
+    let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    file.write_all(b"[[entries]]\npattern = \"hello\"\nreply = \"world\"\n")
+        .unwrap();
+
+    let catalog = CatalogStore::from_file(file.path()).unwrap();
+
+    // captures are only defined for regex patterns
+    std::fs::write(
+        file.path(),
+        "[[entries]]\npattern = \"hello\"\nreply = \"{{ $1 }}\"\n",
+    )
+    .unwrap();
+
+    let err = catalog.reload().unwrap_err();
+    assert!(format!("{err:#}").contains("Capture"));
+    assert!(catalog.current().catalog.find("hello").is_some());
+}
+
+#[tokio::test]
+async fn catalog_lookups_are_cached_per_version_and_can_be_purged() {
+    let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    file.write_all(CATALOG.as_bytes()).unwrap();
+
+    let catalog = Arc::new(CatalogStore::from_file(file.path()).unwrap());
+    let app = spawn_app_with_cache(
+        catalog.clone(),
+        Some(CacheSettings {
+            max_entries: 16,
+            ttl: Duration::from_secs(60),
+            stale_while_revalidate: Duration::ZERO,
+            eviction: Eviction::Lfu,
+        }),
+    );
+    let admin = |path: &str| format!("http://localhost:{}/admin/{path}", app.metrics_port);
+
+    assert_eq!(Some("world".to_string()), reply(&app, "hello").await);
+    assert_eq!(Some("world".to_string()), reply(&app, "hello").await);
+    assert_eq!(None, reply(&app, "ping").await);
+
+    let cache: Value = reqwest::get(admin("caches/catalog_lookups"))
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+    let version = &catalog.current().version;
+    assert_eq!("lfu", cache["eviction"]);
+    assert_eq!(2, cache["size"]);
+    assert_eq!(format!("{version}:ping"), cache["entries"][0]["key"]);
+    assert_eq!(format!("{version}:hello"), cache["entries"][1]["key"]);
+    assert_eq!(1, cache["entries"][1]["hits"]);
+    assert_eq!("fresh", cache["entries"][1]["state"]);
+
+    let metrics = reqwest::get(format!("http://localhost:{}/metrics", app.metrics_port))
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    assert!(metrics.contains("cache_hits_total{cache=\"catalog_lookups\"} 1"));
+    assert!(metrics.contains("cache_misses_total{cache=\"catalog_lookups\"} 2"));
+
+    // lookups of the previous version do not answer for the reloaded one
+    std::fs::write(
+        file.path(),
+        "[[entries]]\npattern = \"ping\"\nreply = \"pong\"\n",
+    )
+    .unwrap();
+    assert!(catalog.reload().unwrap());
+    assert_eq!(Some("pong".to_string()), reply(&app, "ping").await);
+    assert_eq!(None, reply(&app, "hello").await);
+
+    let client = reqwest::Client::new();
+    let purged: Value = client
+        .delete(admin("caches/catalog_lookups"))
+        .query(&[("key", format!("{}:ping", catalog.current().version))])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(1, purged["purged"]);
+    let purged: Value = client
+        .delete(admin("caches/catalog_lookups"))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(1, purged["purged"]);
+
+    let caches: Value = reqwest::get(admin("caches"))
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+    assert_eq!("catalog_lookups", caches[0]["name"]);
+    assert_eq!(0, caches[0]["size"]);
+    let response = client
+        .delete(admin("caches/unknown"))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(404, response.status().as_u16());
+}
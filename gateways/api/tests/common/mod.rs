@@ -0,0 +1,83 @@
+// each test crate uses part of the helpers
+#![allow(dead_code)]
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use api::{openapi, payload, routes::graphql, server, versioning, websocket};
+use application::catalog::ReplyCatalog;
+use infrastructure::{catalog::CatalogStore, memory, shutdown::Shutdown};
+use prometheus_client::registry::Registry;
+use tokio::task::JoinHandle;
+
+/// A server running in the background.
+pub struct TestApp {
+    pub port: u16,
+    pub metrics_port: u16,
+    pub shutdown: Shutdown,
+    pub server: JoinHandle<Result<(), std::io::Error>>,
+}
+
+/// Sets up a server with `settings` and runs it in the background.
+pub fn spawn_app(settings: server::Settings) -> TestApp {
+    let app = server::Server::setup(settings).expect("failed to setup the server");
+    let port = app.port();
+    let metrics_port = app.metrics_port();
+    let shutdown = app.shutdown();
+
+    let server = tokio::spawn(app.run());
+
+    TestApp {
+        port,
+        metrics_port,
+        shutdown,
+        server,
+    }
+}
+
+/// Settings of a server listening on random ports with an empty catalog and in-memory
+/// adapters; tests override what they exercise.
+pub fn settings() -> server::Settings {
+    server::Settings {
+        app: server::AppSettings {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            request_timeout_sec: 10,
+            compression_min_size_bytes: 1024,
+            decompression_limit_bytes: 1024 * 1024,
+            payload_limits: payload::PayloadLimits {
+                json: 64 * 1024,
+                form: 1024,
+                payload: 1024,
+            },
+            route_payload_limits: HashMap::new(),
+            docs_ui: openapi::DocsUi::Disabled,
+            api_versions: vec![versioning::ApiVersion::new("v1")],
+            default_api_version: "v1".to_string(),
+            max_batch_size: 100,
+            idempotency_ttl_sec: 60,
+            websocket: websocket::WebSocketConfig {
+                max_frame_bytes: 64 * 1024,
+                heartbeat_interval: Duration::from_secs(5),
+                client_timeout: Duration::from_secs(15),
+                rate_limit_per_sec: 10.0,
+                rate_limit_burst: 20,
+            },
+            graphql: graphql::GraphQLConfig {
+                max_depth: 8,
+                max_complexity: 256,
+                persisted_query_cache_size: 64,
+                max_batch_operations: 10,
+            },
+            upstreams: HashMap::new(),
+            catalog_cache: None,
+            upstream_cache: None,
+        },
+        metrics: server::MetricSettings {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            registry: Registry::default(),
+        },
+        catalog: Arc::new(CatalogStore::new(ReplyCatalog::default())),
+        context: memory::context(),
+    }
+}
@@ -0,0 +1,139 @@
+use api::payload;
+
+mod common;
+
+use common::TestApp;
+
+fn spawn_app(compression_min_size_bytes: u64) -> TestApp {
+    let mut settings = common::settings();
+    settings.app.compression_min_size_bytes = compression_min_size_bytes;
+    settings.app.decompression_limit_bytes = 1024;
+    settings.app.payload_limits = payload::PayloadLimits {
+        json: 64 * 1024,
+        form: 64 * 1024,
+        payload: 64 * 1024,
+    };
+
+    common::spawn_app(settings)
+}
+
+#[tokio::test]
+async fn response_is_compressed_when_client_accepts_encoding() {
+    let app = spawn_app(0);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("http://localhost:{}/v1/reply", app.port))
+        .header("Accept-Encoding", "gzip")
+        .json(&serde_json::json!({ "message": "ping" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert!(response.status().is_success());
+    assert_eq!(
+        Some("gzip"),
+        response
+            .headers()
+            .get("content-encoding")
+            .and_then(|v| v.to_str().ok())
+    );
+}
+
+#[tokio::test]
+async fn response_below_threshold_is_not_compressed() {
+    let app = spawn_app(1024);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("http://localhost:{}/v1/reply", app.port))
+        .header("Accept-Encoding", "gzip")
+        .json(&serde_json::json!({ "message": "ping" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert!(response.status().is_success());
+    assert!(response.headers().get("content-encoding").is_none());
+}
+
+#[tokio::test]
+async fn metrics_are_not_compressed_unless_requested() {
+    let app = spawn_app(0);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("http://localhost:{}/metrics", app.metrics_port))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert!(response.status().is_success());
+    assert!(response.headers().get("content-encoding").is_none());
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("response_compression_ratio"));
+}
+
+#[tokio::test]
+async fn oversized_decompressed_body_is_rejected() {
+    let app = spawn_app(0);
+    let client = reqwest::Client::new();
+
+    // 4 KiB of spaces inside a JSON string gzip down to a few dozen bytes, but the decoded body
+    // exceeds the 1 KiB decompression limit
+    let body = format!("{{\"message\": \"{}\"}}", " ".repeat(4096));
+    let compressed = gzip(body.as_bytes());
+    assert!(compressed.len() < 1024);
+
+    let response = client
+        .post(format!("http://localhost:{}/v1/reply", app.port))
+        .header("Content-Type", "application/json")
+        .header("Content-Encoding", "gzip")
+        .body(compressed)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(413, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn unsupported_encodings_are_rejected() {
+    let app = spawn_app(1024);
+    let client = reqwest::Client::new();
+
+    for encoding in ["compress", "gzip, br", "snappy"] {
+        let response = client
+            .post(format!("http://localhost:{}/v1/reply", app.port))
+            .header("Content-Type", "application/json")
+            .header("Content-Encoding", encoding)
+            .body(r#"{"message":"ping"}"#)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        assert_eq!(415, response.status().as_u16(), "{encoding}");
+    }
+
+    let response = client
+        .post(format!("http://localhost:{}/v1/reply", app.port))
+        .header("Content-Type", "application/json")
+        .header("Content-Encoding", "identity")
+        .body(r#"{"message":"ping"}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert!(response.status().is_success());
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
@@ -0,0 +1,221 @@
+use api::routes::graphql;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+mod common;
+
+use common::TestApp;
+
+fn config() -> graphql::GraphQLConfig {
+    graphql::GraphQLConfig {
+        max_depth: 8,
+        max_complexity: 256,
+        persisted_query_cache_size: 64,
+        max_batch_operations: 2,
+    }
+}
+
+fn spawn_app(graphql: graphql::GraphQLConfig) -> TestApp {
+    let mut settings = common::settings();
+    settings.app.max_batch_size = 3;
+    settings.app.graphql = graphql;
+
+    common::spawn_app(settings)
+}
+
+async fn post_graphql(app: &TestApp, body: Value) -> Value {
+    let response = reqwest::Client::new()
+        .post(format!("http://localhost:{}/v1/graphql", app.port))
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(200, response.status().as_u16());
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn reply_query_answers_known_message() {
+    let app = spawn_app(config());
+
+    let body = post_graphql(
+        &app,
+        json!({ "query": "{ reply(message: \"ping\") { message locale } }" }),
+    )
+    .await;
+
+    assert_eq!(
+        json!({ "message": "pong", "locale": "en" }),
+        body["data"]["reply"]
+    );
+    assert!(body.get("errors").is_none());
+}
+
+#[tokio::test]
+async fn reply_query_reports_error_codes() {
+    let app = spawn_app(config());
+
+    let body = post_graphql(
+        &app,
+        json!({ "query": "{ reply(message: \"unknown\") { message } }" }),
+    )
+    .await;
+    assert_eq!("Unknown message \"unknown\"", body["errors"][0]["message"]);
+    assert_eq!("UNKNOWN_MESSAGE", body["errors"][0]["extensions"]["code"]);
+    assert_eq!(json!(["reply"]), body["errors"][0]["path"]);
+
+    let body = post_graphql(
+        &app,
+        json!({ "query": "{ reply(message: \"\") { message } }" }),
+    )
+    .await;
+    assert_eq!("BAD_USER_INPUT", body["errors"][0]["extensions"]["code"]);
+    assert_eq!(
+        "message",
+        body["errors"][0]["extensions"]["details"][0]["field"]
+    );
+}
+
+#[tokio::test]
+async fn batch_reply_reports_each_result_in_order() {
+    let app = spawn_app(config());
+
+    let body = post_graphql(
+        &app,
+        json!({
+            "query": "query Batch($messages: [String!]!) { batchReply(messages: $messages) { reply { message } error { code message } } }",
+            "variables": { "messages": ["ping", "unknown", "hello"] },
+        }),
+    )
+    .await;
+
+    let results = &body["data"]["batchReply"];
+    assert_eq!("pong", results[0]["reply"]["message"]);
+    assert_eq!(Value::Null, results[0]["error"]);
+    assert_eq!(Value::Null, results[1]["reply"]);
+    assert_eq!("UNKNOWN_MESSAGE", results[1]["error"]["code"]);
+    assert_eq!("world", results[2]["reply"]["message"]);
+}
+
+#[tokio::test]
+async fn batch_reply_enforces_batch_size() {
+    let app = spawn_app(config());
+
+    let body = post_graphql(
+        &app,
+        json!({ "query": "{ batchReply(messages: [\"a\", \"b\", \"c\", \"d\"]) { reply { message } } }" }),
+    )
+    .await;
+
+    assert_eq!("BAD_USER_INPUT", body["errors"][0]["extensions"]["code"]);
+    assert_eq!(4, body["errors"][0]["extensions"]["size"]);
+    assert_eq!(3, body["errors"][0]["extensions"]["limit"]);
+}
+
+#[tokio::test]
+async fn depth_and_complexity_are_limited() {
+    let app = spawn_app(graphql::GraphQLConfig {
+        max_depth: 2,
+        max_complexity: 6,
+        ..config()
+    });
+
+    let body = post_graphql(
+        &app,
+        json!({ "query": "{ batchReply(messages: [\"ping\"]) { error { code } } }" }),
+    )
+    .await;
+    assert_eq!("Query is nested too deep.", body["errors"][0]["message"]);
+
+    let body = post_graphql(
+        &app,
+        json!({ "query": "{ batchReply(messages: [\"ping\", \"hello\", \"ping\"]) { reply { message locale } } }" }),
+    )
+    .await;
+    assert_eq!("Query is too complex.", body["errors"][0]["message"]);
+
+    let body = post_graphql(
+        &app,
+        json!({ "query": "{ reply(message: \"ping\") { message locale } }" }),
+    )
+    .await;
+    assert_eq!("pong", body["data"]["reply"]["message"]);
+}
+
+#[tokio::test]
+async fn persisted_queries_are_registered_then_served_by_hash() {
+    let app = spawn_app(config());
+    let query = "{ reply(message: \"ping\") { message } }";
+    let extensions = json!({
+        "persistedQuery": { "version": 1, "sha256Hash": format!("{:x}", Sha256::digest(query)) }
+    });
+
+    let body = post_graphql(&app, json!({ "extensions": extensions })).await;
+    assert_eq!("PersistedQueryNotFound", body["errors"][0]["message"]);
+
+    let body = post_graphql(&app, json!({ "query": query, "extensions": extensions })).await;
+    assert_eq!("pong", body["data"]["reply"]["message"]);
+
+    let response = reqwest::Client::new()
+        .get(format!("http://localhost:{}/v1/graphql", app.port))
+        .query(&[("extensions", extensions.to_string())])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!("pong", body["data"]["reply"]["message"]);
+}
+
+#[tokio::test]
+async fn operations_can_be_batched_and_localized() {
+    let app = spawn_app(config());
+
+    let response = reqwest::Client::new()
+        .post(format!("http://localhost:{}/v1/graphql", app.port))
+        .header("Accept-Language", "pt-BR")
+        .json(&json!([
+            { "query": "{ reply(message: \"ping\") { message locale } }" },
+            { "query": "{ reply(message: \"hello\") { message } }" },
+        ]))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let body: Value = response.json().await.unwrap();
+
+    assert_eq!("pong", body[0]["data"]["reply"]["message"]);
+    assert_eq!("en", body[0]["data"]["reply"]["locale"]);
+    assert_eq!("world", body[1]["data"]["reply"]["message"]);
+}
+
+#[tokio::test]
+async fn batches_are_limited_in_operations() {
+    let app = spawn_app(config());
+    let operation = json!({ "query": "{ reply(message: \"ping\") { message } }" });
+
+    let response = reqwest::Client::new()
+        .post(format!("http://localhost:{}/v1/graphql", app.port))
+        .json(&json!([operation, operation, operation]))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(400, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(json!([{ "size": 3, "limit": 2 }]), body["details"]);
+
+    let body = post_graphql(&app, json!([operation, operation])).await;
+    assert_eq!("pong", body[1]["data"]["reply"]["message"]);
+}
+
+#[tokio::test]
+async fn get_requires_a_query() {
+    let app = spawn_app(config());
+    let response = reqwest::get(format!("http://localhost:{}/v1/graphql", app.port))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(400, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(json!([{ "reason": "query" }]), body["details"]);
+}
@@ -1,26 +1,60 @@
-use api::server;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use application::{
+    context::AppContext,
+    outbox::Backoff,
+    ports::{HealthCheck, PortError},
+};
+use async_trait::async_trait;
+use infrastructure::{
+    http_client::UpstreamSettings,
+    lease::Leases,
+    memory::{self, InMemoryLeaseStore},
+    resilience::{BulkheadSettings, CircuitBreakerSettings},
+};
 use prometheus_client::registry::Registry;
+use serde_json::{json, Value};
 
-#[tokio::test]
-async fn healthcheck_works() {
-    let registry = Registry::default();
+mod common;
 
-    let app = server::Server::setup(server::Settings {
-        app: server::AppSettings {
-            host: "127.0.0.1".to_string(),
-            port: 0,
-            request_timeout_sec: 10,
-        },
-        metrics: server::MetricSettings {
-            host: "127.0.0.1".to_string(),
-            port: 0,
-            registry,
-        },
-    })
-    .expect("failed to setup the server");
-    let port = app.port();
+struct StaticCheck {
+    name: &'static str,
+    up: bool,
+}
+
+#[async_trait]
+impl HealthCheck for StaticCheck {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn check(&self) -> Result<(), PortError> {
+        if self.up {
+            Ok(())
+        } else {
+            Err(PortError::new("connection refused", "port closed"))
+        }
+    }
+}
+
+fn spawn_app(context: AppContext) -> u16 {
+    spawn_app_with_upstreams(context, HashMap::new())
+}
+
+fn spawn_app_with_upstreams(
+    context: AppContext,
+    upstreams: HashMap<String, UpstreamSettings>,
+) -> u16 {
+    let mut settings = common::settings();
+    settings.app.upstreams = upstreams;
+    settings.context = context;
 
-    tokio::spawn(app.run());
+    common::spawn_app(settings).port
+}
+
+#[tokio::test]
+async fn healthcheck_works() {
+    let port = spawn_app(memory::context());
     let client = reqwest::Client::new();
 
     // Act
@@ -33,5 +67,147 @@ async fn healthcheck_works() {
 
     // Assert
     assert!(response.status().is_success());
-    assert_eq!(Some(0), response.content_length());
+    assert_eq!(json!({}), response.json::<Value>().await.unwrap());
+}
+
+#[tokio::test]
+async fn readiness_reports_every_check() {
+    let mut context = memory::context();
+    context.readiness = vec![
+        Arc::new(StaticCheck {
+            name: "database",
+            up: true,
+        }),
+        Arc::new(StaticCheck {
+            name: "broker",
+            up: true,
+        }),
+    ];
+    let port = spawn_app(context);
+
+    let response = reqwest::get(format!("http://localhost:{}/v1/readiness", port))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(
+        json!({
+            "status": "ready",
+            "checks": [
+                { "name": "database", "status": "up" },
+                { "name": "broker", "status": "up" },
+            ],
+        }),
+        response.json::<Value>().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn readiness_fails_when_a_check_fails() {
+    let mut context = memory::context();
+    context.readiness = vec![
+        Arc::new(StaticCheck {
+            name: "database",
+            up: false,
+        }),
+        Arc::new(StaticCheck {
+            name: "broker",
+            up: true,
+        }),
+    ];
+    let port = spawn_app(context);
+
+    let response = reqwest::get(format!("http://localhost:{}/readiness", port))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(503, response.status().as_u16());
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!("unavailable", body["status"]);
+    assert_eq!("down", body["checks"][0]["status"]);
+    assert_eq!(
+        "connection refused: port closed",
+        body["checks"][0]["error"]
+    );
+    assert_eq!("up", body["checks"][1]["status"]);
+}
+
+#[tokio::test]
+async fn readiness_reports_lease_holders() {
+    let leases = Leases::new(
+        Arc::new(InMemoryLeaseStore::default()),
+        "api-1",
+        Duration::from_secs(30),
+        &mut Registry::default(),
+    );
+    leases.register("retention");
+    assert!(leases.acquire("outbox.relay").await.unwrap());
+    let mut context = memory::context();
+    context.readiness = vec![Arc::new(leases)];
+    let port = spawn_app(context);
+
+    let response = reqwest::get(format!("http://localhost:{}/v1/readiness", port))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(
+        json!({
+            "status": "ready",
+            "checks": [{
+                "name": "leases",
+                "status": "up",
+                "details": {
+                    "instance": "api-1",
+                    "outbox.relay": "api-1",
+                    "retention": "none",
+                },
+            }],
+        }),
+        response.json::<Value>().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn readiness_reports_upstream_circuits() {
+    let upstream = UpstreamSettings {
+        base_url: "http://127.0.0.1:9".to_string(),
+        timeout: Duration::from_secs(1),
+        deadline: Duration::from_secs(3),
+        max_attempts: 1,
+        backoff: Backoff {
+            initial: Duration::from_millis(50),
+            max: Duration::from_secs(1),
+        },
+        circuit_breaker: CircuitBreakerSettings {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+            half_open_calls: 1,
+        },
+        bulkhead: BulkheadSettings {
+            max_concurrent_calls: 64,
+            max_wait: Duration::ZERO,
+        },
+    };
+    let port = spawn_app_with_upstreams(
+        memory::context(),
+        HashMap::from([("billing".to_string(), upstream)]),
+    );
+
+    let response = reqwest::get(format!("http://localhost:{}/v1/readiness", port))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(
+        json!({
+            "status": "ready",
+            "checks": [{
+                "name": "upstreams",
+                "status": "up",
+                "details": { "billing": "closed" },
+            }],
+        }),
+        response.json::<Value>().await.unwrap()
+    );
 }
@@ -0,0 +1,190 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+mod common;
+
+fn spawn_app() -> u16 {
+    let mut settings = common::settings();
+    settings.app.max_batch_size = 10;
+
+    common::spawn_app(settings).port
+}
+
+async fn reply(port: u16, caller: &str, message: &str) {
+    reqwest::Client::new()
+        .post(format!("http://localhost:{port}/v1/reply"))
+        .header("x-caller", caller)
+        .json(&json!({ "message": message }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+}
+
+async fn history(port: u16, query: &[(&str, &str)]) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(format!("http://localhost:{port}/v1/history"))
+        .query(query)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn exchanges_are_listed_newest_first() {
+    let port = spawn_app();
+    reply(port, "alice", "ping").await;
+    reply(port, "bob", "bye").await;
+
+    let response = history(port, &[]).await;
+
+    assert_eq!(200, response.status().as_u16());
+
+    let body: Value = response.json().await.unwrap();
+    let items = body["items"].as_array().unwrap();
+    assert_eq!(2, items.len());
+    assert!(body.get("next_cursor").is_none());
+
+    assert_eq!("bye", items[0]["message"]);
+    assert_eq!("bob", items[0]["caller"]);
+    assert_eq!("Unknown message \"bye\"", items[0]["error"]);
+    assert!(items[0].get("reply").is_none());
+
+    assert_eq!("ping", items[1]["message"]);
+    assert_eq!("alice", items[1]["caller"]);
+    assert_eq!("pong", items[1]["reply"]);
+    assert_eq!("en", items[1]["locale"]);
+    assert!(items[1]["created_at"].as_str().unwrap().ends_with('Z'));
+}
+
+/// Messages of the listed history, sorted, as entries saved in the same instant have no order.
+fn messages(body: &Value) -> Vec<&str> {
+    let mut messages: Vec<&str> = body["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["message"].as_str().unwrap())
+        .collect();
+    messages.sort();
+    messages
+}
+
+#[tokio::test]
+async fn batch_replies_are_listed() {
+    let port = spawn_app();
+    reqwest::Client::new()
+        .post(format!("http://localhost:{port}/v1/reply:batch"))
+        .header("x-caller", "alice")
+        .json(&json!({
+            "requests": [{ "message": "ping" }, { "message": "bye" }, { "message": "" }]
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    let body: Value = history(port, &[("caller", "alice")])
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // invalid requests are not answered, so not recorded either
+    assert_eq!(vec!["bye", "ping"], messages(&body));
+}
+
+#[tokio::test]
+async fn websocket_replies_are_listed() {
+    let port = spawn_app();
+    let (mut client, _) = connect_async(format!("ws://localhost:{port}/v1/ws"))
+        .await
+        .expect("Failed to connect.");
+    for message in ["ping", "hello"] {
+        client.send(Message::text(message)).await.unwrap();
+        assert!(client.next().await.unwrap().unwrap().is_text());
+    }
+
+    let body: Value = history(port, &[]).await.json().await.unwrap();
+
+    assert_eq!(vec!["hello", "ping"], messages(&body));
+}
+
+#[tokio::test]
+async fn pages_are_followed_with_the_cursor() {
+    let port = spawn_app();
+    for message in ["ping", "hello", "ping"] {
+        reply(port, "alice", message).await;
+    }
+
+    let first: Value = history(port, &[("limit", "2")]).await.json().await.unwrap();
+    assert_eq!(2, first["items"].as_array().unwrap().len());
+    let cursor = first["next_cursor"].as_str().unwrap();
+
+    let second: Value = history(port, &[("limit", "2"), ("cursor", cursor)])
+        .await
+        .json()
+        .await
+        .unwrap();
+    let items = second["items"].as_array().unwrap();
+    assert_eq!(1, items.len());
+    assert_eq!("ping", items[0]["message"]);
+    assert!(second.get("next_cursor").is_none());
+
+    let ids: Vec<_> = first["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .chain(items)
+        .map(|item| item["id"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(
+        3,
+        ids.iter().collect::<std::collections::HashSet<_>>().len()
+    );
+}
+
+#[tokio::test]
+async fn exchanges_are_filtered_by_caller_and_time() {
+    let port = spawn_app();
+    reply(port, "alice", "ping").await;
+    reply(port, "bob", "hello").await;
+
+    let body: Value = history(port, &[("caller", "bob")])
+        .await
+        .json()
+        .await
+        .unwrap();
+    let items = body["items"].as_array().unwrap();
+    assert_eq!(1, items.len());
+    assert_eq!("hello", items[0]["message"]);
+
+    let body: Value = history(port, &[("from", "2000-01-01T00:00:00Z")])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(2, body["items"].as_array().unwrap().len());
+
+    let body: Value = history(port, &[("to", "2000-01-01T00:00:00Z")])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(body["items"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn invalid_query_is_rejected() {
+    let port = spawn_app();
+
+    for (field, value) in [
+        ("cursor", "not-a-cursor"),
+        ("from", "yesterday"),
+        ("limit", "0"),
+    ] {
+        let response = history(port, &[(field, value)]).await;
+
+        assert_eq!(400, response.status().as_u16(), "{field}={value}");
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(field, body["details"][0]["field"]);
+    }
+}
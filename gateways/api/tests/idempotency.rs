@@ -0,0 +1,371 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use actix_web::http::Method;
+use api::middlewares::idempotency;
+use application::{
+    context::AppContext,
+    ports::{
+        HistoryFilter, IdempotencyClaim, IdempotencyKey, IdempotencyStore, MessageRecord,
+        MessageRepository, OutboxEvent, PortError, StoredResponse,
+    },
+};
+use async_trait::async_trait;
+use infrastructure::memory;
+use serde_json::Value;
+
+mod common;
+
+fn spawn_app(context: AppContext) -> u16 {
+    let mut settings = common::settings();
+    settings.app.max_batch_size = 10;
+    settings.context = context;
+
+    common::spawn_app(settings).port
+}
+
+async fn reply(port: u16, caller: &str, key: &str, body: &'static str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("http://localhost:{port}/v1/reply"))
+        .header("x-caller", caller)
+        .header("idempotency-key", key)
+        .header("content-type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn recorded(context: &AppContext) -> usize {
+    application::history::list(
+        context,
+        application::ports::HistoryFilter {
+            caller: None,
+            from: None,
+            to: None,
+            after: None,
+            limit: 100,
+        },
+    )
+    .await
+    .unwrap()
+    .records
+    .len()
+}
+
+/// Store remembering for how long claims and responses were kept.
+#[derive(Default)]
+struct ExpiryRecorder {
+    inner: memory::InMemoryIdempotencyStore,
+    claimed_for: Mutex<Vec<Duration>>,
+    completed_for: Mutex<Vec<Duration>>,
+}
+
+#[async_trait]
+impl IdempotencyStore for ExpiryRecorder {
+    async fn claim(
+        &self,
+        key: &IdempotencyKey,
+        fingerprint: &str,
+        now: SystemTime,
+        expires_at: SystemTime,
+    ) -> Result<IdempotencyClaim, PortError> {
+        let lifetime = expires_at.duration_since(now).unwrap();
+        self.claimed_for.lock().unwrap().push(lifetime);
+        self.inner.claim(key, fingerprint, now, expires_at).await
+    }
+
+    async fn complete(
+        &self,
+        key: &IdempotencyKey,
+        response: &StoredResponse,
+        expires_at: SystemTime,
+    ) -> Result<(), PortError> {
+        let lifetime = expires_at.duration_since(SystemTime::now()).unwrap();
+        self.completed_for.lock().unwrap().push(lifetime);
+        self.inner.complete(key, response, expires_at).await
+    }
+
+    async fn release(&self, key: &IdempotencyKey) -> Result<(), PortError> {
+        self.inner.release(key).await
+    }
+
+    async fn purge_expired(&self, now: SystemTime) -> Result<u64, PortError> {
+        self.inner.purge_expired(now).await
+    }
+}
+
+/// History failing the first `failures` saves, as through a database outage.
+struct FlakyMessages {
+    inner: Arc<dyn MessageRepository>,
+    failures: AtomicUsize,
+}
+
+#[async_trait]
+impl MessageRepository for FlakyMessages {
+    async fn save(&self, record: &MessageRecord, events: &[OutboxEvent]) -> Result<(), PortError> {
+        let failing = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok();
+        if failing {
+            return Err(PortError::new("failed to save", "connection lost"));
+        }
+        self.inner.save(record, events).await
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<MessageRecord>, PortError> {
+        self.inner.get(id).await
+    }
+
+    async fn list(&self, filter: &HistoryFilter) -> Result<Vec<MessageRecord>, PortError> {
+        self.inner.list(filter).await
+    }
+
+    async fn delete_before(&self, before: SystemTime) -> Result<u64, PortError> {
+        self.inner.delete_before(before).await
+    }
+}
+
+fn flaky_context(failures: usize) -> AppContext {
+    let mut context = memory::context();
+    context.messages = Arc::new(FlakyMessages {
+        inner: context.messages.clone(),
+        failures: AtomicUsize::new(failures),
+    });
+    context
+}
+
+#[tokio::test]
+async fn retry_replays_the_first_response() {
+    let context = memory::context();
+    let port = spawn_app(context.clone());
+
+    let first = reply(port, "alice", "order-1", r#"{"message":"ping"}"#).await;
+    assert_eq!(200, first.status().as_u16());
+    assert!(first.headers().get("idempotent-replayed").is_none());
+    let first: Value = first.json().await.unwrap();
+
+    let retry = reply(port, "alice", "order-1", r#"{"message":"ping"}"#).await;
+    assert_eq!(200, retry.status().as_u16());
+    assert_eq!("true", retry.headers()["idempotent-replayed"]);
+    assert_eq!("application/json", retry.headers()["content-type"]);
+    assert_eq!(first, retry.json::<Value>().await.unwrap());
+
+    assert_eq!(1, recorded(&context).await);
+}
+
+#[tokio::test]
+async fn error_responses_are_replayed_too() {
+    let context = memory::context();
+    let port = spawn_app(context.clone());
+
+    let first = reply(port, "alice", "order-1", r#"{"message":"bye"}"#).await;
+    let retry = reply(port, "alice", "order-1", r#"{"message":"bye"}"#).await;
+
+    assert_eq!(500, first.status().as_u16());
+    assert_eq!(500, retry.status().as_u16());
+    assert_eq!("true", retry.headers()["idempotent-replayed"]);
+    assert_eq!(1, recorded(&context).await);
+}
+
+#[tokio::test]
+async fn unrecorded_requests_are_run_again() {
+    let context = flaky_context(1);
+    let port = spawn_app(context.clone());
+
+    let first = reply(port, "alice", "order-1", r#"{"message":"ping"}"#).await;
+    assert_eq!(500, first.status().as_u16());
+    assert_eq!(0, recorded(&context).await);
+
+    // the key is released as soon as the failed request completes
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let retry = reply(port, "alice", "order-1", r#"{"message":"ping"}"#).await;
+    assert_eq!(200, retry.status().as_u16());
+    assert!(retry.headers().get("idempotent-replayed").is_none());
+    assert_eq!(1, recorded(&context).await);
+
+    let replayed = reply(port, "alice", "order-1", r#"{"message":"ping"}"#).await;
+    assert_eq!("true", replayed.headers()["idempotent-replayed"]);
+}
+
+#[tokio::test]
+async fn batches_with_unrecorded_requests_are_run_again() {
+    let context = flaky_context(1);
+    let port = spawn_app(context.clone());
+    let batch = || {
+        reqwest::Client::new()
+            .post(format!("http://localhost:{port}/v1/reply:batch"))
+            .header("x-caller", "alice")
+            .header("idempotency-key", "order-1")
+            .json(&serde_json::json!({ "requests": [{ "message": "ping" }] }))
+            .send()
+    };
+
+    let first: Value = batch().await.unwrap().json().await.unwrap();
+    assert_eq!(500, first["results"][0]["status"]);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let retry = batch().await.unwrap();
+    assert!(retry.headers().get("idempotent-replayed").is_none());
+    let retry: Value = retry.json().await.unwrap();
+    assert_eq!(200, retry["results"][0]["status"]);
+    assert_eq!(1, recorded(&context).await);
+}
+
+#[tokio::test]
+async fn keys_are_shared_by_the_versioned_and_unversioned_routes() {
+    let port = spawn_app(memory::context());
+
+    let first = reply(port, "alice", "order-1", r#"{"message":"ping"}"#).await;
+    assert_eq!(200, first.status().as_u16());
+    let retry = reqwest::Client::new()
+        .post(format!("http://localhost:{port}/reply"))
+        .header("x-caller", "alice")
+        .header("idempotency-key", "order-1")
+        .header("accept-version", "v1")
+        .header("content-type", "application/json")
+        .body(r#"{"message":"ping"}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(200, retry.status().as_u16());
+    assert_eq!("true", retry.headers()["idempotent-replayed"]);
+}
+
+#[tokio::test]
+async fn keys_are_scoped_to_the_caller() {
+    let context = memory::context();
+    let port = spawn_app(context.clone());
+
+    reply(port, "alice", "order-1", r#"{"message":"ping"}"#).await;
+    let other = reply(port, "bob", "order-1", r#"{"message":"hello"}"#).await;
+
+    assert_eq!(200, other.status().as_u16());
+    assert!(other.headers().get("idempotent-replayed").is_none());
+    assert_eq!(2, recorded(&context).await);
+}
+
+#[tokio::test]
+async fn key_reused_with_another_payload_is_rejected() {
+    let context = memory::context();
+    let port = spawn_app(context.clone());
+
+    reply(port, "alice", "order-1", r#"{"message":"ping"}"#).await;
+    let response = reply(port, "alice", "order-1", r#"{"message":"hello"}"#).await;
+
+    assert_eq!(422, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        "The idempotency key was used with a different payload or route",
+        body["message"]
+    );
+    assert_eq!(1, recorded(&context).await);
+}
+
+#[tokio::test]
+async fn key_reused_on_another_route_is_rejected() {
+    let context = memory::context();
+    let port = spawn_app(context.clone());
+    // a body both routes accept
+    let body = r#"{"message":"ping","requests":[{"message":"ping"}]}"#;
+
+    let first = reply(port, "alice", "order-1", body).await;
+    assert_eq!(200, first.status().as_u16());
+    let response = reqwest::Client::new()
+        .post(format!("http://localhost:{port}/v1/reply:batch"))
+        .header("x-caller", "alice")
+        .header("idempotency-key", "order-1")
+        .header("content-type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(422, response.status().as_u16());
+    assert!(response.headers().get("idempotent-replayed").is_none());
+}
+
+#[tokio::test]
+async fn retry_of_a_request_in_progress_is_rejected() {
+    let context = memory::context();
+    let port = spawn_app(context.clone());
+    let body = r#"{"message":"ping"}"#;
+    let fingerprint = idempotency::fingerprint(&Method::POST, "v1", "/reply", body.as_bytes());
+
+    let claim = application::idempotency::claim(
+        &context,
+        &IdempotencyKey {
+            caller: "alice".to_string(),
+            key: "order-1".to_string(),
+        },
+        &fingerprint,
+        Duration::from_secs(60),
+    )
+    .await
+    .unwrap();
+    assert_eq!(IdempotencyClaim::Claimed, claim);
+
+    let response = reply(port, "alice", "order-1", body).await;
+
+    assert_eq!(409, response.status().as_u16());
+    assert_eq!(0, recorded(&context).await);
+}
+
+#[tokio::test]
+async fn keys_are_claimed_for_the_request_timeout_then_kept_for_the_ttl() {
+    let store = Arc::new(ExpiryRecorder::default());
+    let mut context = memory::context();
+    context.idempotency = store.clone();
+    let port = spawn_app(context);
+
+    let response = reply(port, "alice", "order-1", r#"{"message":"ping"}"#).await;
+    assert_eq!(200, response.status().as_u16());
+
+    // the request timeout and idempotency TTL of the test settings
+    assert_eq!(
+        vec![Duration::from_secs(10)],
+        *store.claimed_for.lock().unwrap()
+    );
+    let completed_for = store.completed_for.lock().unwrap();
+    assert_eq!(1, completed_for.len());
+    assert!(completed_for[0] > Duration::from_secs(55));
+    assert!(completed_for[0] <= Duration::from_secs(60));
+}
+
+#[tokio::test]
+async fn invalid_key_is_rejected() {
+    let port = spawn_app(memory::context());
+
+    let response = reply(port, "alice", &"k".repeat(256), r#"{"message":"ping"}"#).await;
+
+    assert_eq!(400, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!("idempotency-key", body["details"][0]["field"]);
+}
+
+#[tokio::test]
+async fn requests_without_a_key_are_not_replayed() {
+    let context = memory::context();
+    let port = spawn_app(context.clone());
+
+    for _ in 0..2 {
+        let response = reqwest::Client::new()
+            .post(format!("http://localhost:{port}/v1/reply"))
+            .json(&serde_json::json!({ "message": "ping" }))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.headers().get("idempotent-replayed").is_none());
+    }
+
+    assert_eq!(2, recorded(&context).await);
+}
@@ -0,0 +1,133 @@
+use std::{io::Write, sync::Arc};
+
+use infrastructure::catalog::CatalogStore;
+use serde_json::Value;
+
+mod common;
+
+struct TestApp {
+    port: u16,
+    metrics_port: u16,
+    _catalog: tempfile::NamedTempFile,
+}
+
+const CATALOG: &str = r#"
+default_locale = "en"
+
+[errors.unknown_message]
+en = "Unknown message \"{{ message }}\""
+pt = "Mensagem desconhecida \"{{ message }}\""
+
+[[entries]]
+pattern = "hello"
+reply = "world"
+translations = { pt = "mundo", pt-PT = "mundo ({{ locale }})", fr = "monde" }
+"#;
+
+fn spawn_app() -> TestApp {
+    let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    file.write_all(CATALOG.as_bytes()).unwrap();
+
+    let mut settings = common::settings();
+    settings.catalog = Arc::new(CatalogStore::from_file(file.path()).unwrap());
+
+    let app = common::spawn_app(settings);
+
+    TestApp {
+        port: app.port,
+        metrics_port: app.metrics_port,
+        _catalog: file,
+    }
+}
+
+async fn reply(app: &TestApp, message: &str, accept_language: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("http://localhost:{}/v1/reply", app.port))
+        .header("accept-language", accept_language)
+        .json(&serde_json::json!({ "message": message }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn localized(app: &TestApp, message: &str, accept_language: &str) -> (String, Value) {
+    let response = reply(app, message, accept_language).await;
+    let language = response
+        .headers()
+        .get("content-language")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    (language, response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn replies_in_the_most_preferred_available_locale() {
+    let app = spawn_app();
+
+    let (language, body) = localized(&app, "hello", "de, fr;q=0.9, pt;q=0.8").await;
+    assert_eq!("fr", language);
+    assert_eq!("monde", body["message"]);
+
+    let (language, body) = localized(&app, "hello", "pt;q=0.5, fr;q=0").await;
+    assert_eq!("pt", language);
+    assert_eq!("mundo", body["message"]);
+
+    let (language, body) = localized(&app, "hello", "pt-pt").await;
+    assert_eq!("pt-PT", language);
+    assert_eq!("mundo (pt-PT)", body["message"]);
+}
+
+#[tokio::test]
+async fn regional_locales_fall_back_to_language_then_default() {
+    let app = spawn_app();
+
+    let (language, body) = localized(&app, "hello", "pt-BR").await;
+    assert_eq!("pt", language);
+    assert_eq!("mundo", body["message"]);
+
+    let (language, body) = localized(&app, "hello", "ja-JP").await;
+    assert_eq!("en", language);
+    assert_eq!("world", body["message"]);
+
+    let (language, body) = localized(&app, "hello", "*").await;
+    assert_eq!("en", language);
+    assert_eq!("world", body["message"]);
+}
+
+#[tokio::test]
+async fn errors_are_localized() {
+    let app = spawn_app();
+
+    let response = reply(&app, "bye", "pt-BR").await;
+    assert_eq!(500, response.status().as_u16());
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!("Mensagem desconhecida \"bye\"", body["message"]);
+}
+
+#[tokio::test]
+async fn fallbacks_are_counted_per_catalog_locale() {
+    let app = spawn_app();
+
+    reply(&app, "hello", "pt-BR").await;
+    reply(&app, "hello", "pt-BR").await;
+    reply(&app, "hello", "pt").await;
+    reply(&app, "hello", "ja").await;
+    reply(&app, "bye", "pt-PT").await;
+
+    let metrics = reqwest::get(format!("http://localhost:{}/metrics", app.metrics_port))
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    // locales the catalog has no text in are not used as labels
+    assert!(metrics.contains("locale_fallback_count_total{requested=\"other\",served=\"pt\"} 2"));
+    assert!(metrics.contains("locale_fallback_count_total{requested=\"other\",served=\"en\"} 1"));
+    assert!(metrics.contains("locale_fallback_count_total{requested=\"pt-pt\",served=\"pt\"} 1"));
+    assert!(!metrics.contains("requested=\"pt\","));
+    assert!(!metrics.contains("requested=\"pt-br\""));
+}
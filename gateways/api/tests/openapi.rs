@@ -0,0 +1,70 @@
+use api::openapi::{self, ApiDoc};
+use api::server;
+use utoipa::OpenApi;
+
+mod common;
+
+const SPEC_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+
+/// Fails when `openapi.json` is out of date. Run with `UPDATE_OPENAPI=1` to regenerate it.
+#[test]
+fn committed_spec_matches_generated_spec() {
+    let generated = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+        std::fs::write(SPEC_PATH, &generated).unwrap();
+    }
+
+    let committed = std::fs::read_to_string(SPEC_PATH).unwrap_or_default();
+    assert!(
+        committed == generated,
+        "openapi.json is out of date, run `UPDATE_OPENAPI=1 cargo test -p api --test openapi`"
+    );
+}
+
+#[tokio::test]
+async fn spec_and_docs_are_served() {
+    let mut settings = common::settings();
+    settings.app.docs_ui = openapi::DocsUi::SwaggerUi;
+
+    let app = server::Server::setup(settings).expect("failed to setup the server");
+    let port = app.port();
+
+    tokio::spawn(app.run());
+
+    let spec: serde_json::Value = reqwest::get(format!("http://localhost:{}/openapi.json", port))
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+    assert_eq!("3.1.0", spec["openapi"]);
+    assert!(spec["paths"]["/v1/reply"]["post"].is_object());
+
+    let docs = reqwest::get(format!("http://localhost:{}/docs", port))
+        .await
+        .expect("Failed to execute request.");
+    assert!(docs.status().is_success());
+    let page = docs.text().await.unwrap();
+    assert!(page.contains("swagger-ui"));
+    assert!(!page.contains("https://"));
+
+    let initializer = reqwest::get(format!(
+        "http://localhost:{}/docs/swagger-initializer.js",
+        port
+    ))
+    .await
+    .expect("Failed to execute request.")
+    .text()
+    .await
+    .unwrap();
+    assert!(initializer.contains("/openapi.json"));
+
+    let bundle = reqwest::get(format!(
+        "http://localhost:{}/docs/swagger-ui-bundle.js",
+        port
+    ))
+    .await
+    .expect("Failed to execute request.");
+    assert!(bundle.status().is_success());
+}
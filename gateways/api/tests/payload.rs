@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use api::{payload, server};
+use serde_json::Value;
+
+mod common;
+
+use common::TestApp;
+
+fn spawn_app(route_payload_limits: HashMap<String, payload::PayloadLimits>) -> TestApp {
+    let mut settings = common::settings();
+    settings.app.payload_limits = payload::PayloadLimits {
+        json: 1024,
+        form: 1024,
+        payload: 1024,
+    };
+    settings.app.route_payload_limits = route_payload_limits;
+
+    common::spawn_app(settings)
+}
+
+async fn post_reply(app: &TestApp, body: String) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("http://localhost:{}/v1/reply", app.port))
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn malformed_json_reports_error_location() {
+    let app = spawn_app(HashMap::new());
+
+    let response = post_reply(&app, "{\n  \"message\": \"hello\",,\n}".to_string()).await;
+
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!(
+        Some("application/json"),
+        response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+    );
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!("syntax", body["details"][0]["reason"]);
+    assert_eq!(2, body["details"][0]["line"]);
+    assert_eq!(22, body["details"][0]["column"]);
+}
+
+#[tokio::test]
+async fn oversized_json_is_rejected_with_limit() {
+    let app = spawn_app(HashMap::new());
+
+    let body = format!("{{\"message\": \"{}\"}}", "a".repeat(2048));
+    let response = post_reply(&app, body).await;
+
+    assert_eq!(413, response.status().as_u16());
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!("overflow", body["details"][0]["reason"]);
+    assert_eq!(1024, body["details"][0]["limit"]);
+}
+
+#[tokio::test]
+async fn route_limits_override_global_limits() {
+    let app = spawn_app(HashMap::from([(
+        "/v1/reply".to_string(),
+        payload::PayloadLimits {
+            json: 8,
+            form: 8,
+            payload: 8,
+        },
+    )]));
+
+    let response = post_reply(&app, "{\"message\": \"hello\"}".to_string()).await;
+
+    assert_eq!(413, response.status().as_u16());
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(8, body["details"][0]["limit"]);
+}
+
+#[test]
+fn route_limits_of_unknown_routes_fail_setup() {
+    let mut settings = common::settings();
+    settings.app.route_payload_limits = HashMap::from([(
+        "/v1/replies".to_string(),
+        payload::PayloadLimits {
+            json: 8,
+            form: 8,
+            payload: 8,
+        },
+    )]);
+
+    let err = server::Server::setup(settings)
+        .err()
+        .expect("setup should fail");
+
+    assert!(err.to_string().contains("/v1/replies"));
+    assert!(err.to_string().contains("/v1/reply, /v1/reply:batch"));
+}
+
+#[tokio::test]
+async fn rejected_payloads_are_counted() {
+    let app = spawn_app(HashMap::new());
+
+    post_reply(&app, "not json".to_string()).await;
+
+    let metrics = reqwest::get(format!("http://localhost:{}/metrics", app.metrics_port))
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    assert!(metrics.contains(
+        "rejected_payload_count_total{path=\"/v1/reply\",kind=\"json\",reason=\"syntax\"} 1"
+    ));
+}
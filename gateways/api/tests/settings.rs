@@ -0,0 +1,31 @@
+use std::io::Write;
+
+use api::settings::get_config;
+
+#[test]
+fn upstreams_are_loaded_from_the_config_file() {
+    let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    file.write_all(
+        br#"
+[app]
+port = 7500
+
+[upstreams.billing]
+base_url = "http://billing.internal:8080"
+timeout_ms = 250
+"#,
+    )
+    .unwrap();
+    std::env::set_var("APP_CONFIG_FILE", file.path());
+    std::env::set_var("APP_APP_PORT", "7600");
+
+    let settings = get_config().expect("failed to load the settings");
+
+    let billing = &settings.upstreams["billing"];
+    assert_eq!("http://billing.internal:8080", billing.base_url);
+    assert_eq!(250, billing.timeout_ms);
+    assert_eq!(5, billing.circuit_failure_threshold);
+    // environment variables take precedence over the file
+    assert_eq!(7600, settings.app.port);
+    assert_eq!("127.0.0.1", settings.app.host);
+}
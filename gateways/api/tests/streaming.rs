@@ -0,0 +1,208 @@
+use std::{io::Write, sync::Arc, time::Duration};
+
+use actix_web::{body, test, web, App, HttpResponse};
+use api::middlewares::timeout::Timeout;
+use application::ports::CatalogSource;
+use futures_util::stream;
+use infrastructure::catalog::CatalogStore;
+
+mod common;
+
+struct TestApp {
+    port: u16,
+    metrics_port: u16,
+    catalog: Arc<CatalogStore>,
+    file: tempfile::NamedTempFile,
+}
+
+fn spawn_app() -> TestApp {
+    let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    file.write_all(b"[[entries]]\npattern = \"ping\"\nreply = \"pong\"\n")
+        .unwrap();
+    let catalog = Arc::new(CatalogStore::from_file(file.path()).unwrap());
+
+    let mut settings = common::settings();
+    settings.app.request_timeout_sec = 1;
+    settings.app.compression_min_size_bytes = 0;
+    settings.catalog = catalog.clone();
+
+    let app = common::spawn_app(settings);
+
+    TestApp {
+        port: app.port,
+        metrics_port: app.metrics_port,
+        catalog,
+        file,
+    }
+}
+
+async fn open_stream(app: &TestApp, message: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{}/v1/reply/stream?message={message}",
+            app.port
+        ))
+        .header("accept-encoding", "gzip")
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn next_chunk(response: &mut reqwest::Response) -> String {
+    let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+        .await
+        .expect("no chunk received")
+        .expect("stream failed")
+        .expect("stream ended");
+
+    String::from_utf8(chunk.to_vec()).unwrap()
+}
+
+async fn metrics(app: &TestApp) -> String {
+    reqwest::get(format!("http://localhost:{}/metrics", app.metrics_port))
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn stream_sends_reply_event_uncompressed() {
+    let app = spawn_app();
+
+    let mut response = open_stream(&app, "ping").await;
+
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(
+        Some("text/event-stream"),
+        response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+    );
+    assert!(response.headers().get("content-encoding").is_none());
+
+    let event = next_chunk(&mut response).await;
+    let version = app.catalog.current().version.clone();
+    assert_eq!(
+        format!("event: reply\nid: {version}\ndata: {{\"message\":\"pong\"}}\n\n"),
+        event
+    );
+}
+
+#[tokio::test]
+async fn idle_stream_outlives_request_timeout_with_keep_alives() {
+    let app = spawn_app();
+
+    let mut response = open_stream(&app, "ping").await;
+    next_chunk(&mut response).await;
+
+    for _ in 0..4 {
+        assert_eq!(": keep-alive\n\n", next_chunk(&mut response).await);
+    }
+}
+
+#[tokio::test]
+async fn stream_follows_catalog_reloads() {
+    let mut app = spawn_app();
+
+    let mut response = open_stream(&app, "hello").await;
+    assert!(next_chunk(&mut response)
+        .await
+        .starts_with("event: error\n"));
+
+    app.file
+        .write_all(b"[[entries]]\npattern = \"hello\"\nreply = \"world\"\n")
+        .unwrap();
+    assert!(app.catalog.reload().unwrap());
+
+    let event = loop {
+        let chunk = next_chunk(&mut response).await;
+        if !chunk.starts_with(':') {
+            break chunk;
+        }
+    };
+    assert!(event.starts_with("event: reply\n"));
+    assert!(event.ends_with("data: {\"message\":\"world\"}\n\n"));
+}
+
+#[tokio::test]
+async fn open_streams_are_counted_until_disconnect() {
+    let app = spawn_app();
+
+    let mut response = open_stream(&app, "ping").await;
+    next_chunk(&mut response).await;
+
+    assert!(metrics(&app).await.contains("sse_open_streams 1\n"));
+    assert!(metrics(&app)
+        .await
+        .contains("sse_event_count_total{event=\"reply\"} 1\n"));
+
+    drop(response);
+
+    for _ in 0..50 {
+        if metrics(&app).await.contains("sse_open_streams 0\n") {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("stream still counted as open after the client disconnected");
+}
+
+#[actix_web::test]
+async fn timeout_applies_to_first_byte() {
+    let app = test::init_service(
+        App::new()
+            .wrap(Timeout::new(Duration::from_millis(100)))
+            .route(
+                "/",
+                web::get().to(|| async {
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                    HttpResponse::Ok().finish()
+                }),
+            ),
+    )
+    .await;
+
+    let err = test::try_call_service(&app, test::TestRequest::get().uri("/").to_request())
+        .await
+        .expect_err("request did not time out");
+
+    assert_eq!(408, err.as_response_error().status_code().as_u16());
+}
+
+#[actix_web::test]
+async fn timeout_applies_to_idle_gaps_between_chunks() {
+    let chunked = |gap: u64| {
+        stream::unfold(0, move |sent| async move {
+            if sent == 3 {
+                return None;
+            }
+            tokio::time::sleep(Duration::from_millis(gap)).await;
+            Some((Ok::<_, std::io::Error>(web::Bytes::from("chunk")), sent + 1))
+        })
+    };
+
+    let app = test::init_service(
+        App::new()
+            .wrap(Timeout::new(Duration::from_millis(200)))
+            .route(
+                "/steady",
+                web::get().to(move || async move { HttpResponse::Ok().streaming(chunked(100)) }),
+            )
+            .route(
+                "/stalled",
+                web::get().to(move || async move { HttpResponse::Ok().streaming(chunked(400)) }),
+            ),
+    )
+    .await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/steady").to_request()).await;
+    let body = body::to_bytes(res.into_body()).await.ok();
+    assert_eq!(Some(web::Bytes::from("chunkchunkchunk")), body);
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/stalled").to_request()).await;
+    assert_eq!(200, res.status().as_u16());
+    assert!(body::to_bytes(res.into_body()).await.is_err());
+}
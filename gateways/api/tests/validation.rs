@@ -0,0 +1,75 @@
+use api::{extractors::field_errors, payload};
+use serde_json::Value;
+use validator::Validate;
+
+mod common;
+
+fn spawn_app() -> u16 {
+    let mut settings = common::settings();
+    settings.app.payload_limits = payload::PayloadLimits {
+        json: 64 * 1024,
+        form: 64 * 1024,
+        payload: 64 * 1024,
+    };
+
+    common::spawn_app(settings).port
+}
+
+#[tokio::test]
+async fn invalid_reply_request_lists_field_errors() {
+    let port = spawn_app();
+
+    let response = reqwest::Client::new()
+        .post(format!("http://localhost:{}/v1/reply", port))
+        .json(&serde_json::json!({ "message": "" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(400, response.status().as_u16());
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!("message", body["details"][0]["field"]);
+    assert_eq!("length", body["details"][0]["code"]);
+    assert_eq!(1, body["details"][0]["params"]["min"]);
+}
+
+#[derive(Validate)]
+struct Inner {
+    #[validate(range(min = 1, max = 10))]
+    count: u32,
+}
+
+#[derive(Validate)]
+struct Outer {
+    #[validate(length(min = 1))]
+    name: String,
+    #[validate(nested)]
+    inner: Inner,
+    #[validate(nested)]
+    items: Vec<Inner>,
+}
+
+#[test]
+fn nested_errors_are_flattened_with_paths() {
+    let value = Outer {
+        name: String::new(),
+        inner: Inner { count: 0 },
+        items: vec![Inner { count: 5 }, Inner { count: 11 }],
+    };
+
+    let errors = field_errors(&value.validate().unwrap_err());
+    let fields: Vec<_> = errors
+        .iter()
+        .map(|err| (err.field.as_str(), err.code.as_str()))
+        .collect();
+
+    assert_eq!(
+        vec![
+            ("inner.count", "range"),
+            ("items[1].count", "range"),
+            ("name", "length"),
+        ],
+        fields
+    );
+}
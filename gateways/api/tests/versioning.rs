@@ -0,0 +1,96 @@
+use api::versioning;
+
+mod common;
+
+use common::TestApp;
+
+fn spawn_app() -> TestApp {
+    let mut settings = common::settings();
+    settings.app.api_versions = vec![
+        versioning::ApiVersion::from_unix_timestamps(
+            "v1",
+            Some(1_700_000_000),
+            Some(1_800_000_000),
+        ),
+        versioning::ApiVersion::new("v2"),
+    ];
+    settings.app.default_api_version = "v2".to_string();
+
+    common::spawn_app(settings)
+}
+
+#[tokio::test]
+async fn deprecated_version_announces_deprecation_and_sunset() {
+    let app = spawn_app();
+
+    let response = reqwest::get(format!("http://localhost:{}/v1/healthcheck", app.port))
+        .await
+        .expect("Failed to execute request.");
+
+    assert!(response.status().is_success());
+    assert_eq!("@1700000000", response.headers()["deprecation"]);
+    assert_eq!(
+        "Fri, 15 Jan 2027 08:00:00 GMT",
+        response.headers()["sunset"]
+    );
+}
+
+#[tokio::test]
+async fn version_is_selected_by_header_media_type_or_default() {
+    let app = spawn_app();
+    let client = reqwest::Client::new();
+    let url = format!("http://localhost:{}/healthcheck", app.port);
+
+    let by_header = client
+        .get(&url)
+        .header("Accept-Version", "1")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert!(by_header.status().is_success());
+    assert!(by_header.headers().contains_key("deprecation"));
+
+    let by_media_type = client
+        .get(&url)
+        .header("Accept", "application/json; version=1")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert!(by_media_type.status().is_success());
+    assert!(by_media_type.headers().contains_key("deprecation"));
+
+    let by_default = client
+        .get(&url)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert!(by_default.status().is_success());
+    assert!(!by_default.headers().contains_key("deprecation"));
+
+    let metrics = reqwest::get(format!("http://localhost:{}/metrics", app.metrics_port))
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    for (version, selected_by) in [("v1", "header"), ("v1", "media_type"), ("v2", "default")] {
+        assert!(metrics.contains(&format!(
+            "api_version_request_count_total{{version=\"{version}\",selected_by=\"{selected_by}\"}} 1"
+        )));
+    }
+}
+
+#[tokio::test]
+async fn unsupported_version_is_rejected() {
+    let app = spawn_app();
+
+    let response = reqwest::Client::new()
+        .get(format!("http://localhost:{}/healthcheck", app.port))
+        .header("Accept-Version", "v9")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(400, response.status().as_u16());
+}
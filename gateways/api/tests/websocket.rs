@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use api::websocket;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{protocol::frame::coding::CloseCode, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+mod common;
+
+use common::TestApp;
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn config() -> websocket::WebSocketConfig {
+    websocket::WebSocketConfig {
+        max_frame_bytes: 1024,
+        heartbeat_interval: Duration::from_secs(5),
+        client_timeout: Duration::from_secs(15),
+        rate_limit_per_sec: 10.0,
+        rate_limit_burst: 20,
+    }
+}
+
+fn spawn_app(websocket: websocket::WebSocketConfig) -> TestApp {
+    let mut settings = common::settings();
+    settings.app.request_timeout_sec = 1;
+    settings.app.compression_min_size_bytes = 0;
+    settings.app.websocket = websocket;
+
+    common::spawn_app(settings)
+}
+
+async fn connect(app: &TestApp) -> Client {
+    let (client, _) = connect_async(format!("ws://localhost:{}/v1/ws", app.port))
+        .await
+        .expect("Failed to connect.");
+    client
+}
+
+/// Next frame other than a ping, which the client answers on its own.
+async fn next_frame(client: &mut Client) -> Message {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("no frame received")
+            .expect("connection ended")
+            .expect("connection failed");
+
+        if !message.is_ping() {
+            return message;
+        }
+    }
+}
+
+async fn ask(client: &mut Client, message: &str) -> Value {
+    client.send(Message::text(message)).await.unwrap();
+
+    match next_frame(client).await {
+        Message::Text(text) => serde_json::from_str(&text).unwrap(),
+        other => panic!("unexpected frame {other:?}"),
+    }
+}
+
+async fn expect_close(client: &mut Client) -> CloseCode {
+    match next_frame(client).await {
+        Message::Close(Some(frame)) => frame.code,
+        other => panic!("unexpected frame {other:?}"),
+    }
+}
+
+async fn metrics(app: &TestApp) -> String {
+    reqwest::get(format!("http://localhost:{}/metrics", app.metrics_port))
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn text_frames_are_answered_in_order() {
+    let app = spawn_app(config());
+    let mut client = connect(&app).await;
+
+    let reply = ask(&mut client, "ping").await;
+    assert_eq!(200, reply["status"]);
+    assert_eq!("pong", reply["message"]);
+
+    let reply = ask(&mut client, "bye").await;
+    assert_eq!(500, reply["status"]);
+    assert_eq!("Unknown message \"bye\"", reply["error"]["message"]);
+
+    let reply = ask(&mut client, "").await;
+    assert_eq!(400, reply["status"]);
+
+    let metrics = metrics(&app).await;
+    assert!(metrics.contains("websocket_active_connections 1\n"));
+    assert!(metrics.contains("websocket_frame_count_total{direction=\"in\",kind=\"text\"} 3\n"));
+    assert!(metrics.contains("websocket_frame_count_total{direction=\"out\",kind=\"text\"} 3\n"));
+
+    client.close(None).await.unwrap();
+}
+
+#[tokio::test]
+async fn client_close_is_echoed_and_counted() {
+    let app = spawn_app(config());
+    let mut client = connect(&app).await;
+
+    client.close(None).await.unwrap();
+    while client.next().await.is_some() {}
+
+    for _ in 0..50 {
+        let metrics = metrics(&app).await;
+        if metrics.contains("websocket_active_connections 0\n") {
+            assert!(metrics
+                .contains("websocket_close_count_total{code=\"1000\",initiator=\"client\"} 1\n"));
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("session still counted as active after the client closed it");
+}
+
+#[tokio::test]
+async fn oversized_frames_close_the_session() {
+    let app = spawn_app(config());
+    let mut client = connect(&app).await;
+
+    client.send(Message::text("a".repeat(2048))).await.unwrap();
+
+    assert_eq!(CloseCode::Size, expect_close(&mut client).await);
+}
+
+#[tokio::test]
+async fn messages_above_the_rate_limit_are_rejected() {
+    let app = spawn_app(websocket::WebSocketConfig {
+        rate_limit_per_sec: 0.01,
+        rate_limit_burst: 2,
+        ..config()
+    });
+    let mut client = connect(&app).await;
+
+    assert_eq!(200, ask(&mut client, "ping").await["status"]);
+    assert_eq!(200, ask(&mut client, "ping").await["status"]);
+
+    let reply = ask(&mut client, "ping").await;
+    assert_eq!(429, reply["status"]);
+    assert_eq!("Rate limit exceeded", reply["error"]["message"]);
+}
+
+#[tokio::test]
+async fn silent_clients_are_disconnected() {
+    let app = spawn_app(websocket::WebSocketConfig {
+        heartbeat_interval: Duration::from_millis(100),
+        client_timeout: Duration::from_millis(300),
+        ..config()
+    });
+    let mut client = connect(&app).await;
+
+    // pings are only answered while the client reads
+    tokio::time::sleep(Duration::from_millis(800)).await;
+
+    assert_eq!(CloseCode::Policy, expect_close(&mut client).await);
+}
+
+#[tokio::test]
+async fn heartbeats_keep_sessions_alive_past_the_request_timeout() {
+    let app = spawn_app(websocket::WebSocketConfig {
+        heartbeat_interval: Duration::from_millis(200),
+        client_timeout: Duration::from_millis(1000),
+        ..config()
+    });
+    let mut client = connect(&app).await;
+
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(2500);
+    while let Ok(message) = tokio::time::timeout_at(deadline, client.next()).await {
+        let message = message.expect("connection ended").unwrap();
+        assert!(message.is_ping(), "unexpected frame {message:?}");
+    }
+
+    assert_eq!(200, ask(&mut client, "ping").await["status"]);
+}
+
+#[tokio::test]
+async fn shutdown_closes_sessions_and_stops_the_server() {
+    let app = spawn_app(config());
+    let mut client = connect(&app).await;
+    assert_eq!(200, ask(&mut client, "ping").await["status"]);
+
+    app.shutdown.trigger();
+
+    assert_eq!(CloseCode::Away, expect_close(&mut client).await);
+    drop(client);
+
+    tokio::time::timeout(Duration::from_secs(10), app.server)
+        .await
+        .expect("server did not stop")
+        .unwrap()
+        .unwrap();
+}
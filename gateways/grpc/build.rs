@@ -0,0 +1,23 @@
+//! Generates the `reply.v1.Reply` service stubs from the definition below rather than from
+//! `proto/reply/v1/reply.proto`, so building does not need `protoc`. Keep both in sync; the
+//! messages themselves live in `src/proto.rs`.
+
+fn main() {
+    let reply = tonic_build::manual::Service::builder()
+        .name("Reply")
+        .package("reply.v1")
+        .comment("Answers messages from the reply catalog.")
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("reply")
+                .route_name("Reply")
+                .comment("Reply to a known message.")
+                .input_type("super::ReplyRequest")
+                .output_type("super::ReplyResponse")
+                .codec_path("tonic_prost::ProstCodec")
+                .build(),
+        )
+        .build();
+
+    tonic_build::manual::Builder::new().compile(&[reply]);
+}
@@ -0,0 +1,4 @@
+pub mod middlewares;
+pub mod proto;
+pub mod server;
+pub mod service;
@@ -0,0 +1,120 @@
+use grpc::server;
+use std::{net::TcpListener, sync::Arc, time::Duration};
+
+use application::{
+    catalog::ReplyCatalog,
+    outbox::{Backoff, RelaySettings},
+};
+use infrastructure::{
+    self,
+    catalog::CatalogStore,
+    memory, metrics_server,
+    outbox::OutboxRelay,
+    retention,
+    scheduler::{Schedule, Scheduler},
+    shutdown::{self, Shutdown},
+    telemetry,
+};
+use prometheus_client::registry::Registry;
+use tracing::log;
+
+mod settings;
+use settings::get_config;
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let settings = get_config()?;
+
+    let _guard = telemetry::setup(telemetry::Settings {
+        log: telemetry::LoggingSettings {
+            format: telemetry::LoggingOptions::PrettyPrint,
+        },
+        telemetry: telemetry::TelemetrySettings {
+            host: settings.telemetry.host,
+            port: settings.telemetry.port,
+            sampler_param: settings.telemetry.sampler_param,
+        },
+        service_name: settings.app.service_name,
+    });
+
+    let mut registry = Registry::default();
+
+    let catalog = Arc::new(match &settings.catalog.path {
+        Some(path) => CatalogStore::from_file(path)?,
+        None => CatalogStore::new(ReplyCatalog::default()),
+    });
+    if settings.catalog.path.is_some() {
+        catalog
+            .clone()
+            .watch(Duration::from_secs(settings.catalog.reload_interval_sec));
+    }
+
+    let mut context = memory::context();
+    context.catalog = catalog;
+
+    // replies are recorded in memory, where their events are relayed from and the history is
+    // purged, as they are by the HTTP gateway
+    let mut scheduler = Scheduler::new(&mut registry);
+    scheduler.add(
+        OutboxRelay::new(
+            RelaySettings {
+                batch_size: settings.outbox.batch_size,
+                lease: Duration::from_secs(settings.outbox.lease_sec),
+                backoff: Backoff {
+                    initial: Duration::from_millis(settings.outbox.retry_initial_ms),
+                    max: Duration::from_secs(settings.outbox.retry_max_sec),
+                },
+            },
+            &mut registry,
+        )
+        .job(
+            context.clone(),
+            Duration::from_millis(settings.outbox.poll_interval_ms),
+        )
+        .timeout(Duration::from_secs(settings.outbox.relay_timeout_sec)),
+    );
+    scheduler.add(
+        retention::job(
+            context.clone(),
+            (settings.history.retention_sec > 0)
+                .then(|| Duration::from_secs(settings.history.retention_sec)),
+            Schedule::Every(Duration::from_secs(settings.history.purge_interval_sec)),
+        )
+        .timeout(Duration::from_secs(settings.history.purge_timeout_sec)),
+    );
+
+    let server = server::Server::setup(
+        server::Settings {
+            host: settings.grpc.host,
+            port: settings.grpc.port,
+            context,
+        },
+        &mut registry,
+    )?;
+
+    let metrics_listener =
+        TcpListener::bind(format!("{}:{}", settings.metric.host, settings.metric.port))?;
+    log::info!(
+        "Metrics Server listening on {}:{}",
+        settings.metric.host,
+        settings.metric.port
+    );
+
+    let shutdown = Shutdown::new();
+    scheduler.spawn(shutdown.clone());
+    let signalled = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        log::info!("Shutdown signal received");
+        signalled.trigger();
+    });
+
+    let (served, exported) = tokio::join!(
+        server.run(shutdown.clone()),
+        metrics_server::serve(metrics_listener, registry, shutdown)
+    );
+    served?;
+    exported?;
+
+    Ok(())
+}
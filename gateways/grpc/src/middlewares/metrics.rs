@@ -0,0 +1,148 @@
+use std::{
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use futures_util::future::BoxFuture;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, histogram::Histogram},
+    registry::Registry,
+};
+use tower::{Layer, Service};
+
+use super::response_code;
+
+/// Methods served, by service. Calls to any other path are labelled `unmatched`, so clients
+/// cannot grow the label set.
+const ROUTES: [(&str, &[&str]); 2] = [
+    ("reply.v1.Reply", &["Reply"]),
+    ("grpc.health.v1.Health", &["Check", "Watch"]),
+];
+
+const UNMATCHED: &str = "unmatched";
+
+/// Labels of a call. `status` is the numeric gRPC status code.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RequestLabel {
+    pub service: &'static str,
+    pub method: &'static str,
+    pub status: i32,
+}
+
+/// Service and method of a known `/{service}/{method}` path.
+fn route(path: &str) -> (&'static str, &'static str) {
+    let (service, method) = path
+        .strip_prefix('/')
+        .and_then(|path| path.split_once('/'))
+        .unwrap_or_default();
+
+    ROUTES
+        .iter()
+        .find(|(known, _)| *known == service)
+        .and_then(|(known, methods)| {
+            methods
+                .iter()
+                .find(|known| **known == method)
+                .map(|method| (*known, *method))
+        })
+        .unwrap_or((UNMATCHED, UNMATCHED))
+}
+
+/// Counts and times every call. The metrics are named as the HTTP gateway's, under a `grpc_`
+/// prefix, so both gateways can share a registry.
+#[derive(Clone)]
+pub struct Metrics {
+    request_duration: Family<RequestLabel, Histogram>,
+    request_count: Family<RequestLabel, Counter>,
+}
+
+impl Metrics {
+    pub fn new(registry: &mut Registry) -> Self {
+        let registry = registry.sub_registry_with_prefix("grpc");
+
+        let request_count = Family::<RequestLabel, Counter>::default();
+        let request_duration = Family::<RequestLabel, Histogram>::new_with_constructor(|| {
+            let buckets = [
+                1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 75.0, 100.0, 250.0, 500.0, 750.0, 1000.0, 1250.0,
+                1500.0, 2000.0,
+            ];
+            Histogram::new(buckets.into_iter())
+        });
+
+        registry.register(
+            "request_count",
+            "Number of calls received",
+            request_count.clone(),
+        );
+
+        registry.register(
+            "request_duration_ms",
+            "Call duration",
+            request_duration.clone(),
+        );
+
+        Metrics {
+            request_duration,
+            request_count,
+        }
+    }
+}
+
+impl<S> Layer<S> for Metrics {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        MetricsService {
+            service,
+            metrics: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MetricsService<S> {
+    service: S,
+    metrics: Metrics,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for MetricsService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let now = Instant::now();
+        let (service, method) = route(req.uri().path());
+
+        let fut = self.service.call(req);
+        let metrics = self.metrics.clone();
+
+        Box::pin(async move {
+            let res = fut.await?;
+
+            let elapsed = now.elapsed().as_millis() as f64;
+            let label = RequestLabel {
+                service,
+                method,
+                status: response_code(&res) as i32,
+            };
+
+            metrics
+                .request_duration
+                .get_or_create(&label)
+                .observe(elapsed);
+            metrics.request_count.get_or_create(&label).inc();
+
+            Ok(res)
+        })
+    }
+}
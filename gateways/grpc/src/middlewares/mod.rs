@@ -0,0 +1,12 @@
+pub mod metrics;
+pub mod tracing;
+
+use tonic::{Code, Status};
+
+/// Status of a call as seen by the layers: errors come back as trailers-only responses with
+/// a `grpc-status` header, while successful calls report `OK` in trailers the layers never see.
+fn response_code<B>(res: &http::Response<B>) -> Code {
+    Status::from_header_map(res.headers())
+        .map(|status| status.code())
+        .unwrap_or(Code::Ok)
+}
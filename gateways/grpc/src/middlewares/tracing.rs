@@ -0,0 +1,113 @@
+use std::task::{Context, Poll};
+
+use futures_util::future::BoxFuture;
+use infrastructure::telemetry;
+use opentelemetry::propagation::Extractor;
+use tonic::Code;
+use tower::{Layer, Service};
+use tracing::{field::Empty, Instrument, Span};
+
+use super::response_code;
+
+const HEALTH_SERVICE: &str = "grpc.health.v1.Health";
+
+/// Opens the root span of every call, continuing the trace of the caller when its metadata
+/// carries a W3C trace context. Health checks are traced at `DEBUG`, as `/healthcheck` is by
+/// the HTTP gateway.
+#[derive(Clone, Copy, Default)]
+pub struct Tracing;
+
+impl<S> Layer<S> for Tracing {
+    type Service = TracingService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        TracingService { service }
+    }
+}
+
+#[derive(Clone)]
+pub struct TracingService<S> {
+    service: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for TracingService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let span = root_span(req.uri().path());
+        telemetry::set_remote_parent(&span, &HeaderExtractor(req.headers()));
+
+        let fut = self.service.call(req);
+        let outcome = span.clone();
+
+        Box::pin(
+            async move {
+                let res = fut.await?;
+
+                let code = response_code(&res);
+                outcome.record("rpc.grpc.status_code", code as i32);
+                outcome.record(
+                    "otel.status_code",
+                    if code == Code::Ok { "OK" } else { "ERROR" },
+                );
+                if code != Code::Ok {
+                    tracing::error!(code = ?code, "call failed");
+                }
+
+                Ok(res)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+fn root_span(path: &str) -> Span {
+    let (service, method) = path
+        .trim_start_matches('/')
+        .split_once('/')
+        .unwrap_or((path, ""));
+
+    macro_rules! call_span {
+        ($level:expr) => {
+            tracing::span!(
+                $level,
+                "gateways.grpc.call",
+                otel.kind = "server",
+                otel.name = path,
+                otel.status_code = Empty,
+                rpc.system = "grpc",
+                rpc.service = service,
+                rpc.method = method,
+                rpc.grpc.status_code = Empty,
+            )
+        };
+    }
+
+    if service == HEALTH_SERVICE {
+        call_span!(tracing::Level::DEBUG)
+    } else {
+        call_span!(tracing::Level::INFO)
+    }
+}
+
+struct HeaderExtractor<'a>(&'a http::HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
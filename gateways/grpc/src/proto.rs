@@ -0,0 +1,23 @@
+//! Messages of `proto/reply/v1/reply.proto`, written by hand, and the service stubs generated
+//! from them by `build.rs`.
+
+pub mod reply {
+    pub mod v1 {
+        #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+        pub struct ReplyRequest {
+            #[prost(string, tag = "1")]
+            pub message: ::prost::alloc::string::String,
+        }
+
+        #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+        pub struct ReplyResponse {
+            #[prost(string, tag = "1")]
+            pub message: ::prost::alloc::string::String,
+            /// Locale the reply is written in.
+            #[prost(string, tag = "2")]
+            pub locale: ::prost::alloc::string::String,
+        }
+
+        include!(concat!(env!("OUT_DIR"), "/reply.v1.Reply.rs"));
+    }
+}
@@ -0,0 +1,92 @@
+use std::{net::TcpListener, time::Duration};
+
+use application::context::AppContext;
+use infrastructure::shutdown::Shutdown;
+use prometheus_client::registry::Registry;
+use tonic::transport::server::TcpIncoming;
+use tonic_health::ServingStatus;
+use tracing::log;
+
+use crate::middlewares::{metrics::Metrics, tracing::Tracing};
+use crate::proto::reply::v1::reply_server::ReplyServer;
+use crate::service::ReplyService;
+
+/// Time in-flight calls, such as health watches, get to finish once shutdown starts. Same as
+/// the default of the HTTP gateway.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Settings {
+    pub host: String,
+    pub port: u16,
+    /// Ports the use cases run against.
+    pub context: AppContext,
+}
+
+pub struct Server {
+    port: u16,
+    listener: TcpListener,
+    metrics: Metrics,
+    context: AppContext,
+}
+
+impl Server {
+    /// Binds the listener and registers the call metrics into `registry`, which may be shared
+    /// with the HTTP gateway when both run in the same binary.
+    pub fn setup(settings: Settings, registry: &mut Registry) -> eyre::Result<Server> {
+        let listener = TcpListener::bind(format!("{}:{}", settings.host, settings.port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+
+        log::info!(
+            "gRPC Server listening on {}:{}",
+            settings.host,
+            settings.port
+        );
+
+        Ok(Server {
+            port,
+            listener,
+            metrics: Metrics::new(registry),
+            context: settings.context,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Serves `reply.v1.Reply` and `grpc.health.v1.Health` until `shutdown` is triggered. The
+    /// health service then reports `NOT_SERVING` while in-flight calls drain.
+    pub async fn run(self, shutdown: Shutdown) -> eyre::Result<()> {
+        let (health, health_service) = tonic_health::server::health_reporter();
+        health.set_serving::<ReplyServer<ReplyService>>().await;
+
+        let incoming = TcpIncoming::from(tokio::net::TcpListener::from_std(self.listener)?);
+
+        let stopping = shutdown.clone();
+        let draining = async move {
+            stopping.wait().await;
+            health
+                .set_service_status("", ServingStatus::NotServing)
+                .await;
+            health.set_not_serving::<ReplyServer<ReplyService>>().await;
+        };
+
+        let serve = tonic::transport::Server::builder()
+            .layer(Tracing)
+            .layer(self.metrics)
+            .add_service(health_service)
+            .add_service(ReplyServer::new(ReplyService::new(self.context)))
+            .serve_with_incoming_shutdown(incoming, draining);
+
+        tokio::select! {
+            result = serve => result?,
+            _ = async {
+                shutdown.wait().await;
+                tokio::time::sleep(SHUTDOWN_TIMEOUT).await;
+            } => log::warn!("gRPC calls still running after {SHUTDOWN_TIMEOUT:?} were dropped"),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use application::{
+    context::AppContext,
+    locale::Locale,
+    messages::{self, ReplyError},
+    template::RequestMetadata,
+};
+use infrastructure::telemetry;
+use tonic::{metadata::MetadataMap, Request, Response, Status};
+use tracing::Span;
+
+use crate::proto::reply::v1::{reply_server::Reply, ReplyRequest, ReplyResponse};
+
+/// Metadata key identifying the principal making the request.
+pub const CALLER: &str = "x-caller";
+/// Metadata key ranking the preferred reply locales, as the HTTP `Accept-Language` header.
+pub const ACCEPT_LANGUAGE: &str = "accept-language";
+
+const MAX_MESSAGE_LENGTH: usize = 256;
+
+/// `reply.v1.Reply`, answered from the same catalog as the HTTP gateway and recorded, with its
+/// event, in the same history.
+pub struct ReplyService {
+    context: AppContext,
+}
+
+impl ReplyService {
+    pub fn new(context: AppContext) -> Self {
+        ReplyService { context }
+    }
+}
+
+#[tonic::async_trait]
+impl Reply for ReplyService {
+    #[tracing::instrument(name = "gateways.grpc.reply", skip(self))]
+    async fn reply(
+        &self,
+        request: Request<ReplyRequest>,
+    ) -> Result<Response<ReplyResponse>, Status> {
+        let locale = accepted_locale(request.metadata());
+        let metadata = RequestMetadata {
+            caller: request
+                .metadata()
+                .get(CALLER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            trace_id: telemetry::trace_id(&Span::current()),
+        };
+        let message = request.into_inner().message;
+
+        let length = message.chars().count();
+        if !(1..=MAX_MESSAGE_LENGTH).contains(&length) {
+            return Err(Status::invalid_argument(format!(
+                "message must be between 1 and {MAX_MESSAGE_LENGTH} characters long"
+            )));
+        }
+
+        let result = messages::reply(
+            &self.context.catalog.current().catalog,
+            &message,
+            &locale,
+            &metadata,
+        );
+        messages::record(&self.context, &message, &result, &metadata)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+
+        match result {
+            Ok(reply) => Ok(Response::new(ReplyResponse {
+                message: reply.text,
+                locale: reply.locale,
+            })),
+            Err(err @ ReplyError::UnknownMessage(..)) => Err(Status::not_found(err.to_string())),
+        }
+    }
+}
+
+/// Languages listed in [`ACCEPT_LANGUAGE`], ranked by q-factor. Wildcards and languages with
+/// `q=0` are left out, as they are by the HTTP gateway.
+fn accepted_locale(metadata: &MetadataMap) -> Locale {
+    let mut languages: Vec<(f32, &str)> = metadata
+        .get_all(ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|language| {
+            let mut params = language.split(';');
+            let tag = params.next()?.trim();
+            let quality = match params.find_map(|param| param.trim().strip_prefix("q=")) {
+                Some(quality) => quality.trim().parse().ok()?,
+                None => 1.0,
+            };
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((quality, tag))
+        })
+        .collect();
+
+    languages.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    Locale::new(languages.into_iter().map(|(_, tag)| tag))
+}
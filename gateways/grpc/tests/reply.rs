@@ -0,0 +1,288 @@
+use std::{
+    collections::BTreeMap,
+    net::TcpListener,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use application::{
+    catalog::{CatalogDefinition, CatalogEntry, ErrorMessages, ReplyCatalog},
+    context::AppContext,
+    ports::DomainEvent,
+};
+use grpc::{
+    proto::reply::v1::{reply_client::ReplyClient, ReplyRequest, ReplyResponse},
+    server,
+};
+use http::uri::PathAndQuery;
+use infrastructure::{catalog::CatalogStore, memory, metrics_server, shutdown::Shutdown};
+use prometheus_client::{encoding::text::encode, registry::Registry};
+use tonic::{transport::Channel, Code, Request};
+use tonic_health::pb::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+use tonic_prost::ProstCodec;
+
+struct TestApp {
+    port: u16,
+    context: AppContext,
+    registry: Registry,
+    shutdown: Shutdown,
+    server: tokio::task::JoinHandle<eyre::Result<()>>,
+}
+
+fn spawn_app(catalog: ReplyCatalog) -> TestApp {
+    let mut registry = Registry::default();
+    let mut context = memory::context();
+    context.catalog = Arc::new(CatalogStore::new(catalog));
+
+    let app = server::Server::setup(
+        server::Settings {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            context: context.clone(),
+        },
+        &mut registry,
+    )
+    .expect("failed to setup the server");
+    let port = app.port();
+    let shutdown = Shutdown::new();
+
+    let server = tokio::spawn(app.run(shutdown.clone()));
+
+    TestApp {
+        port,
+        context,
+        registry,
+        shutdown,
+        server,
+    }
+}
+
+async fn channel(app: &TestApp) -> Channel {
+    Channel::from_shared(format!("http://127.0.0.1:{}", app.port))
+        .unwrap()
+        .connect()
+        .await
+        .expect("Failed to connect.")
+}
+
+fn reply_request(message: &str) -> Request<ReplyRequest> {
+    Request::new(ReplyRequest {
+        message: message.to_string(),
+    })
+}
+
+fn translated_catalog() -> ReplyCatalog {
+    ReplyCatalog::new(CatalogDefinition {
+        default_locale: "en".to_string(),
+        entries: vec![CatalogEntry {
+            translations: BTreeMap::from([("pt-BR".to_string(), "Olá {{ caller }}".to_string())]),
+            ..CatalogEntry::exact("hello", "Hello {{ caller }}")
+        }],
+        errors: ErrorMessages::default(),
+    })
+    .unwrap()
+}
+
+#[tokio::test]
+async fn reply_answers_known_message() {
+    let app = spawn_app(ReplyCatalog::default());
+    let mut client = ReplyClient::new(channel(&app).await);
+
+    let response = client.reply(reply_request("ping")).await.unwrap();
+
+    assert_eq!("pong", response.get_ref().message);
+    assert_eq!("en", response.get_ref().locale);
+}
+
+#[tokio::test]
+async fn reply_reads_locale_and_caller_from_metadata() {
+    let app = spawn_app(translated_catalog());
+    let mut client = ReplyClient::new(channel(&app).await);
+
+    let mut request = reply_request("hello");
+    request.metadata_mut().insert(
+        "accept-language",
+        "fr;q=0, pt-BR;q=0.8, de;q=0.5".parse().unwrap(),
+    );
+    request
+        .metadata_mut()
+        .insert("x-caller", "alice".parse().unwrap());
+
+    let response = client.reply(request).await.unwrap();
+
+    assert_eq!("Olá alice", response.get_ref().message);
+    assert_eq!("pt-BR", response.get_ref().locale);
+}
+
+#[tokio::test]
+async fn unknown_message_is_not_found() {
+    let app = spawn_app(ReplyCatalog::default());
+    let mut client = ReplyClient::new(channel(&app).await);
+
+    let status = client.reply(reply_request("unknown")).await.unwrap_err();
+
+    assert_eq!(Code::NotFound, status.code());
+    assert_eq!("Unknown message \"unknown\"", status.message());
+}
+
+#[tokio::test]
+async fn replies_are_recorded_with_their_event() {
+    let app = spawn_app(ReplyCatalog::default());
+    let mut client = ReplyClient::new(channel(&app).await);
+
+    client.reply(reply_request("ping")).await.unwrap();
+    client.reply(reply_request("unknown")).await.unwrap_err();
+
+    let now = SystemTime::now();
+    let events = app
+        .context
+        .outbox
+        .lease(now, now + Duration::from_secs(60), 10)
+        .await
+        .unwrap();
+    let mut recorded: Vec<(&str, &str, &str)> = events
+        .iter()
+        .map(|event| match &event.event {
+            DomainEvent::MessageReplied(record) | DomainEvent::UnknownMessageReceived(record) => (
+                event.event.name(),
+                record.message.as_str(),
+                record.reply.as_str(),
+            ),
+        })
+        .collect();
+    recorded.sort();
+    assert_eq!(
+        vec![
+            ("message.replied", "ping", "pong"),
+            ("message.unknown", "unknown", "Unknown message \"unknown\""),
+        ],
+        recorded
+    );
+}
+
+#[tokio::test]
+async fn invalid_message_is_rejected() {
+    let app = spawn_app(ReplyCatalog::default());
+    let mut client = ReplyClient::new(channel(&app).await);
+
+    for message in ["".to_string(), "a".repeat(257)] {
+        let status = client.reply(reply_request(&message)).await.unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code());
+    }
+}
+
+#[tokio::test]
+async fn calls_are_counted_by_status() {
+    let app = spawn_app(ReplyCatalog::default());
+    let mut client = ReplyClient::new(channel(&app).await);
+
+    client.reply(reply_request("ping")).await.unwrap();
+    client.reply(reply_request("unknown")).await.unwrap_err();
+
+    let mut metrics = String::new();
+    encode(&mut metrics, &app.registry).unwrap();
+
+    assert!(metrics.contains(
+        "grpc_request_count_total{service=\"reply.v1.Reply\",method=\"Reply\",status=\"0\"} 1"
+    ));
+    assert!(metrics.contains(
+        "grpc_request_count_total{service=\"reply.v1.Reply\",method=\"Reply\",status=\"5\"} 1"
+    ));
+    assert!(metrics.contains("grpc_request_duration_ms_count{"));
+}
+
+#[tokio::test]
+async fn unknown_methods_are_counted_as_unmatched() {
+    let app = spawn_app(ReplyCatalog::default());
+    let mut client = tonic::client::Grpc::new(channel(&app).await);
+
+    client.ready().await.unwrap();
+    let status = client
+        .unary::<_, ReplyResponse, _>(
+            reply_request("ping"),
+            PathAndQuery::from_static("/reply.v1.Reply/Missing"),
+            ProstCodec::default(),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(Code::Unimplemented, status.code());
+
+    let mut metrics = String::new();
+    encode(&mut metrics, &app.registry).unwrap();
+
+    assert!(metrics.contains(
+        "grpc_request_count_total{service=\"unmatched\",method=\"unmatched\",status=\"12\"} 1"
+    ));
+    assert!(!metrics.contains("Missing"));
+}
+
+#[tokio::test]
+async fn health_reports_reply_service_serving() {
+    let app = spawn_app(ReplyCatalog::default());
+    let mut client = HealthClient::new(channel(&app).await);
+
+    for service in ["", "reply.v1.Reply"] {
+        let response = client
+            .check(HealthCheckRequest {
+                service: service.to_string(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(ServingStatus::Serving, response.get_ref().status());
+    }
+}
+
+#[tokio::test]
+async fn shutdown_stops_the_server() {
+    let app = spawn_app(ReplyCatalog::default());
+    let mut client = ReplyClient::new(channel(&app).await);
+    client.reply(reply_request("ping")).await.unwrap();
+
+    app.shutdown.trigger();
+
+    tokio::time::timeout(std::time::Duration::from_secs(5), app.server)
+        .await
+        .expect("server did not stop")
+        .unwrap()
+        .unwrap();
+    assert!(
+        ReplyClient::connect(format!("http://127.0.0.1:{}", app.port))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn metrics_server_exposes_registry() {
+    let app = spawn_app(ReplyCatalog::default());
+    let mut client = ReplyClient::new(channel(&app).await);
+    client.reply(reply_request("ping")).await.unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let metrics_port = listener.local_addr().unwrap().port();
+    let shutdown = Shutdown::new();
+    tokio::spawn(metrics_server::serve(
+        listener,
+        app.registry,
+        shutdown.clone(),
+    ));
+
+    let response = reqwest::get(format!("http://localhost:{metrics_port}/metrics"))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(200, response.status().as_u16());
+    assert!(response.text().await.unwrap().contains(
+        "grpc_request_count_total{service=\"reply.v1.Reply\",method=\"Reply\",status=\"0\"} 1"
+    ));
+
+    let response = reqwest::get(format!("http://localhost:{metrics_port}/other"))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(404, response.status().as_u16());
+
+    shutdown.trigger();
+}
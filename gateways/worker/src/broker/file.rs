@@ -0,0 +1,267 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use application::ports::PortError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use super::{Broker, Delivery, Message, Receipt};
+
+/// How often the subscribed topic is checked for new lines.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A line of a topic file. Payloads are stored as UTF-8 text.
+#[derive(Serialize, Deserialize)]
+struct Line {
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    payload: String,
+}
+
+/// Position of the consumer in the subscribed topic file.
+struct Cursor {
+    line: u64,
+    position: u64,
+}
+
+/// Lines handled so far: `committed` lines in a row, plus those acknowledged out of order.
+struct Acks {
+    path: PathBuf,
+    committed: u64,
+    pending: BTreeSet<u64>,
+}
+
+impl Acks {
+    fn ack(&mut self, line: u64) -> Result<(), PortError> {
+        self.pending.insert(line);
+        let committed = self.committed;
+        while self.pending.remove(&self.committed) {
+            self.committed += 1;
+        }
+        if self.committed == committed {
+            return Ok(());
+        }
+
+        std::fs::write(&self.path, self.committed.to_string())
+            .map_err(|err| PortError::new(format!("failed to write {}", self.path.display()), err))
+    }
+}
+
+/// Broker keeping each topic in a `<topic>.jsonl` file of a directory, one
+/// `{"headers": {...}, "payload": "..."}` object per line. The subscribed topic is tailed, and
+/// the number of lines handled in a row is kept in `<topic>.offset`, so a restarted worker
+/// resumes after them; lines handed back are delivered again before the next ones. Meant for
+/// local runs, with a single worker per directory.
+pub struct FileBroker {
+    dir: PathBuf,
+    topic: String,
+    cursor: tokio::sync::Mutex<Cursor>,
+    acks: Arc<Mutex<Acks>>,
+    /// Lines handed back, with their message, to be delivered again.
+    redeliveries: Arc<Mutex<VecDeque<(u64, Message)>>>,
+    appending: tokio::sync::Mutex<()>,
+}
+
+impl FileBroker {
+    /// Subscribes to `topic` in `dir`, which is created if needed.
+    pub fn open(dir: impl Into<PathBuf>, topic: impl Into<String>) -> Result<Self, PortError> {
+        let dir = dir.into();
+        let topic = topic.into();
+        std::fs::create_dir_all(&dir)
+            .map_err(|err| PortError::new(format!("failed to create {}", dir.display()), err))?;
+
+        let offset = dir.join(format!("{topic}.offset"));
+        let committed = match std::fs::read_to_string(&offset) {
+            Ok(committed) => committed.trim().parse().map_err(|err| {
+                PortError::new(format!("invalid offset in {}", offset.display()), err)
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(err) => {
+                return Err(PortError::new(
+                    format!("failed to read {}", offset.display()),
+                    err,
+                ))
+            }
+        };
+        let position = skip_lines(&topic_path(&dir, &topic), committed)?;
+
+        Ok(FileBroker {
+            cursor: tokio::sync::Mutex::new(Cursor {
+                line: committed,
+                position,
+            }),
+            acks: Arc::new(Mutex::new(Acks {
+                path: offset,
+                committed,
+                pending: BTreeSet::new(),
+            })),
+            redeliveries: Arc::default(),
+            appending: tokio::sync::Mutex::new(()),
+            dir,
+            topic,
+        })
+    }
+
+    /// Reads the line at the cursor, if it was written entirely.
+    async fn next_line(&self, cursor: &mut Cursor) -> Result<Option<Line>, PortError> {
+        let path = topic_path(&self.dir, &self.topic);
+        let position = cursor.position;
+
+        let read = tokio::task::spawn_blocking(move || -> std::io::Result<Option<String>> {
+            let mut file = match std::fs::File::open(&path) {
+                Ok(file) => file,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(err) => return Err(err),
+            };
+            file.seek(SeekFrom::Start(position))?;
+            let mut line = String::new();
+            BufReader::new(file).read_line(&mut line)?;
+            Ok(line.ends_with('\n').then_some(line))
+        })
+        .await
+        .map_err(|err| PortError::new("failed to read the topic file", err))?
+        .map_err(|err| PortError::new("failed to read the topic file", err))?;
+
+        let Some(text) = read else {
+            return Ok(None);
+        };
+        cursor.position += text.len() as u64;
+        cursor.line += 1;
+
+        serde_json::from_str(&text).map(Some).map_err(|err| {
+            PortError::new(
+                format!("invalid line {} of {}", cursor.line, self.topic),
+                err,
+            )
+        })
+    }
+
+    /// Delivery of `message`, read from `line` of the subscribed topic.
+    fn delivery(&self, line: u64, message: Message) -> Delivery {
+        Delivery {
+            topic: self.topic.clone(),
+            message: message.clone(),
+            receipt: Box::new(FileReceipt {
+                acks: self.acks.clone(),
+                redeliveries: self.redeliveries.clone(),
+                line,
+                message,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl Broker for FileBroker {
+    fn system(&self) -> &'static str {
+        "file"
+    }
+
+    async fn receive(&self) -> Result<Option<Delivery>, PortError> {
+        let mut cursor = self.cursor.lock().await;
+        loop {
+            let redelivery = self
+                .redeliveries
+                .lock()
+                .expect("redeliveries lock poisoned")
+                .pop_front();
+            if let Some((line, message)) = redelivery {
+                return Ok(Some(self.delivery(line, message)));
+            }
+
+            let line = cursor.line;
+            match self.next_line(&mut cursor).await {
+                Ok(Some(next)) => {
+                    let message = Message {
+                        headers: next.headers,
+                        payload: next.payload.into_bytes(),
+                    };
+                    return Ok(Some(self.delivery(line, message)));
+                }
+                Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+                Err(err) => {
+                    // a malformed line is skipped, as it could never be handled
+                    self.acks.lock().expect("acks lock poisoned").ack(line)?;
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    async fn publish(&self, topic: &str, message: Message) -> Result<(), PortError> {
+        let mut line = serde_json::to_string(&Line {
+            headers: message.headers,
+            payload: String::from_utf8_lossy(&message.payload).into_owned(),
+        })
+        .map_err(|err| PortError::new("failed to encode the message", err))?;
+        line.push('\n');
+
+        let path = topic_path(&self.dir, topic);
+        let _appending = self.appending.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .map_err(|err| PortError::new(format!("failed to open {}", path.display()), err))?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|err| PortError::new(format!("failed to write {}", path.display()), err))
+    }
+}
+
+struct FileReceipt {
+    acks: Arc<Mutex<Acks>>,
+    redeliveries: Arc<Mutex<VecDeque<(u64, Message)>>>,
+    line: u64,
+    message: Message,
+}
+
+impl Receipt for FileReceipt {
+    fn ack(self: Box<Self>) -> Result<(), PortError> {
+        self.acks.lock().expect("acks lock poisoned").ack(self.line)
+    }
+
+    fn nack(self: Box<Self>) -> Result<(), PortError> {
+        self.redeliveries
+            .lock()
+            .expect("redeliveries lock poisoned")
+            .push_back((self.line, self.message));
+        Ok(())
+    }
+}
+
+fn topic_path(dir: &Path, topic: &str) -> PathBuf {
+    dir.join(format!("{topic}.jsonl"))
+}
+
+/// Byte position after the first `lines` lines of `path`.
+fn skip_lines(path: &Path, lines: u64) -> Result<u64, PortError> {
+    if lines == 0 {
+        return Ok(0);
+    }
+
+    let file = std::fs::File::open(path)
+        .map_err(|err| PortError::new(format!("failed to open {}", path.display()), err))?;
+    let mut reader = BufReader::new(file);
+    let mut position = 0;
+    let mut line = String::new();
+    for _ in 0..lines {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|err| PortError::new(format!("failed to read {}", path.display()), err))?;
+        if read == 0 {
+            break;
+        }
+        position += read as u64;
+    }
+
+    Ok(position)
+}
@@ -0,0 +1,183 @@
+use std::{sync::Arc, time::Duration};
+
+use application::ports::PortError;
+use async_trait::async_trait;
+use rdkafka::{
+    consumer::{Consumer, ConsumerContext, Rebalance, StreamConsumer},
+    message::{Header, Headers, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig, ClientContext, Message as _, Offset,
+};
+
+use super::{offsets::Offsets, Broker, Delivery, Message, Receipt};
+
+pub struct Settings {
+    /// Comma-separated `host:port` list of bootstrap brokers.
+    pub brokers: String,
+    pub group_id: String,
+    /// Time a publish may wait for room in the producer queue.
+    pub send_timeout: Duration,
+}
+
+/// Kafka consumer group member subscribed to one topic. The offset of a partition is stored
+/// once every message received before it is acknowledged, and committed in the background, so
+/// unacknowledged messages are delivered again after a restart or a rebalance. A message
+/// handed back is delivered again right away, by seeking its partition back to it, along with
+/// every later message of the partition, even those already handled.
+pub struct KafkaBroker {
+    consumer: Arc<StreamConsumer<OffsetsContext>>,
+    offsets: Arc<Offsets>,
+    producer: FutureProducer,
+    send_timeout: Duration,
+}
+
+impl KafkaBroker {
+    pub fn connect(settings: &Settings, topic: &str) -> Result<Self, PortError> {
+        let offsets = Arc::new(Offsets::default());
+        let consumer: StreamConsumer<OffsetsContext> = ClientConfig::new()
+            .set("bootstrap.servers", &settings.brokers)
+            .set("group.id", &settings.group_id)
+            .set("enable.auto.commit", "true")
+            .set("enable.auto.offset.store", "false")
+            .set("auto.offset.reset", "earliest")
+            .create_with_context(OffsetsContext {
+                offsets: offsets.clone(),
+            })
+            .map_err(|err| PortError::new("failed to create the Kafka consumer", err))?;
+        consumer
+            .subscribe(&[topic])
+            .map_err(|err| PortError::new(format!("failed to subscribe to {topic}"), err))?;
+
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", &settings.brokers)
+            .create()
+            .map_err(|err| PortError::new("failed to create the Kafka producer", err))?;
+
+        Ok(KafkaBroker {
+            consumer: Arc::new(consumer),
+            offsets,
+            producer,
+            send_timeout: settings.send_timeout,
+        })
+    }
+}
+
+#[async_trait]
+impl Broker for KafkaBroker {
+    fn system(&self) -> &'static str {
+        "kafka"
+    }
+
+    async fn receive(&self) -> Result<Option<Delivery>, PortError> {
+        let received = self
+            .consumer
+            .recv()
+            .await
+            .map_err(|err| PortError::new("failed to receive from Kafka", err))?;
+
+        let headers = received
+            .headers()
+            .map(|headers| {
+                headers
+                    .iter()
+                    .filter_map(|header| {
+                        let value = std::str::from_utf8(header.value?).ok()?;
+                        Some((header.key.to_string(), value.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        self.offsets
+            .received(received.topic(), received.partition(), received.offset());
+
+        Ok(Some(Delivery {
+            topic: received.topic().to_string(),
+            message: Message {
+                headers,
+                payload: received.payload().unwrap_or_default().to_vec(),
+            },
+            receipt: Box::new(KafkaReceipt {
+                consumer: self.consumer.clone(),
+                offsets: self.offsets.clone(),
+                topic: received.topic().to_string(),
+                partition: received.partition(),
+                offset: received.offset(),
+            }),
+        }))
+    }
+
+    async fn publish(&self, topic: &str, message: Message) -> Result<(), PortError> {
+        let headers = message
+            .headers
+            .iter()
+            .fold(OwnedHeaders::new(), |headers, (key, value)| {
+                headers.insert(Header {
+                    key,
+                    value: Some(value),
+                })
+            });
+        let record = FutureRecord::<(), _>::to(topic)
+            .payload(&message.payload)
+            .headers(headers);
+
+        self.producer
+            .send(record, self.send_timeout)
+            .await
+            .map(|_| ())
+            .map_err(|(err, _)| PortError::new(format!("failed to publish to {topic}"), err))
+    }
+}
+
+struct KafkaReceipt {
+    consumer: Arc<StreamConsumer<OffsetsContext>>,
+    offsets: Arc<Offsets>,
+    topic: String,
+    partition: i32,
+    offset: i64,
+}
+
+impl Receipt for KafkaReceipt {
+    fn ack(self: Box<Self>) -> Result<(), PortError> {
+        self.offsets
+            .acked(&self.topic, self.partition, self.offset, |handled| {
+                self.consumer
+                    .store_offset(&self.topic, self.partition, handled)
+            })
+            .map_err(|err| PortError::new("failed to store the Kafka offset", err))
+    }
+
+    fn nack(self: Box<Self>) -> Result<(), PortError> {
+        // the offset stays unacknowledged, so it is not committed past while it is redelivered.
+        // The whole partition is sought back: the later messages already received, running or
+        // acknowledged, are delivered and handled again.
+        self.consumer
+            .seek(
+                &self.topic,
+                self.partition,
+                Offset::Offset(self.offset),
+                Duration::ZERO,
+            )
+            .map_err(|err| PortError::new("failed to seek back to the Kafka message", err))
+    }
+}
+
+/// Consumer context forgetting the offsets of the partitions revoked by a rebalance.
+struct OffsetsContext {
+    offsets: Arc<Offsets>,
+}
+
+impl ClientContext for OffsetsContext {}
+
+impl ConsumerContext for OffsetsContext {
+    fn pre_rebalance(&self, rebalance: &Rebalance<'_>) {
+        if let Rebalance::Revoke(revoked) = rebalance {
+            let revoked = revoked.elements();
+            self.offsets.revoke(
+                revoked
+                    .iter()
+                    .map(|element| (element.topic(), element.partition())),
+            );
+        }
+    }
+}
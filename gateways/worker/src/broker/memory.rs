@@ -0,0 +1,180 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use application::ports::PortError;
+use async_trait::async_trait;
+use tokio::sync::Notify;
+
+use super::{Broker, Delivery, Message, Receipt};
+
+#[derive(Default)]
+struct Topics {
+    messages: HashMap<String, VecDeque<Message>>,
+    failures: HashMap<String, usize>,
+    /// Received messages neither acknowledged nor handed back yet.
+    unsettled: usize,
+    acked: usize,
+    nacked: usize,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct State {
+    topics: Mutex<Topics>,
+    sent: Notify,
+}
+
+impl State {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Topics> {
+        self.topics.lock().expect("memory broker lock poisoned")
+    }
+}
+
+/// Broker keeping its topics in memory, subscribed to one of them. Tests feed it with
+/// [`MemoryBroker::send`], read what the worker published with [`MemoryBroker::messages`] and
+/// make publishing fail with [`MemoryBroker::fail_publishes`]. Messages handed back are
+/// delivered again before the others.
+pub struct MemoryBroker {
+    topic: String,
+    state: Arc<State>,
+}
+
+impl MemoryBroker {
+    pub fn new(topic: impl Into<String>) -> Self {
+        MemoryBroker {
+            topic: topic.into(),
+            state: Arc::default(),
+        }
+    }
+
+    /// Queues `message` on `topic`.
+    pub fn send(&self, topic: &str, message: Message) {
+        self.lock()
+            .messages
+            .entry(topic.to_string())
+            .or_default()
+            .push_back(message);
+        self.state.sent.notify_one();
+    }
+
+    /// Messages queued on `topic` and not received yet.
+    pub fn messages(&self, topic: &str) -> Vec<Message> {
+        self.lock()
+            .messages
+            .get(topic)
+            .map(|messages| messages.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Makes the next `count` publishes to `topic` fail.
+    pub fn fail_publishes(&self, topic: &str, count: usize) {
+        self.lock().failures.insert(topic.to_string(), count);
+    }
+
+    /// Ends the subscription once the messages already queued are received and settled, so
+    /// that those handed back are received again first.
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.state.sent.notify_one();
+    }
+
+    /// Number of received messages acknowledged so far.
+    pub fn acked(&self) -> usize {
+        self.lock().acked
+    }
+
+    /// Number of received messages handed back so far.
+    pub fn nacked(&self) -> usize {
+        self.lock().nacked
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Topics> {
+        self.state.lock()
+    }
+}
+
+#[async_trait]
+impl Broker for MemoryBroker {
+    fn system(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn receive(&self) -> Result<Option<Delivery>, PortError> {
+        loop {
+            {
+                let mut topics = self.lock();
+                let next = topics
+                    .messages
+                    .get_mut(&self.topic)
+                    .and_then(VecDeque::pop_front);
+                match next {
+                    Some(message) => {
+                        topics.unsettled += 1;
+                        return Ok(Some(Delivery {
+                            topic: self.topic.clone(),
+                            message: message.clone(),
+                            receipt: Box::new(MemoryReceipt {
+                                state: self.state.clone(),
+                                topic: self.topic.clone(),
+                                message,
+                            }),
+                        }));
+                    }
+                    None if topics.closed && topics.unsettled == 0 => return Ok(None),
+                    None => {}
+                }
+            }
+            self.state.sent.notified().await;
+        }
+    }
+
+    async fn publish(&self, topic: &str, message: Message) -> Result<(), PortError> {
+        {
+            let mut topics = self.lock();
+            if let Some(failures @ 1..) = topics.failures.get_mut(topic) {
+                *failures -= 1;
+                return Err(PortError::new(
+                    format!("failed to publish to {topic}"),
+                    "broker unavailable",
+                ));
+            }
+        }
+        self.send(topic, message);
+        Ok(())
+    }
+}
+
+struct MemoryReceipt {
+    state: Arc<State>,
+    topic: String,
+    message: Message,
+}
+
+impl Receipt for MemoryReceipt {
+    fn ack(self: Box<Self>) -> Result<(), PortError> {
+        {
+            let mut topics = self.state.lock();
+            topics.unsettled -= 1;
+            topics.acked += 1;
+        }
+        self.state.sent.notify_one();
+        Ok(())
+    }
+
+    fn nack(self: Box<Self>) -> Result<(), PortError> {
+        {
+            let mut topics = self.state.lock();
+            topics.unsettled -= 1;
+            topics.nacked += 1;
+            topics
+                .messages
+                .entry(self.topic)
+                .or_default()
+                .push_front(self.message);
+        }
+        self.state.sent.notify_one();
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+//! Message brokers the worker consumes from and publishes to. The in-memory and file brokers
+//! stand in for a real one in tests and local runs; Kafka and NATS are behind the `kafka` and
+//! `nats` features.
+
+use std::collections::BTreeMap;
+
+use application::ports::PortError;
+use async_trait::async_trait;
+
+pub mod file;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod memory;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod offsets;
+
+/// A message as carried by a broker: an opaque payload and string headers, such as the
+/// `traceparent` of the producer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Message {
+    pub headers: BTreeMap<String, String>,
+    pub payload: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(payload: impl Into<Vec<u8>>) -> Self {
+        Message {
+            headers: BTreeMap::new(),
+            payload: payload.into(),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+}
+
+/// Settles a received message with the broker.
+pub trait Receipt: Send {
+    /// Acknowledges the message, so the broker does not deliver it again.
+    fn ack(self: Box<Self>) -> Result<(), PortError>;
+
+    /// Hands the message back, for the broker to deliver it again without waiting for a
+    /// restart.
+    fn nack(self: Box<Self>) -> Result<(), PortError>;
+}
+
+/// A message received from the subscribed topic, acknowledged once handled.
+pub struct Delivery {
+    pub topic: String,
+    pub message: Message,
+    pub receipt: Box<dyn Receipt>,
+}
+
+/// Receipt of brokers without acknowledgements, such as core NATS, which never deliver a
+/// message again.
+pub struct NoReceipt;
+
+impl Receipt for NoReceipt {
+    fn ack(self: Box<Self>) -> Result<(), PortError> {
+        Ok(())
+    }
+
+    fn nack(self: Box<Self>) -> Result<(), PortError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+pub trait Broker: Send + Sync {
+    /// Name of the broker, reported as the `messaging.system` of the spans.
+    fn system(&self) -> &'static str;
+
+    /// Waits for the next message of the subscribed topic. `None` once the subscription ended.
+    async fn receive(&self) -> Result<Option<Delivery>, PortError>;
+
+    /// Publishes `message` to `topic`, resolving once the broker has accepted it.
+    async fn publish(&self, topic: &str, message: Message) -> Result<(), PortError>;
+}
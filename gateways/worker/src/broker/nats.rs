@@ -0,0 +1,87 @@
+use application::ports::PortError;
+use async_nats::{Client, HeaderMap, Subscriber};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use tokio::sync::Mutex;
+
+use super::{Broker, Delivery, Message, NoReceipt};
+
+pub struct Settings {
+    pub url: String,
+    /// Queue group shared by the workers, so each message goes to one of them.
+    pub queue_group: String,
+}
+
+/// Core NATS subscription to one subject, in a queue group. Core NATS does not redeliver, so
+/// a message is lost if the worker stops before handling it.
+pub struct NatsBroker {
+    client: Client,
+    subscriber: Mutex<Subscriber>,
+}
+
+impl NatsBroker {
+    pub async fn connect(settings: &Settings, subject: &str) -> Result<Self, PortError> {
+        let client = async_nats::connect(settings.url.as_str())
+            .await
+            .map_err(|err| PortError::new(format!("failed to connect to {}", settings.url), err))?;
+        let subscriber = client
+            .queue_subscribe(subject.to_string(), settings.queue_group.clone())
+            .await
+            .map_err(|err| PortError::new(format!("failed to subscribe to {subject}"), err))?;
+
+        Ok(NatsBroker {
+            client,
+            subscriber: Mutex::new(subscriber),
+        })
+    }
+}
+
+#[async_trait]
+impl Broker for NatsBroker {
+    fn system(&self) -> &'static str {
+        "nats"
+    }
+
+    async fn receive(&self) -> Result<Option<Delivery>, PortError> {
+        let Some(received) = self.subscriber.lock().await.next().await else {
+            return Ok(None);
+        };
+
+        let headers = received
+            .headers
+            .iter()
+            .flat_map(HeaderMap::iter)
+            .filter_map(|(name, values)| Some((name.to_string(), values.first()?.to_string())))
+            .collect();
+
+        Ok(Some(Delivery {
+            topic: received.subject.to_string(),
+            message: Message {
+                headers,
+                payload: received.payload.to_vec(),
+            },
+            receipt: Box::new(NoReceipt),
+        }))
+    }
+
+    async fn publish(&self, topic: &str, message: Message) -> Result<(), PortError> {
+        let headers =
+            message
+                .headers
+                .into_iter()
+                .fold(HeaderMap::new(), |mut headers, (name, value)| {
+                    headers.insert(name, value);
+                    headers
+                });
+
+        self.client
+            .publish_with_headers(topic.to_string(), headers, message.payload.into())
+            .await
+            .map_err(|err| PortError::new(format!("failed to publish to {topic}"), err))?;
+        // publishing only queues the message; flushing waits for it to be written out
+        self.client
+            .flush()
+            .await
+            .map_err(|err| PortError::new(format!("failed to publish to {topic}"), err))
+    }
+}
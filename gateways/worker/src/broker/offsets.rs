@@ -0,0 +1,78 @@
+//! Offsets of partitioned brokers such as Kafka, where storing the offset of a message commits
+//! every message of its partition received before it.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Mutex,
+};
+
+/// Messages received from a partition and not acknowledged yet.
+struct PartitionOffsets {
+    unacked: BTreeSet<i64>,
+    /// Highest offset received.
+    received: i64,
+    /// Highest offset stored, at or below which every received message was acknowledged.
+    stored: i64,
+}
+
+/// Acknowledgements of the assigned partitions. Messages are handled concurrently, so they may
+/// be acknowledged out of order; only the highest offset below which every received message
+/// was acknowledged is stored, never one past a message still running or handed back.
+#[derive(Default)]
+pub struct Offsets(Mutex<HashMap<(String, i32), PartitionOffsets>>);
+
+impl Offsets {
+    pub fn received(&self, topic: &str, partition: i32, offset: i64) {
+        let mut partitions = self.lock();
+        let offsets = partitions
+            .entry((topic.to_string(), partition))
+            .or_insert_with(|| PartitionOffsets {
+                unacked: BTreeSet::new(),
+                received: -1,
+                stored: -1,
+            });
+        offsets.unacked.insert(offset);
+        offsets.received = offsets.received.max(offset);
+    }
+
+    /// Marks `offset` acknowledged, passing the offset of its partition to `store` when it
+    /// moves forward. The lock is held while storing, so stored offsets never go back.
+    pub fn acked<E>(
+        &self,
+        topic: &str,
+        partition: i32,
+        offset: i64,
+        store: impl FnOnce(i64) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut partitions = self.lock();
+        // the partition was revoked since the message was received
+        let Some(offsets) = partitions.get_mut(&(topic.to_string(), partition)) else {
+            return Ok(());
+        };
+
+        offsets.unacked.remove(&offset);
+        let handled = offsets
+            .unacked
+            .first()
+            .map_or(offsets.received, |unacked| unacked - 1);
+        if handled <= offsets.stored {
+            return Ok(());
+        }
+
+        store(handled)?;
+        offsets.stored = handled;
+        Ok(())
+    }
+
+    /// Forgets revoked partitions, whose messages are now delivered to another member.
+    pub fn revoke<'a>(&self, revoked: impl IntoIterator<Item = (&'a str, i32)>) {
+        let mut partitions = self.lock();
+        for (topic, partition) in revoked {
+            partitions.remove(&(topic.to_string(), partition));
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(String, i32), PartitionOffsets>> {
+        self.0.lock().expect("offsets lock poisoned")
+    }
+}
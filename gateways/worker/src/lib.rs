@@ -0,0 +1,3 @@
+pub mod broker;
+pub mod metrics;
+pub mod worker;
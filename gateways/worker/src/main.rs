@@ -0,0 +1,166 @@
+use std::{net::TcpListener, sync::Arc, time::Duration};
+
+use application::{
+    catalog::ReplyCatalog,
+    outbox::{Backoff, RelaySettings},
+};
+use infrastructure::{
+    self,
+    catalog::CatalogStore,
+    memory, metrics_server,
+    outbox::OutboxRelay,
+    retention,
+    scheduler::{Schedule, Scheduler},
+    shutdown::{self, Shutdown},
+    telemetry,
+};
+use prometheus_client::registry::Registry;
+use tracing::log;
+use worker::{
+    broker::{file::FileBroker, Broker},
+    worker::{Settings, Worker},
+};
+
+mod settings;
+use settings::{get_config, BrokerBackend};
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let settings = get_config()?;
+
+    let _guard = telemetry::setup(telemetry::Settings {
+        log: telemetry::LoggingSettings {
+            format: telemetry::LoggingOptions::PrettyPrint,
+        },
+        telemetry: telemetry::TelemetrySettings {
+            host: settings.telemetry.host,
+            port: settings.telemetry.port,
+            sampler_param: settings.telemetry.sampler_param,
+        },
+        service_name: settings.app.service_name,
+    });
+
+    let mut registry = Registry::default();
+
+    let catalog = Arc::new(match &settings.catalog.path {
+        Some(path) => CatalogStore::from_file(path)?,
+        None => CatalogStore::new(ReplyCatalog::default()),
+    });
+    if settings.catalog.path.is_some() {
+        catalog
+            .clone()
+            .watch(Duration::from_secs(settings.catalog.reload_interval_sec));
+    }
+
+    let mut context = memory::context();
+    context.catalog = catalog;
+
+    // replies are recorded in memory, where their events are relayed from and the history is
+    // purged, as they are by the HTTP gateway
+    let mut scheduler = Scheduler::new(&mut registry);
+    scheduler.add(
+        OutboxRelay::new(
+            RelaySettings {
+                batch_size: settings.outbox.batch_size,
+                lease: Duration::from_secs(settings.outbox.lease_sec),
+                backoff: Backoff {
+                    initial: Duration::from_millis(settings.outbox.retry_initial_ms),
+                    max: Duration::from_secs(settings.outbox.retry_max_sec),
+                },
+            },
+            &mut registry,
+        )
+        .job(
+            context.clone(),
+            Duration::from_millis(settings.outbox.poll_interval_ms),
+        )
+        .timeout(Duration::from_secs(settings.outbox.relay_timeout_sec)),
+    );
+    scheduler.add(
+        retention::job(
+            context.clone(),
+            (settings.history.retention_sec > 0)
+                .then(|| Duration::from_secs(settings.history.retention_sec)),
+            Schedule::Every(Duration::from_secs(settings.history.purge_interval_sec)),
+        )
+        .timeout(Duration::from_secs(settings.history.purge_timeout_sec)),
+    );
+
+    let worker_settings = &settings.worker;
+    let topic = &worker_settings.input_topic;
+    let broker: Arc<dyn Broker> = match worker_settings.broker {
+        BrokerBackend::File => Arc::new(FileBroker::open(&worker_settings.file.dir, topic)?),
+        #[cfg(feature = "kafka")]
+        BrokerBackend::Kafka => Arc::new(worker::broker::kafka::KafkaBroker::connect(
+            &worker::broker::kafka::Settings {
+                brokers: worker_settings.kafka.brokers.clone(),
+                group_id: worker_settings.kafka.group_id.clone(),
+                send_timeout: Duration::from_millis(worker_settings.kafka.send_timeout_ms),
+            },
+            topic,
+        )?),
+        #[cfg(feature = "nats")]
+        BrokerBackend::Nats => Arc::new(
+            worker::broker::nats::NatsBroker::connect(
+                &worker::broker::nats::Settings {
+                    url: worker_settings.nats.url.clone(),
+                    queue_group: worker_settings.nats.queue_group.clone(),
+                },
+                topic,
+            )
+            .await?,
+        ),
+        #[allow(unreachable_patterns)]
+        backend => eyre::bail!("the worker was built without the {backend:?} broker feature"),
+    };
+    log::info!("Worker consuming {topic} from {} broker", broker.system());
+
+    let worker = Worker::new(
+        Settings {
+            output_topic: worker_settings.output_topic.clone(),
+            dead_letter_topic: worker_settings.dead_letter_topic.clone(),
+            concurrency: worker_settings.concurrency,
+            max_attempts: worker_settings.max_attempts,
+            backoff: Backoff {
+                initial: Duration::from_millis(worker_settings.retry_initial_ms),
+                max: Duration::from_millis(worker_settings.retry_max_ms),
+            },
+            drain_timeout: Duration::from_secs(worker_settings.drain_timeout_sec),
+            context,
+        },
+        broker,
+        &mut registry,
+    );
+
+    let metrics_listener =
+        TcpListener::bind(format!("{}:{}", settings.metric.host, settings.metric.port))?;
+    log::info!(
+        "Metrics Server listening on {}:{}",
+        settings.metric.host,
+        settings.metric.port
+    );
+
+    let shutdown = Shutdown::new();
+    scheduler.spawn(shutdown.clone());
+    let signalled = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        log::info!("Shutdown signal received");
+        signalled.trigger();
+    });
+
+    let stopped = shutdown.clone();
+    let (worked, exported) = tokio::join!(
+        async move {
+            let worked = worker.run(shutdown.clone()).await;
+            // the subscription may end on its own, which stops the metrics server as well
+            shutdown.trigger();
+            worked
+        },
+        metrics_server::serve(metrics_listener, registry, stopped)
+    );
+    worked?;
+    exported?;
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, gauge::Gauge, histogram::Histogram},
+    registry::Registry,
+};
+
+/// How a message was handled: `replied`, `unknown` (answered with the unknown message error),
+/// `dead_lettered` or `failed` when even the dead-letter queue could not take it.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct OutcomeLabel {
+    pub outcome: &'static str,
+}
+
+/// Counts and times handled messages under a `worker_` prefix.
+#[derive(Clone)]
+pub struct Metrics {
+    pub message_count: Family<OutcomeLabel, Counter>,
+    pub message_duration: Family<OutcomeLabel, Histogram>,
+    pub retry_count: Counter,
+    pub in_flight: Gauge,
+}
+
+impl Metrics {
+    pub fn new(registry: &mut Registry) -> Self {
+        let registry = registry.sub_registry_with_prefix("worker");
+
+        let message_count = Family::<OutcomeLabel, Counter>::default();
+        let message_duration = Family::<OutcomeLabel, Histogram>::new_with_constructor(|| {
+            let buckets = [
+                1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 75.0, 100.0, 250.0, 500.0, 750.0, 1000.0, 2500.0,
+                5000.0, 10000.0,
+            ];
+            Histogram::new(buckets.into_iter())
+        });
+        let retry_count = Counter::default();
+        let in_flight = Gauge::default();
+
+        registry.register(
+            "message_count",
+            "Number of messages handled",
+            message_count.clone(),
+        );
+        registry.register(
+            "message_duration_ms",
+            "Time to handle a message, retries included",
+            message_duration.clone(),
+        );
+        registry.register(
+            "retry_count",
+            "Number of publishes retried",
+            retry_count.clone(),
+        );
+        registry.register(
+            "in_flight_messages",
+            "Number of messages being handled",
+            in_flight.clone(),
+        );
+
+        Metrics {
+            message_count,
+            message_duration,
+            retry_count,
+            in_flight,
+        }
+    }
+}
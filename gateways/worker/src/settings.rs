@@ -0,0 +1,179 @@
+use eyre::Context;
+use serde_aux::field_attributes::deserialize_number_from_string;
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Telemetry {
+    pub host: String,
+    pub port: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub sampler_param: f64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Application {
+    pub service_name: String,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Metric {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Catalog {
+    /// `.toml`, `.json` or `.yaml` file with the reply catalog; the built-in catalog is used
+    /// when unset.
+    pub path: Option<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub reload_interval_sec: u64,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum BrokerBackend {
+    File,
+    Kafka,
+    Nats,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct File {
+    /// Directory holding one `<topic>.jsonl` file per topic.
+    pub dir: String,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[cfg_attr(not(feature = "kafka"), allow(dead_code))]
+pub struct Kafka {
+    pub brokers: String,
+    pub group_id: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub send_timeout_ms: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[cfg_attr(not(feature = "nats"), allow(dead_code))]
+pub struct Nats {
+    pub url: String,
+    pub queue_group: String,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Worker {
+    pub broker: BrokerBackend,
+    pub input_topic: String,
+    pub output_topic: String,
+    pub dead_letter_topic: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub concurrency: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_initial_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_max_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub drain_timeout_sec: u64,
+    pub file: File,
+    #[cfg_attr(not(feature = "kafka"), allow(dead_code))]
+    pub kafka: Kafka,
+    #[cfg_attr(not(feature = "nats"), allow(dead_code))]
+    pub nats: Nats,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct History {
+    /// Exchanges older than this are deleted; `0` keeps them forever.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retention_sec: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub purge_interval_sec: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub purge_timeout_sec: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Outbox {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub poll_interval_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub relay_timeout_sec: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub batch_size: usize,
+    /// How long a relay reserves the events it is publishing.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lease_sec: u64,
+    /// Delay before the first retry of an event, doubled on every further failure.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_initial_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_max_sec: u64,
+}
+
+/// Settings of the worker binary. Keys shared with the other gateways use the same names and
+/// environment variables.
+#[derive(serde::Deserialize, Clone)]
+pub struct Settings {
+    pub app: Application,
+    pub worker: Worker,
+    pub metric: Metric,
+    pub telemetry: Telemetry,
+    pub catalog: Catalog,
+    pub history: History,
+    pub outbox: Outbox,
+}
+
+pub fn get_config() -> eyre::Result<Settings> {
+    let settings = config::Config::builder()
+        .add_source(
+            config::Environment::with_prefix("APP")
+                .prefix_separator("_")
+                .separator("_"),
+        )
+        // App default settings
+        .set_default("app.service_name", "{{project-name}}")?
+        // Worker default settings
+        .set_default("worker.broker", "file")?
+        .set_default("worker.input_topic", "messages")?
+        .set_default("worker.output_topic", "replies")?
+        .set_default("worker.dead_letter_topic", "messages.dlq")?
+        .set_default("worker.concurrency", 16)?
+        .set_default("worker.max_attempts", 5)?
+        .set_default("worker.retry_initial_ms", 100)?
+        .set_default("worker.retry_max_ms", 10_000)?
+        .set_default("worker.drain_timeout_sec", 30)?
+        .set_default("worker.file.dir", "broker")?
+        .set_default("worker.kafka.brokers", "127.0.0.1:9092")?
+        .set_default("worker.kafka.group_id", "{{project-name}}")?
+        .set_default("worker.kafka.send_timeout_ms", 5000)?
+        .set_default("worker.nats.url", "nats://127.0.0.1:4222")?
+        .set_default("worker.nats.queue_group", "{{project-name}}")?
+        // Metric default settings
+        .set_default("metric.host", "127.0.0.1")?
+        .set_default("metric.port", 7003)?
+        // Catalog default settings
+        .set_default("catalog.reload_interval_sec", 5)?
+        // History default settings
+        .set_default("history.retention_sec", 30 * 24 * 60 * 60)?
+        .set_default("history.purge_interval_sec", 60 * 60)?
+        .set_default("history.purge_timeout_sec", 5 * 60)?
+        // Outbox default settings
+        .set_default("outbox.poll_interval_ms", 1000)?
+        .set_default("outbox.relay_timeout_sec", 60)?
+        .set_default("outbox.batch_size", 100)?
+        .set_default("outbox.lease_sec", 30)?
+        .set_default("outbox.retry_initial_ms", 1000)?
+        .set_default("outbox.retry_max_sec", 5 * 60)?
+        // Telemetry default settings
+        .set_default("telemetry.host", "127.0.0.1")?
+        .set_default("telemetry.port", 4317)?
+        .set_default("telemetry.sampler_param", 1.0)?
+        .build()
+        .wrap_err("error loading configuration from env variables")?;
+
+    settings
+        .try_deserialize::<Settings>()
+        .wrap_err("error deserializing settings")
+}
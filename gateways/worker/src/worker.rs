@@ -0,0 +1,346 @@
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use application::{
+    context::AppContext,
+    locale::Locale,
+    messages::{self, ReplyError},
+    outbox::Backoff,
+    ports::PortError,
+    template::RequestMetadata,
+};
+use infrastructure::{shutdown::Shutdown, telemetry};
+use opentelemetry::propagation::{Extractor, Injector};
+use prometheus_client::registry::Registry;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Semaphore, task::JoinSet};
+use tracing::{field::Empty, log, Instrument, Span};
+
+use crate::broker::{Broker, Delivery, Message};
+use crate::metrics::{Metrics, OutcomeLabel};
+
+/// Header set on dead-lettered messages, explaining why they could not be handled.
+pub const DEAD_LETTER_REASON: &str = "x-dead-letter-reason";
+
+const MAX_MESSAGE_LENGTH: usize = 256;
+
+/// Pause before receiving again after the broker failed to deliver.
+const RECEIVE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+const REPLIED: &str = "replied";
+const UNKNOWN: &str = "unknown";
+const DEAD_LETTERED: &str = "dead_lettered";
+const FAILED: &str = "failed";
+
+/// Payload of a consumed message.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReplyRequest {
+    /// Copied to the result, to correlate it with the request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller: Option<String>,
+    /// Preferred reply locales, most preferred first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locales: Vec<String>,
+}
+
+/// Payload published for every handled request: the reply, or the error when the message was
+/// unknown.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ReplyResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub locale: String,
+}
+
+pub struct Settings {
+    /// Topic the results are published to.
+    pub output_topic: String,
+    /// Topic taking the messages that could not be handled.
+    pub dead_letter_topic: String,
+    /// Messages handled at the same time.
+    pub concurrency: usize,
+    /// Attempts to publish a result before the message is dead-lettered.
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+    /// Time in-flight messages get to finish once shutdown starts.
+    pub drain_timeout: Duration,
+    /// Ports the use cases run against.
+    pub context: AppContext,
+}
+
+/// Consumes reply requests from a [`Broker`], answers them from the catalog, records them in
+/// the history along with their event and publishes the results. Messages that cannot be
+/// parsed, or whose result cannot be published after `max_attempts`, go to the dead-letter
+/// topic; a message is acknowledged once its result or dead letter is published, and handed
+/// back to the broker otherwise.
+pub struct Worker {
+    handler: Arc<Handler>,
+    concurrency: usize,
+    drain_timeout: Duration,
+}
+
+struct Handler {
+    broker: Arc<dyn Broker>,
+    context: AppContext,
+    output_topic: String,
+    dead_letter_topic: String,
+    max_attempts: u32,
+    backoff: Backoff,
+    metrics: Metrics,
+}
+
+impl Worker {
+    pub fn new(settings: Settings, broker: Arc<dyn Broker>, registry: &mut Registry) -> Self {
+        Worker {
+            handler: Arc::new(Handler {
+                broker,
+                context: settings.context,
+                output_topic: settings.output_topic,
+                dead_letter_topic: settings.dead_letter_topic,
+                max_attempts: settings.max_attempts.max(1),
+                backoff: settings.backoff,
+                metrics: Metrics::new(registry),
+            }),
+            concurrency: settings.concurrency.max(1),
+            drain_timeout: settings.drain_timeout,
+        }
+    }
+
+    /// Handles messages until the subscription ends or `shutdown` is triggered. Receiving then
+    /// stops and in-flight messages get `drain_timeout` to finish; those still running are
+    /// dropped unacknowledged, for the broker to deliver them again.
+    pub async fn run(self, shutdown: Shutdown) -> eyre::Result<()> {
+        let permits = Arc::new(Semaphore::new(self.concurrency));
+        let mut tasks = JoinSet::new();
+
+        loop {
+            while tasks.try_join_next().is_some() {}
+
+            let permit = tokio::select! {
+                permit = permits.clone().acquire_owned() => permit?,
+                _ = shutdown.wait() => break,
+            };
+            let received = tokio::select! {
+                received = self.handler.broker.receive() => received,
+                _ = shutdown.wait() => break,
+            };
+
+            match received {
+                Ok(Some(delivery)) => {
+                    let handler = self.handler.clone();
+                    tasks.spawn(async move {
+                        handler.handle(delivery).await;
+                        drop(permit);
+                    });
+                }
+                Ok(None) => {
+                    log::info!("Subscription ended");
+                    break;
+                }
+                Err(err) => {
+                    tracing::error!("failed to receive a message: {err}");
+                    tokio::select! {
+                        _ = tokio::time::sleep(RECEIVE_RETRY_DELAY) => {}
+                        _ = shutdown.wait() => break,
+                    }
+                }
+            }
+        }
+
+        log::info!("Draining {} in-flight messages", tasks.len());
+        let drained = tokio::time::timeout(self.drain_timeout, async {
+            while tasks.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            log::warn!(
+                "{} messages still running after {:?} were dropped",
+                tasks.len(),
+                self.drain_timeout
+            );
+            tasks.abort_all();
+        }
+
+        Ok(())
+    }
+}
+
+impl Handler {
+    async fn handle(&self, delivery: Delivery) {
+        let Delivery {
+            topic,
+            message,
+            receipt,
+        } = delivery;
+
+        let span = tracing::info_span!(
+            "gateways.worker.message",
+            otel.kind = "consumer",
+            otel.name = format!("process {topic}"),
+            otel.status_code = Empty,
+            messaging.system = self.broker.system(),
+            messaging.operation = "process",
+            messaging.destination.name = topic,
+            outcome = Empty,
+        );
+        telemetry::set_remote_parent(&span, &HeaderExtractor(&message.headers));
+
+        self.metrics.in_flight.inc();
+        let started = Instant::now();
+        let outcome = self.process(message).instrument(span.clone()).await;
+        let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+        self.metrics.in_flight.dec();
+
+        let label = OutcomeLabel { outcome };
+        self.metrics.message_count.get_or_create(&label).inc();
+        self.metrics
+            .message_duration
+            .get_or_create(&label)
+            .observe(elapsed);
+        span.record("outcome", outcome);
+        span.record(
+            "otel.status_code",
+            if outcome == FAILED { "ERROR" } else { "OK" },
+        );
+
+        // handed back, the message is delivered again
+        let settled = if outcome == FAILED {
+            receipt.nack()
+        } else {
+            receipt.ack()
+        };
+        if let Err(err) = settled {
+            tracing::warn!(parent: &span, "failed to settle a message: {err}");
+        }
+    }
+
+    async fn process(&self, message: Message) -> &'static str {
+        let request = match parse(&message.payload) {
+            Ok(request) => request,
+            Err(reason) => return self.dead_letter(message, &reason).await,
+        };
+
+        let metadata = RequestMetadata {
+            caller: request.caller.clone(),
+            trace_id: telemetry::trace_id(&Span::current()),
+        };
+        let reply = messages::reply(
+            &self.context.catalog.current().catalog,
+            &request.message,
+            &Locale::new(&request.locales),
+            &metadata,
+        );
+        // an answer that cannot be recorded is not published; the message is delivered again
+        if let Err(err) = messages::record(&self.context, &request.message, &reply, &metadata).await
+        {
+            tracing::error!("failed to record a reply: {err}");
+            return FAILED;
+        }
+
+        let (result, outcome) = match reply {
+            Ok(reply) => (
+                ReplyResult {
+                    id: request.id,
+                    message: request.message,
+                    reply: Some(reply.text),
+                    error: None,
+                    locale: reply.locale,
+                },
+                REPLIED,
+            ),
+            Err(ReplyError::UnknownMessage(message, error)) => (
+                ReplyResult {
+                    id: request.id,
+                    message,
+                    reply: None,
+                    error: Some(error.text),
+                    locale: error.locale,
+                },
+                UNKNOWN,
+            ),
+        };
+
+        let mut reply = Message::new(serde_json::to_vec(&result).expect("results serialize"));
+        telemetry::inject_context(&Span::current(), &mut HeaderInjector(&mut reply.headers));
+
+        match self.publish(&self.output_topic, reply).await {
+            Ok(()) => outcome,
+            Err(err) => self.dead_letter(message, &err.to_string()).await,
+        }
+    }
+
+    /// Publishes `message`, retrying with backoff up to `max_attempts` times.
+    async fn publish(&self, topic: &str, message: Message) -> Result<(), PortError> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match self.broker.publish(topic, message.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(err) if attempts >= self.max_attempts => return Err(err),
+                Err(err) => {
+                    tracing::warn!(attempts, "failed to publish to {topic}, retrying: {err}");
+                    self.metrics.retry_count.inc();
+                    tokio::time::sleep(self.backoff.delay(attempts)).await;
+                }
+            }
+        }
+    }
+
+    async fn dead_letter(&self, message: Message, reason: &str) -> &'static str {
+        tracing::warn!("dead-lettering a message: {reason}");
+        let message = message.with_header(DEAD_LETTER_REASON, reason);
+
+        match self.publish(&self.dead_letter_topic, message).await {
+            Ok(()) => DEAD_LETTERED,
+            Err(err) => {
+                tracing::error!("failed to dead-letter a message: {err}");
+                FAILED
+            }
+        }
+    }
+}
+
+fn parse(payload: &[u8]) -> Result<ReplyRequest, String> {
+    let request: ReplyRequest =
+        serde_json::from_slice(payload).map_err(|err| format!("invalid request: {err}"))?;
+
+    let length = request.message.chars().count();
+    if !(1..=MAX_MESSAGE_LENGTH).contains(&length) {
+        return Err(format!(
+            "message must be between 1 and {MAX_MESSAGE_LENGTH} characters long"
+        ));
+    }
+
+    Ok(request)
+}
+
+struct HeaderExtractor<'a>(&'a BTreeMap<String, String>);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(String::as_str).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut BTreeMap<String, String>);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.insert(key.to_string(), value);
+    }
+}
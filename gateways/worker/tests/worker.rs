@@ -0,0 +1,426 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use application::{outbox::Backoff, ports::DomainEvent};
+use infrastructure::{memory, shutdown::Shutdown};
+use prometheus_client::{encoding::text::encode, registry::Registry};
+use worker::{
+    broker::{file::FileBroker, memory::MemoryBroker, offsets::Offsets, Broker, Delivery, Message},
+    worker::{ReplyResult, Settings, Worker, DEAD_LETTER_REASON},
+};
+
+const INPUT: &str = "messages";
+const OUTPUT: &str = "replies";
+const DEAD_LETTERS: &str = "messages.dlq";
+
+fn settings(max_attempts: u32, retry: Duration, drain_timeout: Duration) -> Settings {
+    Settings {
+        output_topic: OUTPUT.to_string(),
+        dead_letter_topic: DEAD_LETTERS.to_string(),
+        concurrency: 4,
+        max_attempts,
+        backoff: Backoff {
+            initial: retry,
+            max: retry * 4,
+        },
+        drain_timeout,
+        context: memory::context(),
+    }
+}
+
+fn request(message: &str) -> Message {
+    Message::new(format!(r#"{{"id":"{message}-1","message":"{message}"}}"#))
+}
+
+fn results(broker: &MemoryBroker) -> Vec<ReplyResult> {
+    let mut results: Vec<ReplyResult> = broker
+        .messages(OUTPUT)
+        .iter()
+        .map(|message| serde_json::from_slice(&message.payload).unwrap())
+        .collect();
+    results.sort_by(|a, b| a.message.cmp(&b.message));
+    results
+}
+
+/// Runs the worker until every queued message is received and handled.
+async fn run(broker: &Arc<MemoryBroker>, settings: Settings) -> String {
+    let mut registry = Registry::default();
+    broker.close();
+
+    Worker::new(settings, broker.clone(), &mut registry)
+        .run(Shutdown::new())
+        .await
+        .unwrap();
+
+    let mut metrics = String::new();
+    encode(&mut metrics, &registry).unwrap();
+    metrics
+}
+
+#[tokio::test]
+async fn worker_publishes_replies_and_acknowledges_messages() {
+    let broker = Arc::new(MemoryBroker::new(INPUT));
+    broker.send(INPUT, request("ping"));
+    broker.send(INPUT, request("hi"));
+
+    let metrics = run(
+        &broker,
+        settings(3, Duration::from_millis(1), Duration::from_secs(5)),
+    )
+    .await;
+
+    assert_eq!(
+        vec![
+            ReplyResult {
+                id: Some("hi-1".to_string()),
+                message: "hi".to_string(),
+                reply: None,
+                error: Some("Unknown message \"hi\"".to_string()),
+                locale: "en".to_string(),
+            },
+            ReplyResult {
+                id: Some("ping-1".to_string()),
+                message: "ping".to_string(),
+                reply: Some("pong".to_string()),
+                error: None,
+                locale: "en".to_string(),
+            },
+        ],
+        results(&broker)
+    );
+    assert_eq!(2, broker.acked());
+    assert!(metrics.contains("worker_message_count_total{outcome=\"replied\"} 1"));
+    assert!(metrics.contains("worker_message_count_total{outcome=\"unknown\"} 1"));
+    assert!(metrics.contains("worker_in_flight_messages 0"));
+}
+
+#[tokio::test]
+async fn replies_are_recorded_with_their_event() {
+    let broker = Arc::new(MemoryBroker::new(INPUT));
+    broker.send(INPUT, request("ping"));
+    broker.send(INPUT, request("hi"));
+    let settings = settings(3, Duration::from_millis(1), Duration::from_secs(5));
+    let context = settings.context.clone();
+
+    run(&broker, settings).await;
+
+    let now = SystemTime::now();
+    let events = context
+        .outbox
+        .lease(now, now + Duration::from_secs(60), 10)
+        .await
+        .unwrap();
+    let mut recorded: Vec<(&str, &str, &str)> = events
+        .iter()
+        .map(|event| match &event.event {
+            DomainEvent::MessageReplied(record) | DomainEvent::UnknownMessageReceived(record) => (
+                event.event.name(),
+                record.message.as_str(),
+                record.reply.as_str(),
+            ),
+        })
+        .collect();
+    recorded.sort();
+    assert_eq!(
+        vec![
+            ("message.replied", "ping", "pong"),
+            ("message.unknown", "hi", "Unknown message \"hi\""),
+        ],
+        recorded
+    );
+}
+
+#[tokio::test]
+async fn invalid_messages_are_dead_lettered() {
+    let broker = Arc::new(MemoryBroker::new(INPUT));
+    broker.send(
+        INPUT,
+        Message::new("not json").with_header("x-caller", "alice"),
+    );
+    broker.send(INPUT, request(""));
+
+    let metrics = run(
+        &broker,
+        settings(3, Duration::from_millis(1), Duration::from_secs(5)),
+    )
+    .await;
+
+    let dead_letters = broker.messages(DEAD_LETTERS);
+    assert_eq!(2, dead_letters.len());
+    assert!(results(&broker).is_empty());
+    let invalid = dead_letters
+        .iter()
+        .find(|message| message.payload == b"not json")
+        .expect("the invalid message is dead-lettered");
+    assert_eq!(
+        Some("alice"),
+        invalid.headers.get("x-caller").map(String::as_str)
+    );
+    assert!(invalid.headers[DEAD_LETTER_REASON].starts_with("invalid request"));
+    assert_eq!(2, broker.acked());
+    assert!(metrics.contains("worker_message_count_total{outcome=\"dead_lettered\"} 2"));
+}
+
+#[tokio::test]
+async fn failed_publishes_are_retried() {
+    let broker = Arc::new(MemoryBroker::new(INPUT));
+    broker.send(INPUT, request("ping"));
+    broker.fail_publishes(OUTPUT, 2);
+
+    let metrics = run(
+        &broker,
+        settings(3, Duration::from_millis(1), Duration::from_secs(5)),
+    )
+    .await;
+
+    assert_eq!(1, results(&broker).len());
+    assert!(broker.messages(DEAD_LETTERS).is_empty());
+    assert!(metrics.contains("worker_retry_count_total 2"));
+    assert!(metrics.contains("worker_message_count_total{outcome=\"replied\"} 1"));
+}
+
+#[tokio::test]
+async fn messages_are_dead_lettered_after_the_last_attempt() {
+    let broker = Arc::new(MemoryBroker::new(INPUT));
+    broker.send(INPUT, request("ping"));
+    broker.fail_publishes(OUTPUT, 2);
+
+    run(
+        &broker,
+        settings(2, Duration::from_millis(1), Duration::from_secs(5)),
+    )
+    .await;
+
+    assert!(results(&broker).is_empty());
+    let dead_letters = broker.messages(DEAD_LETTERS);
+    assert_eq!(
+        vec![request("ping").payload],
+        vec![dead_letters[0].payload.clone()]
+    );
+    assert_eq!(
+        "failed to publish to replies: broker unavailable",
+        dead_letters[0].headers[DEAD_LETTER_REASON]
+    );
+    assert_eq!(1, broker.acked());
+}
+
+#[tokio::test]
+async fn failed_messages_are_delivered_again() {
+    let broker = Arc::new(MemoryBroker::new(INPUT));
+    broker.send(INPUT, request("ping"));
+    broker.fail_publishes(OUTPUT, 1);
+    broker.fail_publishes(DEAD_LETTERS, 1);
+
+    let metrics = run(
+        &broker,
+        settings(1, Duration::from_millis(1), Duration::from_secs(5)),
+    )
+    .await;
+
+    assert_eq!(1, broker.nacked());
+    assert_eq!(1, broker.acked());
+    assert_eq!(1, results(&broker).len());
+    assert!(metrics.contains("worker_message_count_total{outcome=\"failed\"} 1"));
+    assert!(metrics.contains("worker_message_count_total{outcome=\"replied\"} 1"));
+}
+
+#[tokio::test]
+async fn shutdown_drains_in_flight_messages() {
+    let broker = Arc::new(MemoryBroker::new(INPUT));
+    broker.send(INPUT, request("ping"));
+    broker.fail_publishes(OUTPUT, 1);
+    let shutdown = Shutdown::new();
+
+    let running = tokio::spawn(
+        Worker::new(
+            settings(3, Duration::from_millis(200), Duration::from_secs(5)),
+            broker.clone(),
+            &mut Registry::default(),
+        )
+        .run(shutdown.clone()),
+    );
+    // the reply waits for its retry when shutdown starts
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    shutdown.trigger();
+    running.await.unwrap().unwrap();
+
+    assert_eq!(1, results(&broker).len());
+    assert_eq!(1, broker.acked());
+}
+
+#[tokio::test]
+async fn drain_timeout_drops_unfinished_messages() {
+    let broker = Arc::new(MemoryBroker::new(INPUT));
+    broker.send(INPUT, request("ping"));
+    broker.fail_publishes(OUTPUT, 1);
+    let shutdown = Shutdown::new();
+
+    let running = tokio::spawn(
+        Worker::new(
+            settings(3, Duration::from_secs(60), Duration::from_millis(50)),
+            broker.clone(),
+            &mut Registry::default(),
+        )
+        .run(shutdown.clone()),
+    );
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    shutdown.trigger();
+    tokio::time::timeout(Duration::from_secs(5), running)
+        .await
+        .expect("the drain timed out")
+        .unwrap()
+        .unwrap();
+
+    assert!(results(&broker).is_empty());
+    assert_eq!(0, broker.acked());
+}
+
+async fn receive(broker: &FileBroker) -> Delivery {
+    tokio::time::timeout(Duration::from_secs(1), broker.receive())
+        .await
+        .expect("no message received")
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn file_broker_resumes_after_acknowledged_messages() {
+    let dir = tempfile::tempdir().unwrap();
+    let broker = FileBroker::open(dir.path(), INPUT).unwrap();
+    for message in ["ping", "hi", "hello"] {
+        broker.publish(INPUT, request(message)).await.unwrap();
+    }
+
+    let first = receive(&broker).await;
+    let second = receive(&broker).await;
+    assert_eq!(request("ping"), first.message);
+    assert_eq!(INPUT, first.topic);
+    // the second message is handled, but the first is not yet
+    second.receipt.ack().unwrap();
+    drop(broker);
+
+    let broker = FileBroker::open(dir.path(), INPUT).unwrap();
+    let first = receive(&broker).await;
+    assert_eq!(request("ping"), first.message);
+    first.receipt.ack().unwrap();
+    receive(&broker).await.receipt.ack().unwrap();
+    receive(&broker).await.receipt.ack().unwrap();
+    drop(broker);
+
+    let broker = FileBroker::open(dir.path(), INPUT).unwrap();
+    broker
+        .publish(
+            INPUT,
+            request("ping").with_header("traceparent", "00-1-2-01"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        Some("00-1-2-01"),
+        receive(&broker)
+            .await
+            .message
+            .headers
+            .get("traceparent")
+            .map(String::as_str)
+    );
+}
+
+#[tokio::test]
+async fn file_broker_delivers_handed_back_messages_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let broker = FileBroker::open(dir.path(), INPUT).unwrap();
+    for message in ["ping", "hi"] {
+        broker.publish(INPUT, request(message)).await.unwrap();
+    }
+
+    receive(&broker).await.receipt.nack().unwrap();
+    let again = receive(&broker).await;
+    assert_eq!(request("ping"), again.message);
+    // the next message is handled, but the one handed back is not yet
+    receive(&broker).await.receipt.ack().unwrap();
+    drop(again);
+    drop(broker);
+
+    let broker = FileBroker::open(dir.path(), INPUT).unwrap();
+    assert_eq!(request("ping"), receive(&broker).await.message);
+}
+
+fn ack(offsets: &Offsets, partition: i32, offset: i64, stored: &mut Vec<(i32, i64)>) {
+    offsets
+        .acked(INPUT, partition, offset, |handled| {
+            stored.push((partition, handled));
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+}
+
+#[test]
+fn offsets_are_stored_once_acknowledged_in_a_row() {
+    let offsets = Offsets::default();
+    let mut stored = Vec::new();
+    for offset in 0..4 {
+        offsets.received(INPUT, 0, offset);
+    }
+    offsets.received(INPUT, 1, 7);
+
+    // acknowledged out of order, the offsets move past the first gap only
+    ack(&offsets, 0, 1, &mut stored);
+    ack(&offsets, 0, 3, &mut stored);
+    assert!(stored.is_empty());
+    ack(&offsets, 0, 0, &mut stored);
+    assert_eq!(vec![(0, 1)], stored);
+    ack(&offsets, 1, 7, &mut stored);
+    ack(&offsets, 0, 2, &mut stored);
+    assert_eq!(vec![(0, 1), (1, 7), (0, 3)], stored);
+
+    // a failed store is retried by the next acknowledgement
+    offsets.received(INPUT, 0, 4);
+    offsets.received(INPUT, 0, 5);
+    assert_eq!(Err(()), offsets.acked(INPUT, 0, 4, |_| Err::<(), _>(())));
+    ack(&offsets, 0, 5, &mut stored);
+    assert_eq!((0, 5), stored[3]);
+}
+
+#[test]
+fn handed_back_offsets_hold_back_their_partition() {
+    let offsets = Offsets::default();
+    let mut stored = Vec::new();
+    for offset in 0..3 {
+        offsets.received(INPUT, 0, offset);
+    }
+
+    // the first message is handed back and sought back to, so it is received again
+    ack(&offsets, 0, 1, &mut stored);
+    ack(&offsets, 0, 2, &mut stored);
+    assert!(stored.is_empty());
+    for offset in 0..3 {
+        offsets.received(INPUT, 0, offset);
+    }
+    // the later messages are handled again before the offsets move past them
+    for offset in 0..3 {
+        ack(&offsets, 0, offset, &mut stored);
+    }
+    assert_eq!(vec![(0, 0), (0, 1), (0, 2)], stored);
+}
+
+#[test]
+fn revoked_partitions_are_forgotten() {
+    let offsets = Offsets::default();
+    let mut stored = Vec::new();
+    offsets.received(INPUT, 0, 0);
+    offsets.received(INPUT, 1, 0);
+
+    offsets.revoke([(INPUT, 0)]);
+    // acknowledging a message of a revoked partition does not store its offset
+    ack(&offsets, 0, 0, &mut stored);
+    ack(&offsets, 1, 0, &mut stored);
+    assert_eq!(vec![(1, 0)], stored);
+
+    // a partition assigned again starts over
+    offsets.received(INPUT, 0, 5);
+    ack(&offsets, 0, 5, &mut stored);
+    assert_eq!(vec![(1, 0), (0, 5)], stored);
+}
@@ -0,0 +1,13 @@
+-- Kept to types PostgreSQL and SQLite both understand so every backend runs the same
+-- migrations. `created_at` is in microseconds since the Unix epoch.
+CREATE TABLE messages (
+    id TEXT PRIMARY KEY,
+    caller TEXT,
+    message TEXT NOT NULL,
+    reply TEXT NOT NULL,
+    locale TEXT NOT NULL,
+    known BOOLEAN NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX messages_created_at ON messages (created_at);
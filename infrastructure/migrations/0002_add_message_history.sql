@@ -0,0 +1,6 @@
+ALTER TABLE messages ADD COLUMN trace_id TEXT;
+
+-- History pages are read newest first, optionally for one caller.
+DROP INDEX messages_created_at;
+CREATE INDEX messages_created_at_id ON messages (created_at, id);
+CREATE INDEX messages_caller_created_at_id ON messages (caller, created_at, id);
@@ -0,0 +1,15 @@
+-- Responses replayed to the retries of idempotent requests. A request claims its key with a
+-- NULL `status` and stores its response once it completes. `expires_at` is in microseconds
+-- since the Unix epoch.
+CREATE TABLE idempotency_keys (
+    caller TEXT NOT NULL,
+    idempotency_key TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    status INTEGER,
+    headers TEXT,
+    body BYTEA,
+    expires_at BIGINT NOT NULL,
+    PRIMARY KEY (caller, idempotency_key)
+);
+
+CREATE INDEX idempotency_keys_expires_at ON idempotency_keys (expires_at);
@@ -0,0 +1,14 @@
+-- Domain events stored with the change that produced them, until the relay publishes them.
+-- Times are in microseconds since the Unix epoch; a leased or failed event is due again at
+-- `next_attempt_at`.
+CREATE TABLE outbox (
+    id TEXT PRIMARY KEY,
+    event TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at BIGINT NOT NULL,
+    next_attempt_at BIGINT NOT NULL
+);
+
+CREATE INDEX outbox_next_attempt_at ON outbox (next_attempt_at, created_at, id);
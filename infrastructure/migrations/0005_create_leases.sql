@@ -0,0 +1,8 @@
+-- Leases naming the instance allowed to run singleton work, e.g. a scheduled job. A holder
+-- keeps its lease by renewing it before `expires_at`, in microseconds since the Unix epoch;
+-- any instance may take it over afterwards.
+CREATE TABLE leases (
+    name TEXT PRIMARY KEY,
+    holder TEXT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
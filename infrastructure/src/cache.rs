@@ -0,0 +1,493 @@
+//! In-process caches of values that are costly to compute or fetch, e.g. catalog lookups and
+//! upstream responses. Entries expire after a TTL and the least recently or least frequently
+//! used ones are evicted past a size limit. Concurrent misses of a key share a single load, and
+//! an expired entry can still be served for a while as it is reloaded in the background.
+
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    future::Future,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+    registry::Registry,
+};
+use tokio::sync::watch;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eviction {
+    /// Evicts the least recently used entry.
+    Lru,
+    /// Evicts the least frequently used entry, the least recently used of them on a tie.
+    Lfu,
+}
+
+impl Eviction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Eviction::Lru => "lru",
+            Eviction::Lfu => "lfu",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CacheSettings {
+    pub max_entries: usize,
+    /// How long an entry is fresh after being loaded.
+    pub ttl: Duration,
+    /// How long after expiring an entry is still served while it is reloaded in the background.
+    pub stale_while_revalidate: Duration,
+    pub eviction: Eviction,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CacheLabel {
+    pub cache: String,
+}
+
+/// Labels of an eviction. `reason` is `capacity`, `expired` or `purged`.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct EvictionLabel {
+    pub cache: String,
+    pub reason: &'static str,
+}
+
+/// Metrics of the caches, labelled by cache.
+#[derive(Clone)]
+pub struct CacheMetrics {
+    hits: Family<CacheLabel, Counter>,
+    misses: Family<CacheLabel, Counter>,
+    evictions: Family<EvictionLabel, Counter>,
+    entries: Family<CacheLabel, Gauge>,
+}
+
+impl CacheMetrics {
+    pub fn new(registry: &mut Registry) -> Self {
+        let metrics = CacheMetrics {
+            hits: Family::default(),
+            misses: Family::default(),
+            evictions: Family::default(),
+            entries: Family::default(),
+        };
+
+        registry.register(
+            "cache_hits",
+            "Number of lookups answered from the cache, stale entries included",
+            metrics.hits.clone(),
+        );
+        registry.register(
+            "cache_misses",
+            "Number of lookups that found no usable entry",
+            metrics.misses.clone(),
+        );
+        registry.register(
+            "cache_evictions",
+            "Number of entries removed before being replaced",
+            metrics.evictions.clone(),
+        );
+        registry.register(
+            "cache_entries",
+            "Number of entries held",
+            metrics.entries.clone(),
+        );
+
+        metrics
+    }
+}
+
+/// An entry as listed by [`CacheAdmin::entries`].
+#[derive(Clone, Debug)]
+pub struct EntryInfo {
+    pub key: String,
+    pub age: Duration,
+    /// `fresh`, `stale` while it can be served as it is reloaded, or `expired`.
+    pub state: &'static str,
+    pub hits: u64,
+}
+
+/// What operators can see and do with a cache, whatever it holds.
+pub trait CacheAdmin: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn settings(&self) -> &CacheSettings;
+
+    /// Entries from the next evicted to the last.
+    fn entries(&self) -> Vec<EntryInfo>;
+
+    /// Removes every entry, returning how many were removed.
+    fn purge(&self) -> usize;
+
+    /// Removes the entry whose key is displayed as `key`, returning whether there was one.
+    fn purge_key(&self, key: &str) -> bool;
+}
+
+struct Entry<V> {
+    value: V,
+    loaded_at: Instant,
+    hits: u64,
+    rank: Rank,
+}
+
+/// Position of an entry in the eviction order: use count (always 0 in LRU caches), then last
+/// use.
+type Rank = (u64, u64);
+
+struct Entries<K, V> {
+    map: HashMap<K, Entry<V>>,
+    order: BTreeMap<Rank, K>,
+    ticks: u64,
+}
+
+/// Outcome of a load shared with the lookups waiting for it. The error is the `Arc<E>` of
+/// the load, type-erased.
+type Loaded<V> = Option<Result<V, Arc<dyn Any + Send + Sync>>>;
+
+enum Lookup<V> {
+    Fresh(V),
+    Stale(V),
+    Missing,
+}
+
+enum Flight<K: Eq + Hash, V> {
+    Lead(Load<K, V>),
+    Wait(watch::Receiver<Loaded<V>>),
+}
+
+/// A cache of up to `max_entries` values. Keys are displayed in [`CacheAdmin::entries`].
+pub struct Cache<K, V> {
+    name: String,
+    settings: CacheSettings,
+    entries: Mutex<Entries<K, V>>,
+    loads: Mutex<HashMap<K, watch::Receiver<Loaded<V>>>>,
+    label: CacheLabel,
+    metrics: CacheMetrics,
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Clone + Eq + Hash + Display + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    pub fn new(name: impl Into<String>, settings: CacheSettings, metrics: CacheMetrics) -> Self {
+        let name = name.into();
+        Cache {
+            label: CacheLabel {
+                cache: name.clone(),
+            },
+            name,
+            settings,
+            entries: Mutex::new(Entries {
+                map: HashMap::new(),
+                order: BTreeMap::new(),
+                ticks: 0,
+            }),
+            loads: Mutex::new(HashMap::new()),
+            metrics,
+        }
+    }
+
+    /// Fresh value of `key`.
+    pub fn get(&self, key: &K) -> Option<V> {
+        match self.lookup(key) {
+            Lookup::Fresh(value) => {
+                self.metrics.hits.get_or_create(&self.label).inc();
+                Some(value)
+            }
+            Lookup::Stale(_) | Lookup::Missing => {
+                self.metrics.misses.get_or_create(&self.label).inc();
+                None
+            }
+        }
+    }
+
+    /// Value of `key`, loaded with `load` on a miss. Lookups missing the same key meanwhile
+    /// wait for that load rather than starting their own, and get its error if it fails. A
+    /// stale value is returned right away and reloaded in the background.
+    pub async fn get_with<F, Fut, E>(self: &Arc<Self>, key: K, load: F) -> Result<V, Arc<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>> + Send + 'static,
+        E: Display + Send + Sync + 'static,
+    {
+        match self.lookup(&key) {
+            Lookup::Fresh(value) => {
+                self.metrics.hits.get_or_create(&self.label).inc();
+                return Ok(value);
+            }
+            Lookup::Stale(value) => {
+                self.metrics.hits.get_or_create(&self.label).inc();
+                if let Flight::Lead(flight) = self.flight(&key) {
+                    let loading = load();
+                    tokio::spawn(async move {
+                        let cache = flight.cache.clone();
+                        if let Err(err) = flight.finish(loading.await) {
+                            log::warn!("failed to revalidate an entry of {}: {err}", cache.name);
+                        }
+                    });
+                }
+                return Ok(value);
+            }
+            Lookup::Missing => {
+                self.metrics.misses.get_or_create(&self.label).inc();
+            }
+        }
+
+        let mut load = Some(load);
+        loop {
+            match self.flight(&key) {
+                Flight::Lead(flight) => {
+                    // the previous load may have finished since the miss
+                    if let Lookup::Fresh(value) = self.lookup(&key) {
+                        return Ok(value);
+                    }
+                    // a lookup only leads once: it returns whatever its load gives
+                    let load = load.take().expect("a lookup leads a single load");
+                    return flight.finish(load().await);
+                }
+                Flight::Wait(mut loaded) => {
+                    if let Ok(loaded) = loaded.wait_for(Option::is_some).await {
+                        match loaded.clone().expect("waited for an outcome") {
+                            Ok(value) => return Ok(value),
+                            Err(err) => {
+                                if let Ok(err) = err.downcast::<E>() {
+                                    return Err(err);
+                                }
+                            }
+                        }
+                    }
+                    // the load was cancelled, or failed with an error of another type
+                    if let Lookup::Fresh(value) = self.lookup(&key) {
+                        return Ok(value);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+
+        let hits = match entries.map.remove(&key) {
+            // a reloaded entry keeps how often it was used
+            Some(replaced) => {
+                entries.order.remove(&replaced.rank);
+                replaced.hits
+            }
+            None => {
+                while entries.map.len() >= self.settings.max_entries.max(1) {
+                    let Some((_, evicted)) = entries.order.pop_first() else {
+                        break;
+                    };
+                    entries.map.remove(&evicted);
+                    self.evicted("capacity", 1);
+                }
+                0
+            }
+        };
+
+        let rank = self.rank(&mut entries, hits);
+        entries.order.insert(rank, key.clone());
+        entries.map.insert(
+            key,
+            Entry {
+                value,
+                loaded_at: Instant::now(),
+                hits,
+                rank,
+            },
+        );
+        self.count(&entries);
+    }
+
+    pub fn remove(&self, key: &K) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+
+        let Some(removed) = entries.map.remove(key) else {
+            return false;
+        };
+        entries.order.remove(&removed.rank);
+        self.evicted("purged", 1);
+        self.count(&entries);
+        true
+    }
+
+    fn lookup(&self, key: &K) -> Lookup<V> {
+        let mut entries = self.entries.lock().unwrap();
+
+        let Some(entry) = entries.map.get(key) else {
+            return Lookup::Missing;
+        };
+        let age = entry.loaded_at.elapsed();
+        if age >= self.settings.ttl + self.settings.stale_while_revalidate {
+            let rank = entry.rank;
+            entries.map.remove(key);
+            entries.order.remove(&rank);
+            self.evicted("expired", 1);
+            self.count(&entries);
+            return Lookup::Missing;
+        }
+
+        let (hits, rank) = (entry.hits + 1, entry.rank);
+        let next = self.rank(&mut entries, hits);
+        entries.order.remove(&rank);
+        entries.order.insert(next, key.clone());
+        let entry = entries.map.get_mut(key).expect("the entry was found above");
+        entry.hits = hits;
+        entry.rank = next;
+
+        if age < self.settings.ttl {
+            Lookup::Fresh(entry.value.clone())
+        } else {
+            Lookup::Stale(entry.value.clone())
+        }
+    }
+
+    /// Joins the load of `key` in flight, or starts one.
+    fn flight(self: &Arc<Self>, key: &K) -> Flight<K, V> {
+        let mut loads = self.loads.lock().unwrap();
+
+        if let Some(loaded) = loads.get(key) {
+            return Flight::Wait(loaded.clone());
+        }
+        let (done, loaded) = watch::channel(None);
+        loads.insert(key.clone(), loaded);
+        Flight::Lead(Load {
+            cache: self.clone(),
+            key: key.clone(),
+            done,
+        })
+    }
+
+    fn rank(&self, entries: &mut Entries<K, V>, hits: u64) -> Rank {
+        entries.ticks += 1;
+        match self.settings.eviction {
+            Eviction::Lru => (0, entries.ticks),
+            Eviction::Lfu => (hits, entries.ticks),
+        }
+    }
+
+    fn evicted(&self, reason: &'static str, count: u64) {
+        self.metrics
+            .evictions
+            .get_or_create(&EvictionLabel {
+                cache: self.name.clone(),
+                reason,
+            })
+            .inc_by(count);
+    }
+
+    fn count(&self, entries: &Entries<K, V>) {
+        self.metrics
+            .entries
+            .get_or_create(&self.label)
+            .set(entries.map.len() as i64);
+    }
+}
+
+impl<K, V> CacheAdmin for Cache<K, V>
+where
+    K: Clone + Eq + Hash + Display + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn settings(&self) -> &CacheSettings {
+        &self.settings
+    }
+
+    fn entries(&self) -> Vec<EntryInfo> {
+        let entries = self.entries.lock().unwrap();
+
+        entries
+            .order
+            .values()
+            .filter_map(|key| {
+                let entry = entries.map.get(key)?;
+                let age = entry.loaded_at.elapsed();
+                let state = if age < self.settings.ttl {
+                    "fresh"
+                } else if age < self.settings.ttl + self.settings.stale_while_revalidate {
+                    "stale"
+                } else {
+                    "expired"
+                };
+                Some(EntryInfo {
+                    key: key.to_string(),
+                    age,
+                    state,
+                    hits: entry.hits,
+                })
+            })
+            .collect()
+    }
+
+    fn purge(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+
+        let purged = entries.map.len();
+        entries.map.clear();
+        entries.order.clear();
+        if purged > 0 {
+            self.evicted("purged", purged as u64);
+        }
+        self.count(&entries);
+        purged
+    }
+
+    fn purge_key(&self, key: &str) -> bool {
+        let found = {
+            let entries = self.entries.lock().unwrap();
+            entries
+                .map
+                .keys()
+                .find(|candidate| candidate.to_string() == key)
+                .cloned()
+        };
+        found.is_some_and(|key| self.remove(&key))
+    }
+}
+
+/// A load of a key led by one lookup. Dropping it unfinished, e.g. when the lookup is
+/// cancelled, lets a waiting lookup lead a new one.
+struct Load<K: Eq + Hash, V> {
+    cache: Arc<Cache<K, V>>,
+    key: K,
+    done: watch::Sender<Loaded<V>>,
+}
+
+impl<K, V> Load<K, V>
+where
+    K: Clone + Eq + Hash + Display + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Stores a loaded value and hands the outcome to the waiting lookups.
+    fn finish<E: Send + Sync + 'static>(self, outcome: Result<V, E>) -> Result<V, Arc<E>> {
+        match outcome {
+            Ok(value) => {
+                self.cache.insert(self.key.clone(), value.clone());
+                self.done.send_replace(Some(Ok(value.clone())));
+                Ok(value)
+            }
+            Err(err) => {
+                let err = Arc::new(err);
+                self.done.send_replace(Some(Err(err.clone())));
+                Err(err)
+            }
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> Drop for Load<K, V> {
+    fn drop(&mut self) {
+        self.cache.loads.lock().unwrap().remove(&self.key);
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::{Duration, SystemTime},
+};
+
+use application::{
+    catalog::{CatalogDefinition, CatalogSnapshot, ReplyCatalog},
+    ports::{CatalogSource, LookupCache},
+};
+use eyre::{eyre, Context};
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+
+use crate::cache::{Cache, CacheAdmin};
+
+const BUILTIN_VERSION: &str = "builtin";
+
+/// Holds the active [`ReplyCatalog`] and swaps it atomically when its file changes. A file
+/// that fails to parse or compile is logged and the previous catalog stays active. Snapshot
+/// versions are the SHA-256 of the file contents, or `builtin` for the catalog compiled into
+/// the binary.
+pub struct CatalogStore {
+    source: Option<PathBuf>,
+    current: watch::Sender<Arc<CatalogSnapshot>>,
+    lookups: OnceLock<Arc<Cache<String, Option<usize>>>>,
+}
+
+impl CatalogStore {
+    pub fn new(catalog: ReplyCatalog) -> Self {
+        CatalogStore {
+            source: None,
+            current: watch::channel(Arc::new(CatalogSnapshot {
+                catalog,
+                source: None,
+                version: BUILTIN_VERSION.to_string(),
+                loaded_at: SystemTime::now(),
+            }))
+            .0,
+            lookups: OnceLock::new(),
+        }
+    }
+
+    /// Loads the catalog from a `.toml`, `.json`, `.yaml` or `.yml` file.
+    pub fn from_file(path: impl Into<PathBuf>) -> eyre::Result<Self> {
+        let path = path.into();
+        let snapshot = load(&path)?;
+
+        Ok(CatalogStore {
+            source: Some(path),
+            current: watch::channel(Arc::new(snapshot)).0,
+            lookups: OnceLock::new(),
+        })
+    }
+
+    /// Remembers the entries messages match in `cache`, for this version of the catalog and
+    /// the ones reloaded later. Only the first cache set is used.
+    pub fn cache_lookups(&self, cache: Arc<Cache<String, Option<usize>>>) {
+        if self.lookups.set(cache).is_ok() {
+            self.attach_lookups(&self.current());
+        }
+    }
+
+    /// Reloads the catalog file, returning whether a new version became active.
+    pub fn reload(&self) -> eyre::Result<bool> {
+        let Some(path) = &self.source else {
+            return Ok(false);
+        };
+
+        let content = std::fs::read(path)
+            .wrap_err_with(|| format!("error reading catalog {}", path.display()))?;
+        if version_of(&content) == self.current().version {
+            return Ok(false);
+        }
+
+        let snapshot = Arc::new(load_bytes(path, &content)?);
+        log::info!(
+            "Loaded reply catalog {} version {}",
+            path.display(),
+            snapshot.version
+        );
+        self.attach_lookups(&snapshot);
+        self.current.send_replace(snapshot);
+        // lookups of the previous version are not used anymore
+        if let Some(cache) = self.lookups.get() {
+            cache.purge();
+        }
+
+        Ok(true)
+    }
+
+    /// Polls the catalog file every `interval` and reloads it when its contents change.
+    pub fn watch(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+                if let Err(err) = self.reload() {
+                    log::error!("failed to reload reply catalog: {err:#}");
+                }
+            }
+        })
+    }
+}
+
+impl Default for CatalogStore {
+    /// Store of the catalog compiled into the binary.
+    fn default() -> Self {
+        CatalogStore::new(ReplyCatalog::default())
+    }
+}
+
+impl CatalogSource for CatalogStore {
+    fn current(&self) -> Arc<CatalogSnapshot> {
+        self.current.borrow().clone()
+    }
+
+    fn subscribe(&self) -> watch::Receiver<Arc<CatalogSnapshot>> {
+        self.current.subscribe()
+    }
+}
+
+impl CatalogStore {
+    fn attach_lookups(&self, snapshot: &CatalogSnapshot) {
+        if let Some(cache) = self.lookups.get() {
+            snapshot.catalog.cache_lookups(Arc::new(CatalogLookups {
+                cache: cache.clone(),
+                version: snapshot.version.clone(),
+            }));
+        }
+    }
+}
+
+/// Lookups of one version of a catalog, keyed by `<version>:<message>` so that the lookups of
+/// a catalog being replaced never answer for the new one.
+struct CatalogLookups {
+    cache: Arc<Cache<String, Option<usize>>>,
+    version: String,
+}
+
+impl LookupCache for CatalogLookups {
+    fn get(&self, message: &str) -> Option<Option<usize>> {
+        self.cache.get(&format!("{}:{message}", self.version))
+    }
+
+    fn insert(&self, message: &str, entry: Option<usize>) {
+        self.cache
+            .insert(format!("{}:{message}", self.version), entry);
+    }
+}
+
+fn load(path: &Path) -> eyre::Result<CatalogSnapshot> {
+    let content = std::fs::read(path)
+        .wrap_err_with(|| format!("error reading catalog {}", path.display()))?;
+    load_bytes(path, &content)
+}
+
+/// Parses `content` read from `path`, whose extension tells the format.
+fn load_bytes(path: &Path, content: &[u8]) -> eyre::Result<CatalogSnapshot> {
+    let text = std::str::from_utf8(content).wrap_err("catalog is not valid UTF-8")?;
+
+    let definition: CatalogDefinition = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(text).wrap_err("error parsing TOML catalog")?,
+        Some("json") => serde_json::from_str(text).wrap_err("error parsing JSON catalog")?,
+        Some("yaml" | "yml") => {
+            serde_yaml::from_str(text).wrap_err("error parsing YAML catalog")?
+        }
+        _ => return Err(eyre!("unsupported catalog format {}", path.display())),
+    };
+
+    Ok(CatalogSnapshot {
+        catalog: ReplyCatalog::new(definition)?,
+        source: Some(path.to_path_buf()),
+        version: version_of(content),
+        loaded_at: SystemTime::now(),
+    })
+}
+
+fn version_of(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
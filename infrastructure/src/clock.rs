@@ -0,0 +1,11 @@
+use std::time::SystemTime;
+
+use application::ports::Clock;
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
@@ -0,0 +1,193 @@
+use std::time::SystemTime;
+
+use application::ports::{
+    IdempotencyClaim, IdempotencyKey, IdempotencyStore, PortError, StoredResponse,
+};
+use async_trait::async_trait;
+
+use super::{to_micros, traced, Database, SYSTEM};
+
+/// Claims a free key, or takes over an expired one; affects no row when the key is live.
+pub(super) const CLAIM_KEY: &str = "INSERT INTO idempotency_keys \
+    (caller, idempotency_key, fingerprint, expires_at) VALUES ($1, $2, $3, $4) \
+    ON CONFLICT (caller, idempotency_key) DO UPDATE SET \
+    fingerprint = excluded.fingerprint, status = NULL, headers = NULL, body = NULL, \
+    expires_at = excluded.expires_at \
+    WHERE idempotency_keys.expires_at <= $5";
+
+pub(super) const SELECT_KEY: &str = "SELECT fingerprint, status, headers, body \
+    FROM idempotency_keys WHERE caller = $1 AND idempotency_key = $2";
+
+pub(super) const COMPLETE_KEY: &str = "UPDATE idempotency_keys \
+    SET status = $3, headers = $4, body = $5, expires_at = $6 \
+    WHERE caller = $1 AND idempotency_key = $2";
+
+pub(super) const RELEASE_KEY: &str = "DELETE FROM idempotency_keys \
+    WHERE caller = $1 AND idempotency_key = $2 AND status IS NULL";
+
+pub(super) const PURGE_KEYS: &str = "DELETE FROM idempotency_keys WHERE expires_at <= $1";
+
+/// A live row of the `idempotency_keys` table.
+#[derive(sqlx::FromRow)]
+pub(super) struct IdempotencyRow {
+    fingerprint: String,
+    status: Option<i32>,
+    headers: Option<String>,
+    body: Option<Vec<u8>>,
+}
+
+impl IdempotencyRow {
+    /// What claiming the key of this row for a request hashing to `fingerprint` resulted in.
+    pub(super) fn claim(self, fingerprint: &str) -> Result<IdempotencyClaim, PortError> {
+        if self.fingerprint != fingerprint {
+            return Ok(IdempotencyClaim::Mismatch);
+        }
+        let Some(status) = self.status else {
+            return Ok(IdempotencyClaim::InFlight);
+        };
+
+        let headers = serde_json::from_str(self.headers.as_deref().unwrap_or("[]"))
+            .map_err(|err| PortError::new("failed to decode stored headers", err))?;
+
+        Ok(IdempotencyClaim::Completed(StoredResponse {
+            status: u16::try_from(status)
+                .map_err(|err| PortError::new("failed to decode stored status", err))?,
+            headers,
+            body: self.body.unwrap_or_default(),
+        }))
+    }
+}
+
+/// Headers of `response` as stored in the `headers` column.
+pub(super) fn encode_headers(response: &StoredResponse) -> Result<String, PortError> {
+    serde_json::to_string(&response.headers)
+        .map_err(|err| PortError::new("failed to encode headers", err))
+}
+
+/// Keeps idempotent responses in the `idempotency_keys` table of PostgreSQL, shared by every
+/// instance of the service.
+#[derive(Clone)]
+pub struct PgIdempotencyStore {
+    db: Database,
+}
+
+impl PgIdempotencyStore {
+    pub fn new(db: Database) -> Self {
+        PgIdempotencyStore { db }
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for PgIdempotencyStore {
+    async fn claim(
+        &self,
+        key: &IdempotencyKey,
+        fingerprint: &str,
+        now: SystemTime,
+        expires_at: SystemTime,
+    ) -> Result<IdempotencyClaim, PortError> {
+        let query = sqlx::query(CLAIM_KEY)
+            .bind(&key.caller)
+            .bind(&key.key)
+            .bind(fingerprint)
+            .bind(to_micros(expires_at))
+            .bind(to_micros(now))
+            .execute(&self.db.pool);
+
+        let claimed = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "idempotency.claim",
+            CLAIM_KEY,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to claim idempotency key", err))?;
+        if claimed.rows_affected() > 0 {
+            return Ok(IdempotencyClaim::Claimed);
+        }
+
+        let query = sqlx::query_as::<_, IdempotencyRow>(SELECT_KEY)
+            .bind(&key.caller)
+            .bind(&key.key)
+            .fetch_optional(&self.db.pool);
+
+        let row = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "idempotency.get",
+            SELECT_KEY,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to load idempotency key", err))?;
+
+        // purged between the two statements: the key is about to be free again
+        row.map_or(Ok(IdempotencyClaim::InFlight), |row| row.claim(fingerprint))
+    }
+
+    async fn complete(
+        &self,
+        key: &IdempotencyKey,
+        response: &StoredResponse,
+        expires_at: SystemTime,
+    ) -> Result<(), PortError> {
+        let query = sqlx::query(COMPLETE_KEY)
+            .bind(&key.caller)
+            .bind(&key.key)
+            .bind(i32::from(response.status))
+            .bind(encode_headers(response)?)
+            .bind(&response.body)
+            .bind(to_micros(expires_at))
+            .execute(&self.db.pool);
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "idempotency.complete",
+            COMPLETE_KEY,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to store idempotent response", err))?;
+
+        Ok(())
+    }
+
+    async fn release(&self, key: &IdempotencyKey) -> Result<(), PortError> {
+        let query = sqlx::query(RELEASE_KEY)
+            .bind(&key.caller)
+            .bind(&key.key)
+            .execute(&self.db.pool);
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "idempotency.release",
+            RELEASE_KEY,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to release idempotency key", err))?;
+
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: SystemTime) -> Result<u64, PortError> {
+        let query = sqlx::query(PURGE_KEYS)
+            .bind(to_micros(now))
+            .execute(&self.db.pool);
+
+        let result = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "idempotency.purge",
+            PURGE_KEYS,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to purge idempotency keys", err))?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::SystemTime;
+
+use application::ports::PortError;
+use async_trait::async_trait;
+
+use super::{to_micros, traced, Database, SYSTEM};
+use crate::lease::LeaseStore;
+
+/// Takes a free or expired lease, or renews one the holder already has; affects no row when
+/// another holder has it.
+pub(super) const ACQUIRE_LEASE: &str = "INSERT INTO leases (name, holder, expires_at) \
+    VALUES ($1, $2, $4) \
+    ON CONFLICT (name) DO UPDATE SET holder = excluded.holder, expires_at = excluded.expires_at \
+    WHERE leases.holder = excluded.holder OR leases.expires_at <= $3";
+
+pub(super) const RELEASE_LEASE: &str = "DELETE FROM leases WHERE name = $1 AND holder = $2";
+
+pub(super) const SELECT_LEASE_HOLDER: &str =
+    "SELECT holder FROM leases WHERE name = $1 AND expires_at > $2";
+
+/// Keeps leases in the `leases` table of PostgreSQL, shared by every instance of the service.
+#[derive(Clone)]
+pub struct PgLeaseStore {
+    db: Database,
+}
+
+impl PgLeaseStore {
+    pub fn new(db: Database) -> Self {
+        PgLeaseStore { db }
+    }
+}
+
+#[async_trait]
+impl LeaseStore for PgLeaseStore {
+    async fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        now: SystemTime,
+        expires_at: SystemTime,
+    ) -> Result<bool, PortError> {
+        let query = sqlx::query(ACQUIRE_LEASE)
+            .bind(name)
+            .bind(holder)
+            .bind(to_micros(now))
+            .bind(to_micros(expires_at))
+            .execute(&self.db.pool);
+
+        let acquired = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "leases.acquire",
+            ACQUIRE_LEASE,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to acquire lease {name}"), err))?;
+
+        Ok(acquired.rows_affected() > 0)
+    }
+
+    async fn release(&self, name: &str, holder: &str) -> Result<(), PortError> {
+        let query = sqlx::query(RELEASE_LEASE)
+            .bind(name)
+            .bind(holder)
+            .execute(&self.db.pool);
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "leases.release",
+            RELEASE_LEASE,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to release lease {name}"), err))?;
+
+        Ok(())
+    }
+
+    async fn holder(&self, name: &str, now: SystemTime) -> Result<Option<String>, PortError> {
+        let query = sqlx::query_scalar::<_, String>(SELECT_LEASE_HOLDER)
+            .bind(name)
+            .bind(to_micros(now))
+            .fetch_optional(&self.db.pool);
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "leases.holder",
+            SELECT_LEASE_HOLDER,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to load lease {name}"), err))
+    }
+}
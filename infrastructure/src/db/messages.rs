@@ -0,0 +1,214 @@
+use std::time::SystemTime;
+
+use application::ports::{HistoryFilter, MessageRecord, MessageRepository, OutboxEvent, PortError};
+use async_trait::async_trait;
+
+use super::{from_micros, outbox::INSERT_EVENT, to_micros, traced, Database, SYSTEM};
+use crate::events;
+
+pub(super) const INSERT_MESSAGE: &str = "INSERT INTO messages \
+    (id, caller, message, reply, locale, known, trace_id, created_at) \
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)";
+
+pub(super) const SELECT_MESSAGE: &str = "SELECT id, caller, message, reply, locale, known, \
+    trace_id, created_at FROM messages WHERE id = $1";
+
+/// Binds the fields of a [`HistoryFilter`] through [`list_query`].
+pub(super) const LIST_MESSAGES: &str = "SELECT id, caller, message, reply, locale, known, \
+    trace_id, created_at FROM messages \
+    WHERE ($1 IS NULL OR caller = $1) \
+    AND ($2 IS NULL OR created_at >= $2) \
+    AND ($3 IS NULL OR created_at < $3) \
+    AND ($4 IS NULL OR created_at < $4 OR (created_at = $4 AND id < $5)) \
+    ORDER BY created_at DESC, id DESC \
+    LIMIT $6";
+
+pub(super) const DELETE_MESSAGES_BEFORE: &str = "DELETE FROM messages WHERE created_at < $1";
+
+/// A row of the `messages` table.
+#[derive(sqlx::FromRow)]
+pub(super) struct MessageRow {
+    id: String,
+    caller: Option<String>,
+    message: String,
+    reply: String,
+    locale: String,
+    known: bool,
+    trace_id: Option<String>,
+    created_at: i64,
+}
+
+impl From<MessageRow> for MessageRecord {
+    fn from(row: MessageRow) -> Self {
+        MessageRecord {
+            id: row.id,
+            caller: row.caller,
+            message: row.message,
+            reply: row.reply,
+            locale: row.locale,
+            known: row.known,
+            trace_id: row.trace_id,
+            created_at: from_micros(row.created_at),
+        }
+    }
+}
+
+/// Binds `record` to [`INSERT_MESSAGE`].
+pub(super) fn bind_message<'q, DB>(
+    query: sqlx::query::Query<'q, DB, <DB as sqlx::Database>::Arguments<'q>>,
+    record: &'q MessageRecord,
+) -> sqlx::query::Query<'q, DB, <DB as sqlx::Database>::Arguments<'q>>
+where
+    DB: sqlx::Database,
+    &'q str: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    Option<&'q str>: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    bool: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    i64: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+{
+    query
+        .bind(record.id.as_str())
+        .bind(record.caller.as_deref())
+        .bind(record.message.as_str())
+        .bind(record.reply.as_str())
+        .bind(record.locale.as_str())
+        .bind(record.known)
+        .bind(record.trace_id.as_deref())
+        .bind(to_micros(record.created_at))
+}
+
+/// Binds `event` to [`INSERT_EVENT`].
+pub(super) fn bind_event<'q, DB>(
+    query: sqlx::query::Query<'q, DB, <DB as sqlx::Database>::Arguments<'q>>,
+    event: &'q OutboxEvent,
+) -> sqlx::query::Query<'q, DB, <DB as sqlx::Database>::Arguments<'q>>
+where
+    DB: sqlx::Database,
+    &'q str: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    String: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    i64: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+{
+    query
+        .bind(event.id.as_str())
+        .bind(event.event.name())
+        .bind(events::encode(&event.event))
+        .bind(to_micros(event.created_at))
+}
+
+/// Prepares [`LIST_MESSAGES`] for `filter`.
+pub(super) fn list_query<DB>(
+    filter: &HistoryFilter,
+) -> sqlx::query::QueryAs<'static, DB, MessageRow, <DB as sqlx::Database>::Arguments<'static>>
+where
+    DB: sqlx::Database,
+    MessageRow: for<'r> sqlx::FromRow<'r, DB::Row>,
+    Option<String>: sqlx::Encode<'static, DB> + sqlx::Type<DB>,
+    Option<i64>: sqlx::Encode<'static, DB> + sqlx::Type<DB>,
+    i64: sqlx::Encode<'static, DB> + sqlx::Type<DB>,
+{
+    sqlx::query_as::<DB, MessageRow>(LIST_MESSAGES)
+        .bind(filter.caller.clone())
+        .bind(filter.from.map(to_micros))
+        .bind(filter.to.map(to_micros))
+        .bind(
+            filter
+                .after
+                .as_ref()
+                .map(|after| to_micros(after.created_at)),
+        )
+        .bind(filter.after.as_ref().map(|after| after.id.clone()))
+        .bind(i64::try_from(filter.limit).unwrap_or(i64::MAX))
+}
+
+/// Stores messages in the `messages` table of PostgreSQL.
+#[derive(Clone)]
+pub struct PgMessageRepository {
+    db: Database,
+}
+
+impl PgMessageRepository {
+    pub fn new(db: Database) -> Self {
+        PgMessageRepository { db }
+    }
+}
+
+#[async_trait]
+impl MessageRepository for PgMessageRepository {
+    async fn save(&self, record: &MessageRecord, events: &[OutboxEvent]) -> Result<(), PortError> {
+        let save = async {
+            let mut tx = self.db.pool.begin().await?;
+            bind_message(sqlx::query(INSERT_MESSAGE), record)
+                .execute(&mut *tx)
+                .await?;
+            for event in events {
+                bind_event(sqlx::query(INSERT_EVENT), event)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await
+        };
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "messages.save",
+            INSERT_MESSAGE,
+            save,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to save message {}", record.id), err))?;
+
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<MessageRecord>, PortError> {
+        let query = sqlx::query_as::<_, MessageRow>(SELECT_MESSAGE)
+            .bind(id)
+            .fetch_optional(&self.db.pool);
+
+        let row = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "messages.get",
+            SELECT_MESSAGE,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to load message {id}"), err))?;
+
+        Ok(row.map(MessageRecord::from))
+    }
+
+    async fn list(&self, filter: &HistoryFilter) -> Result<Vec<MessageRecord>, PortError> {
+        let query = list_query(filter).fetch_all(&self.db.pool);
+
+        let rows = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "messages.list",
+            LIST_MESSAGES,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to list messages", err))?;
+
+        Ok(rows.into_iter().map(MessageRecord::from).collect())
+    }
+
+    async fn delete_before(&self, before: SystemTime) -> Result<u64, PortError> {
+        let query = sqlx::query(DELETE_MESSAGES_BEFORE)
+            .bind(to_micros(before))
+            .execute(&self.db.pool);
+
+        let result = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "messages.delete_before",
+            DELETE_MESSAGES_BEFORE,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to delete old messages", err))?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use prometheus_client::{
+    collector::Collector,
+    encoding::{DescriptorEncoder, EncodeLabelSet, EncodeMetric},
+    metrics::{family::Family, gauge::ConstGauge, histogram::Histogram, MetricType},
+    registry::Registry,
+};
+use sqlx::Pool;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct QueryLabel {
+    query: String,
+    status: String,
+}
+
+#[derive(Clone)]
+pub(super) struct QueryMetrics {
+    duration: Family<QueryLabel, Histogram>,
+}
+
+impl QueryMetrics {
+    pub(super) fn new(registry: &mut Registry) -> Self {
+        let duration = Family::<QueryLabel, Histogram>::new_with_constructor(|| {
+            let buckets = [
+                0.5, 1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0,
+            ];
+            Histogram::new(buckets.into_iter())
+        });
+
+        registry.register(
+            "db_query_duration_ms",
+            "Duration of database queries, including the wait for a connection",
+            duration.clone(),
+        );
+
+        QueryMetrics { duration }
+    }
+
+    pub(super) fn observe(&self, query: &str, succeeded: bool, elapsed: Duration) {
+        self.duration
+            .get_or_create(&QueryLabel {
+                query: query.to_string(),
+                status: if succeeded { "ok" } else { "error" }.to_string(),
+            })
+            .observe(elapsed.as_secs_f64() * 1000.0);
+    }
+}
+
+/// Reports the state of the pools of a database when metrics are scraped, labelled by the
+/// role of each pool (e.g. `writer`).
+#[derive(Debug)]
+pub(super) struct PoolCollector<DB: sqlx::Database> {
+    pools: Vec<(&'static str, Pool<DB>)>,
+}
+
+impl<DB: sqlx::Database> PoolCollector<DB> {
+    pub(super) fn new(pools: Vec<(&'static str, Pool<DB>)>) -> Self {
+        PoolCollector { pools }
+    }
+}
+
+impl<DB: sqlx::Database> Collector for PoolCollector<DB> {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> Result<(), std::fmt::Error> {
+        let mut connections = encoder.encode_descriptor(
+            "db_pool_connections",
+            "Number of connections open in the database pools, by state",
+            None,
+            MetricType::Gauge,
+        )?;
+        for (name, pool) in &self.pools {
+            let idle = pool.num_idle() as i64;
+            let in_use = i64::from(pool.size()) - idle;
+
+            for (state, value) in [("idle", idle), ("in_use", in_use)] {
+                ConstGauge::new(value)
+                    .encode(connections.encode_family(&[("pool", *name), ("state", state)])?)?;
+            }
+        }
+
+        let mut max_connections = encoder.encode_descriptor(
+            "db_pool_max_connections",
+            "Maximum number of connections of the database pools",
+            None,
+            MetricType::Gauge,
+        )?;
+        for (name, pool) in &self.pools {
+            ConstGauge::new(i64::from(pool.options().get_max_connections()))
+                .encode(max_connections.encode_family(&[("pool", *name)])?)?;
+        }
+
+        Ok(())
+    }
+}
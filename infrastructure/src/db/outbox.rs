@@ -0,0 +1,151 @@
+use std::time::SystemTime;
+
+use application::ports::{Outbox, OutboxBacklog, OutboxEvent, PortError};
+use async_trait::async_trait;
+
+use super::{from_micros, to_micros, traced, Database, SYSTEM};
+use crate::events;
+
+pub(super) const INSERT_EVENT: &str = "INSERT INTO outbox \
+    (id, event, payload, created_at, next_attempt_at) VALUES ($1, $2, $3, $4, $4)";
+
+/// The top-level `next_attempt_at` check makes concurrent relays skip the events another one
+/// leased in the meantime.
+pub(super) const LEASE_EVENTS: &str = "UPDATE outbox SET next_attempt_at = $1 \
+    WHERE next_attempt_at <= $2 AND id IN (\
+        SELECT id FROM outbox WHERE next_attempt_at <= $2 \
+        ORDER BY next_attempt_at, created_at, id LIMIT $3) \
+    RETURNING id, event, payload, attempts, created_at";
+
+pub(super) const DELETE_EVENT: &str = "DELETE FROM outbox WHERE id = $1";
+
+pub(super) const FAIL_EVENT: &str = "UPDATE outbox \
+    SET attempts = attempts + 1, last_error = $2, next_attempt_at = $3 WHERE id = $1";
+
+pub(super) const COUNT_EVENTS: &str = "SELECT COUNT(*), MIN(created_at) FROM outbox";
+
+/// A row of the `outbox` table.
+#[derive(sqlx::FromRow)]
+pub(super) struct EventRow {
+    id: String,
+    event: String,
+    payload: String,
+    attempts: i32,
+    created_at: i64,
+}
+
+impl TryFrom<EventRow> for OutboxEvent {
+    type Error = PortError;
+
+    fn try_from(row: EventRow) -> Result<Self, Self::Error> {
+        Ok(OutboxEvent {
+            event: events::decode(&row.event, row.payload.as_bytes())?,
+            id: row.id,
+            attempts: row.attempts.try_into().unwrap_or_default(),
+            created_at: from_micros(row.created_at),
+        })
+    }
+}
+
+/// Leased rows as events, oldest first.
+pub(super) fn leased(rows: Vec<EventRow>) -> Result<Vec<OutboxEvent>, PortError> {
+    let mut events = rows
+        .into_iter()
+        .map(OutboxEvent::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    events.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+    Ok(events)
+}
+
+pub(super) fn backlog((pending, oldest): (i64, Option<i64>)) -> OutboxBacklog {
+    OutboxBacklog {
+        pending: pending.try_into().unwrap_or_default(),
+        oldest: oldest.map(from_micros),
+    }
+}
+
+/// The `outbox` table of PostgreSQL, filled by [`super::PgMessageRepository`].
+#[derive(Clone)]
+pub struct PgOutbox {
+    db: Database,
+}
+
+impl PgOutbox {
+    pub fn new(db: Database) -> Self {
+        PgOutbox { db }
+    }
+}
+
+#[async_trait]
+impl Outbox for PgOutbox {
+    async fn lease(
+        &self,
+        now: SystemTime,
+        lease_until: SystemTime,
+        limit: usize,
+    ) -> Result<Vec<OutboxEvent>, PortError> {
+        let query = sqlx::query_as::<_, EventRow>(LEASE_EVENTS)
+            .bind(to_micros(lease_until))
+            .bind(to_micros(now))
+            .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+            .fetch_all(&self.db.pool);
+
+        let rows = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "outbox.lease",
+            LEASE_EVENTS,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to lease outbox events", err))?;
+
+        leased(rows)
+    }
+
+    async fn delivered(&self, id: &str) -> Result<(), PortError> {
+        let query = sqlx::query(DELETE_EVENT).bind(id).execute(&self.db.pool);
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "outbox.delivered",
+            DELETE_EVENT,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to remove event {id}"), err))?;
+
+        Ok(())
+    }
+
+    async fn failed(&self, id: &str, error: &str, retry_at: SystemTime) -> Result<(), PortError> {
+        let query = sqlx::query(FAIL_EVENT)
+            .bind(id)
+            .bind(error)
+            .bind(to_micros(retry_at))
+            .execute(&self.db.pool);
+
+        traced(&self.db.metrics, SYSTEM, "outbox.failed", FAIL_EVENT, query)
+            .await
+            .map_err(|err| PortError::new(format!("failed to reschedule event {id}"), err))?;
+
+        Ok(())
+    }
+
+    async fn backlog(&self) -> Result<OutboxBacklog, PortError> {
+        let query = sqlx::query_as(COUNT_EVENTS).fetch_one(&self.db.pool);
+
+        let row = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "outbox.backlog",
+            COUNT_EVENTS,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to measure the outbox", err))?;
+
+        Ok(backlog(row))
+    }
+}
@@ -0,0 +1,537 @@
+//! SQLite storage for single-node deployments. SQLite allows one writer at a time, so writes go
+//! through a single connection while reads share a pool of read-only connections; WAL mode
+//! keeps readers from blocking on the writer.
+
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use application::{
+    context::AppContext,
+    ports::{
+        HealthCheck, HistoryFilter, IdempotencyClaim, IdempotencyKey, IdempotencyStore,
+        MessageRecord, MessageRepository, Outbox, OutboxBacklog, OutboxEvent, PortError,
+        StoredResponse,
+    },
+};
+use async_trait::async_trait;
+use log::LevelFilter;
+use prometheus_client::registry::Registry;
+use sqlx::{
+    sqlite::{
+        SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous,
+    },
+    ConnectOptions,
+};
+
+use super::{
+    idempotency::{
+        encode_headers, IdempotencyRow, CLAIM_KEY, COMPLETE_KEY, PURGE_KEYS, RELEASE_KEY,
+        SELECT_KEY,
+    },
+    leases::{ACQUIRE_LEASE, RELEASE_LEASE, SELECT_LEASE_HOLDER},
+    messages::{
+        bind_event, bind_message, list_query, MessageRow, DELETE_MESSAGES_BEFORE, INSERT_MESSAGE,
+        LIST_MESSAGES, SELECT_MESSAGE,
+    },
+    metrics::{PoolCollector, QueryMetrics},
+    outbox::{
+        backlog, leased, EventRow, COUNT_EVENTS, DELETE_EVENT, FAIL_EVENT, INSERT_EVENT,
+        LEASE_EVENTS,
+    },
+    to_micros, traced, HEALTH_CHECK, MIGRATOR,
+};
+use crate::{
+    catalog::CatalogStore, clock::SystemClock, events::LogEventPublisher, ids::UuidGenerator,
+    lease::LeaseStore,
+};
+
+const SYSTEM: &str = "sqlite";
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Database file, created when missing.
+    pub path: PathBuf,
+    /// Number of read-only connections.
+    pub readers: u32,
+    /// How long a statement waits for a lock held by another connection before failing.
+    pub busy_timeout: Duration,
+    /// Statements running longer than this are logged at `WARN`.
+    pub slow_statement_threshold: Duration,
+}
+
+#[derive(Clone)]
+pub struct SqliteDatabase {
+    writer: SqlitePool,
+    readers: SqlitePool,
+    metrics: QueryMetrics,
+}
+
+impl SqliteDatabase {
+    /// Opens the writer connection, creating the file and switching it to WAL mode; readers
+    /// are opened on first use.
+    pub async fn connect(settings: &Settings, registry: &mut Registry) -> eyre::Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(&settings.path)
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal)
+            .busy_timeout(settings.busy_timeout)
+            .log_statements(LevelFilter::Debug)
+            .log_slow_statements(LevelFilter::Warn, settings.slow_statement_threshold);
+
+        let writer = SqlitePoolOptions::new()
+            .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options.clone().create_if_missing(true))
+            .await?;
+
+        let readers = SqlitePoolOptions::new()
+            .max_connections(settings.readers)
+            .connect_lazy_with(options.read_only(true));
+
+        registry.register_collector(Box::new(PoolCollector::new(vec![
+            ("writer", writer.clone()),
+            ("reader", readers.clone()),
+        ])));
+
+        Ok(SqliteDatabase {
+            writer,
+            readers,
+            metrics: QueryMetrics::new(registry),
+        })
+    }
+
+    /// Applies the migrations the database has not run yet; they are the ones PostgreSQL runs.
+    #[tracing::instrument(name = "infrastructure.db.sqlite.migrate", skip_all, err)]
+    pub async fn migrate(&self) -> eyre::Result<()> {
+        MIGRATOR.run(&self.writer).await?;
+        Ok(())
+    }
+
+    /// Pool of the connection every write goes through.
+    pub fn writer(&self) -> &SqlitePool {
+        &self.writer
+    }
+
+    /// Pool of read-only connections.
+    pub fn readers(&self) -> &SqlitePool {
+        &self.readers
+    }
+
+    pub fn messages(&self) -> SqliteMessageRepository {
+        SqliteMessageRepository { db: self.clone() }
+    }
+
+    pub fn outbox(&self) -> SqliteOutbox {
+        SqliteOutbox { db: self.clone() }
+    }
+
+    pub fn idempotency(&self) -> SqliteIdempotencyStore {
+        SqliteIdempotencyStore { db: self.clone() }
+    }
+
+    pub fn leases(&self) -> SqliteLeaseStore {
+        SqliteLeaseStore { db: self.clone() }
+    }
+
+    pub async fn close(&self) {
+        tokio::join!(self.writer.close(), self.readers.close());
+    }
+}
+
+#[async_trait]
+impl HealthCheck for SqliteDatabase {
+    fn name(&self) -> &str {
+        "database"
+    }
+
+    async fn check(&self) -> Result<(), PortError> {
+        let query = sqlx::query(HEALTH_CHECK).execute(&self.writer);
+
+        traced(&self.metrics, SYSTEM, "health", HEALTH_CHECK, query)
+            .await
+            .map(|_| ())
+            .map_err(|err| PortError::new("database is unavailable", err))
+    }
+}
+
+/// Stores messages in the `messages` table of SQLite.
+#[derive(Clone)]
+pub struct SqliteMessageRepository {
+    db: SqliteDatabase,
+}
+
+#[async_trait]
+impl MessageRepository for SqliteMessageRepository {
+    async fn save(&self, record: &MessageRecord, events: &[OutboxEvent]) -> Result<(), PortError> {
+        let save = async {
+            let mut tx = self.db.writer.begin().await?;
+            bind_message(sqlx::query(INSERT_MESSAGE), record)
+                .execute(&mut *tx)
+                .await?;
+            for event in events {
+                bind_event(sqlx::query(INSERT_EVENT), event)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await
+        };
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "messages.save",
+            INSERT_MESSAGE,
+            save,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to save message {}", record.id), err))?;
+
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<MessageRecord>, PortError> {
+        let query = sqlx::query_as::<_, MessageRow>(SELECT_MESSAGE)
+            .bind(id)
+            .fetch_optional(&self.db.readers);
+
+        let row = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "messages.get",
+            SELECT_MESSAGE,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to load message {id}"), err))?;
+
+        Ok(row.map(MessageRecord::from))
+    }
+
+    async fn list(&self, filter: &HistoryFilter) -> Result<Vec<MessageRecord>, PortError> {
+        let query = list_query(filter).fetch_all(&self.db.readers);
+
+        let rows = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "messages.list",
+            LIST_MESSAGES,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to list messages", err))?;
+
+        Ok(rows.into_iter().map(MessageRecord::from).collect())
+    }
+
+    async fn delete_before(&self, before: SystemTime) -> Result<u64, PortError> {
+        let query = sqlx::query(DELETE_MESSAGES_BEFORE)
+            .bind(to_micros(before))
+            .execute(&self.db.writer);
+
+        let result = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "messages.delete_before",
+            DELETE_MESSAGES_BEFORE,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to delete old messages", err))?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// The `outbox` table of SQLite, filled by [`SqliteMessageRepository`].
+#[derive(Clone)]
+pub struct SqliteOutbox {
+    db: SqliteDatabase,
+}
+
+#[async_trait]
+impl Outbox for SqliteOutbox {
+    async fn lease(
+        &self,
+        now: SystemTime,
+        lease_until: SystemTime,
+        limit: usize,
+    ) -> Result<Vec<OutboxEvent>, PortError> {
+        let query = sqlx::query_as::<_, EventRow>(LEASE_EVENTS)
+            .bind(to_micros(lease_until))
+            .bind(to_micros(now))
+            .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+            .fetch_all(&self.db.writer);
+
+        let rows = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "outbox.lease",
+            LEASE_EVENTS,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to lease outbox events", err))?;
+
+        leased(rows)
+    }
+
+    async fn delivered(&self, id: &str) -> Result<(), PortError> {
+        let query = sqlx::query(DELETE_EVENT).bind(id).execute(&self.db.writer);
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "outbox.delivered",
+            DELETE_EVENT,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to remove event {id}"), err))?;
+
+        Ok(())
+    }
+
+    async fn failed(&self, id: &str, error: &str, retry_at: SystemTime) -> Result<(), PortError> {
+        let query = sqlx::query(FAIL_EVENT)
+            .bind(id)
+            .bind(error)
+            .bind(to_micros(retry_at))
+            .execute(&self.db.writer);
+
+        traced(&self.db.metrics, SYSTEM, "outbox.failed", FAIL_EVENT, query)
+            .await
+            .map_err(|err| PortError::new(format!("failed to reschedule event {id}"), err))?;
+
+        Ok(())
+    }
+
+    async fn backlog(&self) -> Result<OutboxBacklog, PortError> {
+        let query = sqlx::query_as(COUNT_EVENTS).fetch_one(&self.db.readers);
+
+        let row = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "outbox.backlog",
+            COUNT_EVENTS,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to measure the outbox", err))?;
+
+        Ok(backlog(row))
+    }
+}
+
+/// Keeps idempotent responses in the `idempotency_keys` table of SQLite. Claims read back
+/// through the writer, so they see the rows it just wrote.
+#[derive(Clone)]
+pub struct SqliteIdempotencyStore {
+    db: SqliteDatabase,
+}
+
+#[async_trait]
+impl IdempotencyStore for SqliteIdempotencyStore {
+    async fn claim(
+        &self,
+        key: &IdempotencyKey,
+        fingerprint: &str,
+        now: SystemTime,
+        expires_at: SystemTime,
+    ) -> Result<IdempotencyClaim, PortError> {
+        let query = sqlx::query(CLAIM_KEY)
+            .bind(&key.caller)
+            .bind(&key.key)
+            .bind(fingerprint)
+            .bind(to_micros(expires_at))
+            .bind(to_micros(now))
+            .execute(&self.db.writer);
+
+        let claimed = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "idempotency.claim",
+            CLAIM_KEY,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to claim idempotency key", err))?;
+        if claimed.rows_affected() > 0 {
+            return Ok(IdempotencyClaim::Claimed);
+        }
+
+        let query = sqlx::query_as::<_, IdempotencyRow>(SELECT_KEY)
+            .bind(&key.caller)
+            .bind(&key.key)
+            .fetch_optional(&self.db.writer);
+
+        let row = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "idempotency.get",
+            SELECT_KEY,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to load idempotency key", err))?;
+
+        row.map_or(Ok(IdempotencyClaim::InFlight), |row| row.claim(fingerprint))
+    }
+
+    async fn complete(
+        &self,
+        key: &IdempotencyKey,
+        response: &StoredResponse,
+        expires_at: SystemTime,
+    ) -> Result<(), PortError> {
+        let query = sqlx::query(COMPLETE_KEY)
+            .bind(&key.caller)
+            .bind(&key.key)
+            .bind(i32::from(response.status))
+            .bind(encode_headers(response)?)
+            .bind(&response.body)
+            .bind(to_micros(expires_at))
+            .execute(&self.db.writer);
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "idempotency.complete",
+            COMPLETE_KEY,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to store idempotent response", err))?;
+
+        Ok(())
+    }
+
+    async fn release(&self, key: &IdempotencyKey) -> Result<(), PortError> {
+        let query = sqlx::query(RELEASE_KEY)
+            .bind(&key.caller)
+            .bind(&key.key)
+            .execute(&self.db.writer);
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "idempotency.release",
+            RELEASE_KEY,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to release idempotency key", err))?;
+
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: SystemTime) -> Result<u64, PortError> {
+        let query = sqlx::query(PURGE_KEYS)
+            .bind(to_micros(now))
+            .execute(&self.db.writer);
+
+        let result = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "idempotency.purge",
+            PURGE_KEYS,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new("failed to purge idempotency keys", err))?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Keeps leases in the `leases` table of SQLite, read through the writer so a lease is never
+/// reported free right after it was taken.
+#[derive(Clone)]
+pub struct SqliteLeaseStore {
+    db: SqliteDatabase,
+}
+
+#[async_trait]
+impl LeaseStore for SqliteLeaseStore {
+    async fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        now: SystemTime,
+        expires_at: SystemTime,
+    ) -> Result<bool, PortError> {
+        let query = sqlx::query(ACQUIRE_LEASE)
+            .bind(name)
+            .bind(holder)
+            .bind(to_micros(now))
+            .bind(to_micros(expires_at))
+            .execute(&self.db.writer);
+
+        let acquired = traced(
+            &self.db.metrics,
+            SYSTEM,
+            "leases.acquire",
+            ACQUIRE_LEASE,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to acquire lease {name}"), err))?;
+
+        Ok(acquired.rows_affected() > 0)
+    }
+
+    async fn release(&self, name: &str, holder: &str) -> Result<(), PortError> {
+        let query = sqlx::query(RELEASE_LEASE)
+            .bind(name)
+            .bind(holder)
+            .execute(&self.db.writer);
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "leases.release",
+            RELEASE_LEASE,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to release lease {name}"), err))?;
+
+        Ok(())
+    }
+
+    async fn holder(&self, name: &str, now: SystemTime) -> Result<Option<String>, PortError> {
+        let query = sqlx::query_scalar::<_, String>(SELECT_LEASE_HOLDER)
+            .bind(name)
+            .bind(to_micros(now))
+            .fetch_optional(&self.db.writer);
+
+        traced(
+            &self.db.metrics,
+            SYSTEM,
+            "leases.holder",
+            SELECT_LEASE_HOLDER,
+            query,
+        )
+        .await
+        .map_err(|err| PortError::new(format!("failed to load lease {name}"), err))
+    }
+}
+
+/// A context storing messages, their events and idempotent responses in `db`, which is also
+/// checked for readiness. Replies come from the built-in catalog until `catalog` is replaced.
+pub fn context(db: &SqliteDatabase) -> AppContext {
+    AppContext {
+        messages: Arc::new(db.messages()),
+        events: Arc::new(LogEventPublisher),
+        outbox: Arc::new(db.outbox()),
+        clock: Arc::new(SystemClock),
+        ids: Arc::new(UuidGenerator),
+        idempotency: Arc::new(db.idempotency()),
+        catalog: Arc::new(CatalogStore::default()),
+        readiness: vec![Arc::new(db.clone())],
+    }
+}
@@ -0,0 +1,91 @@
+use application::ports::{DomainEvent, EventPublisher, MessageRecord, PortError};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{from_micros, to_micros};
+
+/// Publishes events to the log only, for deployments without a broker.
+pub struct LogEventPublisher;
+
+#[async_trait]
+impl EventPublisher for LogEventPublisher {
+    async fn publish(&self, event: &DomainEvent) -> Result<(), PortError> {
+        match event {
+            DomainEvent::MessageReplied(record) | DomainEvent::UnknownMessageReceived(record) => {
+                tracing::info!(event = event.name(), id = %record.id, "published event");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// JSON body of the `message.*` events; `created_at` is in microseconds since the Unix epoch.
+#[derive(Serialize, Deserialize)]
+struct MessagePayload {
+    id: String,
+    caller: Option<String>,
+    message: String,
+    reply: String,
+    locale: String,
+    known: bool,
+    trace_id: Option<String>,
+    created_at: i64,
+}
+
+impl From<&MessageRecord> for MessagePayload {
+    fn from(record: &MessageRecord) -> Self {
+        MessagePayload {
+            id: record.id.clone(),
+            caller: record.caller.clone(),
+            message: record.message.clone(),
+            reply: record.reply.clone(),
+            locale: record.locale.clone(),
+            known: record.known,
+            trace_id: record.trace_id.clone(),
+            created_at: to_micros(record.created_at),
+        }
+    }
+}
+
+impl From<MessagePayload> for MessageRecord {
+    fn from(payload: MessagePayload) -> Self {
+        MessageRecord {
+            id: payload.id,
+            caller: payload.caller,
+            message: payload.message,
+            reply: payload.reply,
+            locale: payload.locale,
+            known: payload.known,
+            trace_id: payload.trace_id,
+            created_at: from_micros(payload.created_at),
+        }
+    }
+}
+
+/// The JSON payload `event` is stored and sent as.
+pub fn encode(event: &DomainEvent) -> String {
+    let payload = match event {
+        DomainEvent::MessageReplied(record) | DomainEvent::UnknownMessageReceived(record) => {
+            MessagePayload::from(record)
+        }
+    };
+    serde_json::to_string(&payload).expect("event payloads serialize to JSON")
+}
+
+/// The event named `name` carried by `payload`, as produced by [`encode`].
+pub fn decode(name: &str, payload: &[u8]) -> Result<DomainEvent, PortError> {
+    let record = |payload| {
+        serde_json::from_slice::<MessagePayload>(payload)
+            .map(MessageRecord::from)
+            .map_err(|err| PortError::new(format!("invalid {name} event"), err))
+    };
+
+    match name {
+        "message.replied" => Ok(DomainEvent::MessageReplied(record(payload)?)),
+        "message.unknown" => Ok(DomainEvent::UnknownMessageReceived(record(payload)?)),
+        _ => Err(PortError::new(
+            "failed to decode event",
+            format!("unknown event {name}"),
+        )),
+    }
+}
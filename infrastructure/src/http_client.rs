@@ -0,0 +1,501 @@
+//! Outbound HTTP calls to the services this one depends on. Each upstream is named and has its
+//! own timeouts and retries; every attempt runs under a client span whose W3C trace context is
+//! sent along, and is measured in `http_client_request_duration_ms`. Attempts go through the
+//! circuit breaker and bulkhead of their upstream, whose circuits are reported by readiness.
+//! Responses to GET requests can be cached with [`HttpClient::get_cached`].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use application::{
+    outbox::Backoff,
+    ports::{HealthCheck, PortError},
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use opentelemetry::propagation::Injector;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, histogram::Histogram},
+    registry::Registry,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Method, Request, Response, StatusCode, Url,
+};
+use serde::Serialize;
+use tracing::{field::Empty, Instrument};
+
+use crate::{
+    cache::Cache,
+    resilience::{
+        Bulkhead, BulkheadSettings, CircuitBreaker, CircuitBreakerSettings, CircuitState,
+        ResilienceMetrics,
+    },
+    telemetry,
+};
+
+/// Statuses of a response worth retrying: the upstream is overloaded or restarting.
+const RETRIED_STATUSES: [StatusCode; 4] = [
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+#[derive(Clone, Debug)]
+pub struct UpstreamSettings {
+    /// e.g. `http://billing:8080/api`; request paths are appended to it.
+    pub base_url: String,
+    /// How long each attempt may last.
+    pub timeout: Duration,
+    /// How long a request may last, its retries and the delays between them included.
+    pub deadline: Duration,
+    /// Attempts of an idempotent request failing with a connection error, a timeout or a
+    /// 429, 502, 503 or 504 status. Other requests are sent once.
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+    /// Connection errors, timeouts and 5xx statuses count as failures of the upstream.
+    pub circuit_breaker: CircuitBreakerSettings,
+    pub bulkhead: BulkheadSettings,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum HttpClientError {
+    #[error("unknown upstream {0}")]
+    UnknownUpstream(String),
+    #[error("request to {upstream} failed: {source}")]
+    Request {
+        upstream: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("request to {upstream} exceeded its {deadline:?} deadline")]
+    DeadlineExceeded {
+        upstream: String,
+        deadline: Duration,
+    },
+    #[error("circuit of {upstream} is open")]
+    CircuitOpen { upstream: String },
+    #[error("too many concurrent requests to {upstream}")]
+    BulkheadFull { upstream: String },
+    #[error("{upstream} answered {status}")]
+    Status {
+        upstream: String,
+        status: StatusCode,
+    },
+}
+
+/// A successful response, read whole so it can be cached.
+#[derive(Clone, Debug)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+/// Labels of an attempt. `status` is the response status, or `error` when none was received.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RequestLabel {
+    pub upstream: String,
+    pub method: String,
+    pub status: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct UpstreamLabel {
+    pub upstream: String,
+}
+
+#[derive(Clone)]
+struct ClientMetrics {
+    duration: Family<RequestLabel, Histogram>,
+    retries: Family<UpstreamLabel, Counter>,
+}
+
+impl ClientMetrics {
+    fn new(registry: &mut Registry) -> Self {
+        let metrics = ClientMetrics {
+            duration: Family::new_with_constructor(|| {
+                let buckets = [
+                    1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0,
+                    10000.0,
+                ];
+                Histogram::new(buckets.into_iter())
+            }),
+            retries: Family::default(),
+        };
+
+        registry.register(
+            "http_client_request_duration_ms",
+            "Duration of each attempt of an outbound request",
+            metrics.duration.clone(),
+        );
+        registry.register(
+            "http_client_retries",
+            "Number of outbound requests retried",
+            metrics.retries.clone(),
+        );
+
+        metrics
+    }
+}
+
+struct Upstream {
+    name: String,
+    settings: UpstreamSettings,
+    breaker: CircuitBreaker,
+    bulkhead: Bulkhead,
+}
+
+/// Client of the configured upstreams, sharing one connection pool. Cheap to share behind an
+/// `Arc`.
+pub struct HttpClient {
+    client: reqwest::Client,
+    upstreams: HashMap<String, Upstream>,
+    metrics: ClientMetrics,
+    responses: Option<Arc<Cache<String, CachedResponse>>>,
+}
+
+impl HttpClient {
+    pub fn new(
+        upstreams: HashMap<String, UpstreamSettings>,
+        registry: &mut Registry,
+    ) -> eyre::Result<Self> {
+        let resilience = ResilienceMetrics::new(registry);
+        let upstreams = upstreams
+            .into_iter()
+            .map(|(name, settings)| {
+                Url::parse(&settings.base_url).map_err(|err| {
+                    eyre::eyre!("invalid base url {:?} of {name}: {err}", settings.base_url)
+                })?;
+                let upstream = Upstream {
+                    breaker: CircuitBreaker::new(
+                        &name,
+                        settings.circuit_breaker.clone(),
+                        resilience.clone(),
+                    ),
+                    bulkhead: Bulkhead::new(&name, &settings.bulkhead, resilience.clone()),
+                    name: name.clone(),
+                    settings,
+                };
+                Ok((name, upstream))
+            })
+            .collect::<eyre::Result<_>>()?;
+
+        Ok(HttpClient {
+            client: reqwest::Client::builder().build()?,
+            upstreams,
+            metrics: ClientMetrics::new(registry),
+            responses: None,
+        })
+    }
+
+    /// Caches the responses of [`HttpClient::get_cached`] in `responses`, keyed by
+    /// `<upstream> <path>`.
+    pub fn with_cache(mut self, responses: Arc<Cache<String, CachedResponse>>) -> Self {
+        self.responses = Some(responses);
+        self
+    }
+
+    /// State of the circuit of `upstream`, `None` when it is unknown.
+    pub fn circuit(&self, upstream: &str) -> Option<CircuitState> {
+        self.upstreams
+            .get(upstream)
+            .map(|upstream| upstream.breaker.state())
+    }
+
+    /// Starts a request to `path` (e.g. `/v1/invoices`) of `upstream`.
+    pub fn request(
+        &self,
+        upstream: &str,
+        method: Method,
+        path: &str,
+    ) -> Result<UpstreamRequest<'_>, HttpClientError> {
+        let upstream = self
+            .upstreams
+            .get(upstream)
+            .ok_or_else(|| HttpClientError::UnknownUpstream(upstream.to_string()))?;
+        let url = format!(
+            "{}/{}",
+            upstream.settings.base_url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        );
+
+        Ok(UpstreamRequest {
+            client: self,
+            upstream,
+            builder: self.client.request(method, url),
+        })
+    }
+
+    pub fn get(&self, upstream: &str, path: &str) -> Result<UpstreamRequest<'_>, HttpClientError> {
+        self.request(upstream, Method::GET, path)
+    }
+
+    pub fn post(&self, upstream: &str, path: &str) -> Result<UpstreamRequest<'_>, HttpClientError> {
+        self.request(upstream, Method::POST, path)
+    }
+
+    /// Sends a GET request to `path` of `upstream` unless its response is cached, and caches
+    /// it when its status is a success. Other statuses are reported as
+    /// [`HttpClientError::Status`], to each request waiting on the same response.
+    pub async fn get_cached(
+        self: &Arc<Self>,
+        upstream: &str,
+        path: &str,
+    ) -> Result<CachedResponse, Arc<HttpClientError>> {
+        if !self.upstreams.contains_key(upstream) {
+            return Err(Arc::new(HttpClientError::UnknownUpstream(
+                upstream.to_string(),
+            )));
+        }
+
+        let key = format!("{upstream} {path}");
+        let (client, upstream, path) = (self.clone(), upstream.to_string(), path.to_string());
+        let fetch = async move {
+            let response = client.get(&upstream, &path)?.send().await?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(HttpClientError::Status { upstream, status });
+            }
+
+            let headers = response.headers().clone();
+            let body = response
+                .bytes()
+                .await
+                .map_err(|err| HttpClientError::Request {
+                    upstream,
+                    source: err,
+                })?;
+            Ok(CachedResponse {
+                status,
+                headers,
+                body,
+            })
+        };
+
+        match &self.responses {
+            Some(responses) => responses.get_with(key, || fetch).await,
+            None => fetch.await.map_err(Arc::new),
+        }
+    }
+
+    /// Sends `request`, retrying it while the policy of `upstream` and its deadline allow.
+    /// Returns the last response received, whatever its status, unless the circuit or the
+    /// bulkhead of `upstream` rejects an attempt.
+    async fn send(
+        &self,
+        upstream: &Upstream,
+        mut request: Request,
+    ) -> Result<Response, HttpClientError> {
+        let settings = &upstream.settings;
+        let deadline = Instant::now() + settings.deadline;
+        let exceeded = || HttpClientError::DeadlineExceeded {
+            upstream: upstream.name.clone(),
+            deadline: settings.deadline,
+        };
+        let retried = request.method().is_idempotent();
+        let mut attempt = 1;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(exceeded());
+            }
+            let timeout = settings.timeout.min(remaining);
+            // `None` when the body is a stream, which cannot be sent twice
+            let next = if retried && attempt < settings.max_attempts {
+                request.try_clone()
+            } else {
+                None
+            };
+
+            let call =
+                upstream
+                    .breaker
+                    .try_acquire()
+                    .ok_or_else(|| HttpClientError::CircuitOpen {
+                        upstream: upstream.name.clone(),
+                    })?;
+            let slot = upstream.bulkhead.acquire(remaining).await.ok_or_else(|| {
+                HttpClientError::BulkheadFull {
+                    upstream: upstream.name.clone(),
+                }
+            })?;
+
+            *request.timeout_mut() = Some(timeout);
+            let outcome = self.attempt(upstream, request, attempt).await;
+            let failed = match &outcome {
+                Ok(response) => RETRIED_STATUSES.contains(&response.status()),
+                Err(err) => err.is_connect() || err.is_timeout(),
+            };
+            match &outcome {
+                Ok(response) if !response.status().is_server_error() => call.success(),
+                _ => call.failure(),
+            }
+            drop(slot);
+
+            let delay = settings.backoff.delay(attempt);
+            match next {
+                Some(next) if failed && Instant::now() + delay < deadline => {
+                    self.metrics
+                        .retries
+                        .get_or_create(&UpstreamLabel {
+                            upstream: upstream.name.clone(),
+                        })
+                        .inc();
+                    tokio::time::sleep(delay).await;
+                    request = next;
+                    attempt += 1;
+                }
+                _ => {
+                    return outcome.map_err(|err| {
+                        if err.is_timeout() && timeout < settings.timeout {
+                            exceeded()
+                        } else {
+                            HttpClientError::Request {
+                                upstream: upstream.name.clone(),
+                                source: err,
+                            }
+                        }
+                    })
+                }
+            }
+        }
+    }
+
+    async fn attempt(
+        &self,
+        upstream: &Upstream,
+        mut request: Request,
+        attempt: u32,
+    ) -> Result<Response, reqwest::Error> {
+        let method = request.method().clone();
+        let span = tracing::info_span!(
+            "infrastructure.http_client.request",
+            otel.name = method.as_str(),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            upstream = upstream.name,
+            http.request.method = method.as_str(),
+            http.request.resend_count = attempt - 1,
+            http.response.status_code = Empty,
+            server.address = request.url().host_str(),
+            url.full = request.url().as_str(),
+        );
+        telemetry::inject_context(&span, &mut HeaderInjector(request.headers_mut()));
+
+        let started = Instant::now();
+        let outcome = self.client.execute(request).instrument(span.clone()).await;
+        let elapsed = started.elapsed();
+
+        let status = match &outcome {
+            Ok(response) => {
+                let status = response.status();
+                span.record("http.response.status_code", status.as_u16());
+                if status.is_server_error() {
+                    span.record("otel.status_code", "ERROR");
+                }
+                status.as_u16().to_string()
+            }
+            Err(err) => {
+                span.record("otel.status_code", "ERROR");
+                tracing::warn!(parent: &span, error = %err, "request to {} failed", upstream.name);
+                "error".to_string()
+            }
+        };
+        self.metrics
+            .duration
+            .get_or_create(&RequestLabel {
+                upstream: upstream.name.clone(),
+                method: method.to_string(),
+                status,
+            })
+            .observe(elapsed.as_secs_f64() * 1000.0);
+
+        outcome
+    }
+}
+
+#[async_trait]
+impl HealthCheck for HttpClient {
+    fn name(&self) -> &str {
+        "upstreams"
+    }
+
+    /// An open circuit does not make this instance unready: requests depending on the
+    /// upstream fail fast, the others are still served.
+    async fn check(&self) -> Result<(), PortError> {
+        Ok(())
+    }
+
+    async fn details(&self) -> BTreeMap<String, String> {
+        self.upstreams
+            .values()
+            .map(|upstream| {
+                (
+                    upstream.name.clone(),
+                    upstream.breaker.state().as_str().to_string(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// A request being built for an upstream, sent by [`UpstreamRequest::send`].
+pub struct UpstreamRequest<'a> {
+    client: &'a HttpClient,
+    upstream: &'a Upstream,
+    builder: reqwest::RequestBuilder,
+}
+
+impl UpstreamRequest<'_> {
+    pub fn header(mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.builder = self.builder.header(name.as_ref(), value.as_ref());
+        self
+    }
+
+    pub fn query<T: Serialize + ?Sized>(mut self, query: &T) -> Self {
+        self.builder = self.builder.query(query);
+        self
+    }
+
+    pub fn json<T: Serialize + ?Sized>(mut self, body: &T) -> Self {
+        self.builder = self.builder.json(body);
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<reqwest::Body>) -> Self {
+        self.builder = self.builder.body(body);
+        self
+    }
+
+    pub async fn send(self) -> Result<Response, HttpClientError> {
+        let request = self
+            .builder
+            .build()
+            .map_err(|err| HttpClientError::Request {
+                upstream: self.upstream.name.clone(),
+                source: err,
+            })?;
+
+        self.client.send(self.upstream, request).await
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
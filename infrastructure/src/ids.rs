@@ -0,0 +1,11 @@
+use application::ports::IdGenerator;
+use uuid::Uuid;
+
+/// Generates UUIDv7 identifiers, which sort by creation time.
+pub struct UuidGenerator;
+
+impl IdGenerator for UuidGenerator {
+    fn generate(&self) -> String {
+        Uuid::now_v7().to_string()
+    }
+}
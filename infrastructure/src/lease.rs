@@ -0,0 +1,264 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::File,
+    io::{Read, Seek, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use application::ports::{HealthCheck, PortError};
+use async_trait::async_trait;
+use fs4::FileExt;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{family::Family, gauge::Gauge},
+    registry::Registry,
+};
+
+/// Named leases shared by the instances of the service, each held by at most one of them at a
+/// time, e.g. to run a job on a single instance.
+#[async_trait]
+pub trait LeaseStore: Send + Sync {
+    /// Takes `name` for `holder` until `expires_at`, or extends it when `holder` already has
+    /// it. `false` when another holder has it past `now`.
+    async fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        now: SystemTime,
+        expires_at: SystemTime,
+    ) -> Result<bool, PortError>;
+
+    /// Gives up `name` if `holder` has it.
+    async fn release(&self, name: &str, holder: &str) -> Result<(), PortError>;
+
+    /// Holder of `name` at `now`, if any.
+    async fn holder(&self, name: &str, now: SystemTime) -> Result<Option<String>, PortError>;
+}
+
+/// Leases held with exclusive locks on `<name>.lock` files of a directory, for instances
+/// running on a single host. A lock is held until released or until its process exits, so
+/// expiry times are ignored; the holder is written in the file for the others to read, and
+/// cleared on release. Reading it takes no lock, since `acquire` fails while a reader holds one;
+/// the name of a holder that exited without releasing is read until another instance takes the
+/// lease.
+pub struct FileLeaseStore {
+    dir: PathBuf,
+    held: Mutex<HashMap<String, (File, String)>>,
+}
+
+impl FileLeaseStore {
+    /// Keeps the lock files in `dir`, which is created if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, PortError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .map_err(|err| PortError::new(format!("failed to create {}", dir.display()), err))?;
+
+        Ok(FileLeaseStore {
+            dir,
+            held: Mutex::default(),
+        })
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.lock"))
+    }
+
+    fn open(&self, name: &str) -> Result<File, PortError> {
+        let path = self.path(name);
+        File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|err| PortError::new(format!("failed to open {}", path.display()), err))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, (File, String)>> {
+        self.held.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl LeaseStore for FileLeaseStore {
+    async fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        _now: SystemTime,
+        _expires_at: SystemTime,
+    ) -> Result<bool, PortError> {
+        let mut held = self.lock();
+        if let Some((_, current)) = held.get(name) {
+            return Ok(current == holder);
+        }
+
+        let mut file = self.open(name)?;
+        match FileExt::try_lock(&file) {
+            Ok(()) => {}
+            Err(fs4::TryLockError::WouldBlock) => return Ok(false),
+            Err(fs4::TryLockError::Error(err)) => {
+                return Err(PortError::new(format!("failed to lock {name}"), err))
+            }
+        }
+
+        let written = file
+            .set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| file.write_all(holder.as_bytes()));
+        if let Err(err) = written {
+            return Err(PortError::new(format!("failed to write {name}"), err));
+        }
+        held.insert(name.to_string(), (file, holder.to_string()));
+
+        Ok(true)
+    }
+
+    async fn release(&self, name: &str, holder: &str) -> Result<(), PortError> {
+        let mut held = self.lock();
+        if held.get(name).is_some_and(|(_, current)| current == holder) {
+            // closing the file unlocks it
+            if let Some((file, _)) = held.remove(name) {
+                file.set_len(0)
+                    .map_err(|err| PortError::new(format!("failed to clear {name}"), err))?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn holder(&self, name: &str, _now: SystemTime) -> Result<Option<String>, PortError> {
+        if let Some((_, holder)) = self.lock().get(name) {
+            return Ok(Some(holder.clone()));
+        }
+        if !self.path(name).exists() {
+            return Ok(None);
+        }
+
+        let mut holder = String::new();
+        self.open(name)?
+            .read_to_string(&mut holder)
+            .map_err(|err| PortError::new(format!("failed to read {name}"), err))?;
+        Ok(Some(holder).filter(|holder| !holder.is_empty()))
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct LeaseLabel {
+    pub lease: String,
+}
+
+/// The leases of this instance, identified as `holder`, each taken for `ttl` and renewed
+/// while in use. Reports, in `lease_held`, `1` for each lease it holds and `0` for the
+/// others, and, as the `leases` readiness check, the holder of each lease it competes for.
+pub struct Leases {
+    store: Arc<dyn LeaseStore>,
+    holder: String,
+    ttl: Duration,
+    names: Mutex<BTreeSet<String>>,
+    held: Family<LeaseLabel, Gauge>,
+}
+
+impl Leases {
+    pub fn new(
+        store: Arc<dyn LeaseStore>,
+        holder: impl Into<String>,
+        ttl: Duration,
+        registry: &mut Registry,
+    ) -> Self {
+        let held = Family::<LeaseLabel, Gauge>::default();
+        registry.register(
+            "lease_held",
+            "Whether this instance holds the lease",
+            held.clone(),
+        );
+
+        Leases {
+            store,
+            holder: holder.into(),
+            ttl,
+            names: Mutex::default(),
+            held,
+        }
+    }
+
+    /// Identity of this instance in the lease store.
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    /// How long a lease lasts once taken or renewed.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Takes or renews `name`. `false` when another instance holds it.
+    pub async fn acquire(&self, name: &str) -> Result<bool, PortError> {
+        self.register(name);
+        let now = SystemTime::now();
+        let acquired = self
+            .store
+            .acquire(name, &self.holder, now, now + self.ttl)
+            .await;
+        self.set_held(name, *acquired.as_ref().unwrap_or(&false));
+        acquired
+    }
+
+    pub async fn release(&self, name: &str) -> Result<(), PortError> {
+        self.set_held(name, false);
+        self.store.release(name, &self.holder).await
+    }
+
+    /// Reports `name` in the readiness details before it is first acquired.
+    pub fn register(&self, name: &str) {
+        let mut names = self.names.lock().unwrap();
+        if names.insert(name.to_string()) {
+            self.set_held(name, false);
+        }
+    }
+
+    fn set_held(&self, name: &str, held: bool) {
+        self.held
+            .get_or_create(&LeaseLabel {
+                lease: name.to_string(),
+            })
+            .set(i64::from(held));
+    }
+
+    fn names(&self) -> Vec<String> {
+        let names = self.names.lock().unwrap();
+        names.iter().cloned().collect()
+    }
+}
+
+#[async_trait]
+impl HealthCheck for Leases {
+    fn name(&self) -> &str {
+        "leases"
+    }
+
+    /// Holding a lease or not does not make an instance unready; only a store that cannot be
+    /// read does.
+    async fn check(&self) -> Result<(), PortError> {
+        let now = SystemTime::now();
+        for name in self.names() {
+            self.store.holder(&name, now).await?;
+        }
+        Ok(())
+    }
+
+    async fn details(&self) -> BTreeMap<String, String> {
+        let now = SystemTime::now();
+        let mut details = BTreeMap::from([("instance".to_string(), self.holder.clone())]);
+        for name in self.names() {
+            let holder = match self.store.holder(&name, now).await {
+                Ok(Some(holder)) => holder,
+                Ok(None) => "none".to_string(),
+                Err(_) => "unknown".to_string(),
+            };
+            details.insert(name, holder);
+        }
+        details
+    }
+}
@@ -1 +1,16 @@
+pub mod cache;
+pub mod catalog;
+pub mod clock;
+pub mod db;
+pub mod events;
+pub mod http_client;
+pub mod ids;
+pub mod lease;
+pub mod memory;
+pub mod metrics_server;
+pub mod outbox;
+pub mod resilience;
+pub mod retention;
+pub mod scheduler;
+pub mod shutdown;
 pub mod telemetry;
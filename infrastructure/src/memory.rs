@@ -0,0 +1,312 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::SystemTime,
+};
+
+use application::{
+    context::AppContext,
+    ports::{
+        HistoryFilter, IdempotencyClaim, IdempotencyKey, IdempotencyStore, MessageRecord,
+        MessageRepository, Outbox, OutboxBacklog, OutboxEvent, PortError, StoredResponse,
+    },
+};
+use async_trait::async_trait;
+
+use crate::{
+    catalog::CatalogStore, clock::SystemClock, events::LogEventPublisher, ids::UuidGenerator,
+    lease::LeaseStore,
+};
+
+/// Keeps messages in memory for the lifetime of the process, adding their events to `outbox`.
+pub struct InMemoryMessageRepository {
+    records: RwLock<Vec<MessageRecord>>,
+    outbox: Arc<InMemoryOutbox>,
+}
+
+impl InMemoryMessageRepository {
+    pub fn new(outbox: Arc<InMemoryOutbox>) -> Self {
+        InMemoryMessageRepository {
+            records: RwLock::default(),
+            outbox,
+        }
+    }
+}
+
+#[async_trait]
+impl MessageRepository for InMemoryMessageRepository {
+    async fn save(&self, record: &MessageRecord, events: &[OutboxEvent]) -> Result<(), PortError> {
+        let mut records = self.records.write().unwrap();
+        self.outbox.add(events);
+        records.push(record.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<MessageRecord>, PortError> {
+        Ok(self
+            .records
+            .read()
+            .unwrap()
+            .iter()
+            .find(|record| record.id == id)
+            .cloned())
+    }
+
+    async fn list(&self, filter: &HistoryFilter) -> Result<Vec<MessageRecord>, PortError> {
+        let mut records: Vec<MessageRecord> = self
+            .records
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|record| {
+                filter
+                    .caller
+                    .as_ref()
+                    .is_none_or(|caller| record.caller.as_ref() == Some(caller))
+                    && filter.from.is_none_or(|from| record.created_at >= from)
+                    && filter.to.is_none_or(|to| record.created_at < to)
+                    && filter.after.as_ref().is_none_or(|after| {
+                        (record.created_at, &record.id) < (after.created_at, &after.id)
+                    })
+            })
+            .cloned()
+            .collect();
+
+        records.sort_by(|a, b| (b.created_at, &b.id).cmp(&(a.created_at, &a.id)));
+        records.truncate(filter.limit);
+
+        Ok(records)
+    }
+
+    async fn delete_before(&self, before: SystemTime) -> Result<u64, PortError> {
+        let mut records = self.records.write().unwrap();
+        let count = records.len();
+        records.retain(|record| record.created_at >= before);
+
+        Ok((count - records.len()) as u64)
+    }
+}
+
+struct PendingEvent {
+    event: OutboxEvent,
+    due: SystemTime,
+}
+
+/// Keeps the events of [`InMemoryMessageRepository`] until they are published; they are lost
+/// when the process exits.
+#[derive(Default)]
+pub struct InMemoryOutbox {
+    events: Mutex<Vec<PendingEvent>>,
+}
+
+impl InMemoryOutbox {
+    fn add(&self, events: &[OutboxEvent]) {
+        self.events
+            .lock()
+            .unwrap()
+            .extend(events.iter().map(|event| PendingEvent {
+                event: event.clone(),
+                due: event.created_at,
+            }));
+    }
+}
+
+#[async_trait]
+impl Outbox for InMemoryOutbox {
+    async fn lease(
+        &self,
+        now: SystemTime,
+        lease_until: SystemTime,
+        limit: usize,
+    ) -> Result<Vec<OutboxEvent>, PortError> {
+        let mut events = self.events.lock().unwrap();
+        events.sort_by(|a, b| {
+            (a.event.created_at, &a.event.id).cmp(&(b.event.created_at, &b.event.id))
+        });
+
+        Ok(events
+            .iter_mut()
+            .filter(|pending| pending.due <= now)
+            .take(limit)
+            .map(|pending| {
+                pending.due = lease_until;
+                pending.event.clone()
+            })
+            .collect())
+    }
+
+    async fn delivered(&self, id: &str) -> Result<(), PortError> {
+        self.events
+            .lock()
+            .unwrap()
+            .retain(|pending| pending.event.id != id);
+        Ok(())
+    }
+
+    async fn failed(&self, id: &str, _: &str, retry_at: SystemTime) -> Result<(), PortError> {
+        if let Some(pending) = self
+            .events
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|pending| pending.event.id == id)
+        {
+            pending.event.attempts += 1;
+            pending.due = retry_at;
+        }
+        Ok(())
+    }
+
+    async fn backlog(&self) -> Result<OutboxBacklog, PortError> {
+        let events = self.events.lock().unwrap();
+        Ok(OutboxBacklog {
+            pending: events.len() as u64,
+            oldest: events.iter().map(|pending| pending.event.created_at).min(),
+        })
+    }
+}
+
+struct IdempotencyEntry {
+    fingerprint: String,
+    response: Option<StoredResponse>,
+    expires_at: SystemTime,
+}
+
+/// Keeps idempotent responses in memory, so retries are only recognized by the process that
+/// served the original request.
+#[derive(Default)]
+pub struct InMemoryIdempotencyStore {
+    entries: Mutex<HashMap<IdempotencyKey, IdempotencyEntry>>,
+}
+
+#[async_trait]
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    async fn claim(
+        &self,
+        key: &IdempotencyKey,
+        fingerprint: &str,
+        now: SystemTime,
+        expires_at: SystemTime,
+    ) -> Result<IdempotencyClaim, PortError> {
+        let mut entries = self.entries.lock().unwrap();
+
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > now => Ok(if entry.fingerprint != fingerprint {
+                IdempotencyClaim::Mismatch
+            } else if let Some(response) = &entry.response {
+                IdempotencyClaim::Completed(response.clone())
+            } else {
+                IdempotencyClaim::InFlight
+            }),
+            _ => {
+                entries.insert(
+                    key.clone(),
+                    IdempotencyEntry {
+                        fingerprint: fingerprint.to_string(),
+                        response: None,
+                        expires_at,
+                    },
+                );
+                Ok(IdempotencyClaim::Claimed)
+            }
+        }
+    }
+
+    async fn complete(
+        &self,
+        key: &IdempotencyKey,
+        response: &StoredResponse,
+        expires_at: SystemTime,
+    ) -> Result<(), PortError> {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key) {
+            entry.response = Some(response.clone());
+            entry.expires_at = expires_at;
+        }
+        Ok(())
+    }
+
+    async fn release(&self, key: &IdempotencyKey) -> Result<(), PortError> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries
+            .get(key)
+            .is_some_and(|entry| entry.response.is_none())
+        {
+            entries.remove(key);
+        }
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: SystemTime) -> Result<u64, PortError> {
+        let mut entries = self.entries.lock().unwrap();
+        let count = entries.len();
+        entries.retain(|_, entry| entry.expires_at > now);
+
+        Ok((count - entries.len()) as u64)
+    }
+}
+
+/// Keeps leases in memory, so they are only shared by the users of this store, e.g. the
+/// schedulers of a test.
+#[derive(Default)]
+pub struct InMemoryLeaseStore {
+    leases: Mutex<HashMap<String, (String, SystemTime)>>,
+}
+
+#[async_trait]
+impl LeaseStore for InMemoryLeaseStore {
+    async fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        now: SystemTime,
+        expires_at: SystemTime,
+    ) -> Result<bool, PortError> {
+        let mut leases = self.leases.lock().unwrap();
+
+        match leases.get(name) {
+            Some((current, until)) if current != holder && *until > now => Ok(false),
+            _ => {
+                leases.insert(name.to_string(), (holder.to_string(), expires_at));
+                Ok(true)
+            }
+        }
+    }
+
+    async fn release(&self, name: &str, holder: &str) -> Result<(), PortError> {
+        let mut leases = self.leases.lock().unwrap();
+        if leases
+            .get(name)
+            .is_some_and(|(current, _)| current == holder)
+        {
+            leases.remove(name);
+        }
+        Ok(())
+    }
+
+    async fn holder(&self, name: &str, now: SystemTime) -> Result<Option<String>, PortError> {
+        Ok(self
+            .leases
+            .lock()
+            .unwrap()
+            .get(name)
+            .filter(|(_, until)| *until > now)
+            .map(|(holder, _)| holder.clone()))
+    }
+}
+
+/// A context that needs no external service: messages are kept in memory, events are only
+/// logged and replies come from the built-in catalog.
+pub fn context() -> AppContext {
+    let outbox = Arc::new(InMemoryOutbox::default());
+
+    AppContext {
+        messages: Arc::new(InMemoryMessageRepository::new(outbox.clone())),
+        events: Arc::new(LogEventPublisher),
+        outbox,
+        clock: Arc::new(SystemClock),
+        ids: Arc::new(UuidGenerator),
+        idempotency: Arc::new(InMemoryIdempotencyStore::default()),
+        catalog: Arc::new(CatalogStore::default()),
+        readiness: Vec::new(),
+    }
+}
@@ -0,0 +1,75 @@
+use std::{convert::Infallible, net::TcpListener, sync::Arc};
+
+use http::{header, Method, Request, Response, StatusCode};
+use http_body_util::Full;
+use hyper::{
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use prometheus_client::{encoding::text::encode, registry::Registry};
+use tracing::log;
+
+use crate::shutdown::Shutdown;
+
+/// Serves `GET /metrics` for the binaries without an HTTP server of their own, such as the
+/// standalone gRPC gateway and the worker, until `shutdown` is triggered.
+pub async fn serve(
+    listener: TcpListener,
+    registry: Registry,
+    shutdown: Shutdown,
+) -> eyre::Result<()> {
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    let registry = Arc::new(registry);
+
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.wait() => return Ok(()),
+        };
+
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| {
+                let response = metrics(&req, &registry);
+                async move { Ok::<_, Infallible>(response) }
+            });
+
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                log::debug!("metrics connection failed: {err}");
+            }
+        });
+    }
+}
+
+fn metrics(req: &Request<Incoming>, registry: &Registry) -> Response<Full<Bytes>> {
+    if req.method() != Method::GET || req.uri().path() != "/metrics" {
+        return status(StatusCode::NOT_FOUND);
+    }
+
+    let mut body = String::new();
+    if let Err(err) = encode(&mut body, registry) {
+        log::error!("failed to encode metrics: {err}");
+        return status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static(
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        ),
+    );
+    response
+}
+
+fn status(status: StatusCode) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::default());
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,124 @@
+use std::{sync::Arc, time::Duration};
+
+use application::{
+    context::AppContext,
+    outbox::{self, RelayReport, RelaySettings},
+    ports::PortError,
+};
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+    registry::Registry,
+};
+
+use crate::scheduler::{Job, Schedule};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct EventLabel {
+    pub event: &'static str,
+}
+
+#[derive(Clone)]
+struct OutboxMetrics {
+    published: Family<EventLabel, Counter>,
+    failed: Family<EventLabel, Counter>,
+    pending: Gauge,
+    lag: Gauge,
+}
+
+impl OutboxMetrics {
+    fn new(registry: &mut Registry) -> Self {
+        let metrics = OutboxMetrics {
+            published: Family::default(),
+            failed: Family::default(),
+            pending: Gauge::default(),
+            lag: Gauge::default(),
+        };
+
+        registry.register(
+            "outbox_published_events",
+            "Number of outbox events published",
+            metrics.published.clone(),
+        );
+        registry.register(
+            "outbox_failed_attempts",
+            "Number of failed attempts to publish an outbox event",
+            metrics.failed.clone(),
+        );
+        registry.register(
+            "outbox_pending_events",
+            "Number of events waiting in the outbox",
+            metrics.pending.clone(),
+        );
+        registry.register(
+            "outbox_lag_ms",
+            "Age of the oldest event waiting in the outbox",
+            metrics.lag.clone(),
+        );
+
+        metrics
+    }
+
+    fn record(&self, report: &RelayReport) {
+        for &event in &report.published {
+            self.published.get_or_create(&EventLabel { event }).inc();
+        }
+        for &event in &report.failed {
+            self.failed.get_or_create(&EventLabel { event }).inc();
+        }
+    }
+}
+
+/// Publishes the events of the outbox, as a scheduled [`Job`] polling it.
+pub struct OutboxRelay {
+    settings: RelaySettings,
+    metrics: OutboxMetrics,
+}
+
+impl OutboxRelay {
+    pub fn new(settings: RelaySettings, registry: &mut Registry) -> Self {
+        OutboxRelay {
+            settings,
+            metrics: OutboxMetrics::new(registry),
+        }
+    }
+
+    /// Publishes the due events, batch after batch, then measures what is left in the outbox.
+    pub async fn run_once(&self, context: &AppContext) -> Result<(), PortError> {
+        loop {
+            let report = outbox::relay(context, &self.settings).await?;
+            self.metrics.record(&report);
+            if report.len() < self.settings.batch_size {
+                break;
+            }
+        }
+
+        let backlog = context.outbox.backlog().await?;
+        let lag = backlog.oldest.map_or(Duration::ZERO, |oldest| {
+            context
+                .clock
+                .now()
+                .duration_since(oldest)
+                .unwrap_or_default()
+        });
+        self.metrics
+            .pending
+            .set(backlog.pending.try_into().unwrap_or(i64::MAX));
+        self.metrics
+            .lag
+            .set(lag.as_millis().try_into().unwrap_or(i64::MAX));
+
+        Ok(())
+    }
+
+    /// The `outbox.relay` job, running the relay every `interval`.
+    pub fn job(self, context: AppContext, interval: Duration) -> Job {
+        let relay = Arc::new(self);
+
+        Job::new("outbox.relay", Schedule::Every(interval), move || {
+            let relay = relay.clone();
+            let context = context.clone();
+            async move { Ok(relay.run_once(&context).await?) }
+        })
+    }
+}
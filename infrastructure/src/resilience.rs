@@ -0,0 +1,395 @@
+//! Protections against a failing or slow dependency: a [`CircuitBreaker`] stops calling it
+//! after repeated failures, and a [`Bulkhead`] caps the calls waiting on it, so it cannot tie
+//! up every worker of the service.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+    registry::Registry,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through; consecutive failures are counted.
+    Closed,
+    /// Calls are rejected until the open duration elapses.
+    Open,
+    /// A few trial calls go through; they close the circuit if they all succeed.
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerSettings {
+    /// Consecutive failures opening the circuit.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before letting trial calls through.
+    pub open_duration: Duration,
+    /// Trial calls let through at once while half-open, all of which must succeed to close
+    /// the circuit.
+    pub half_open_calls: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct BulkheadSettings {
+    pub max_concurrent_calls: usize,
+    /// How long a call waits for a free slot before being rejected.
+    pub max_wait: Duration,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CircuitLabel {
+    pub dependency: String,
+    pub state: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DependencyLabel {
+    pub dependency: String,
+}
+
+/// Metrics of the circuit breakers and bulkheads, labelled by dependency.
+#[derive(Clone)]
+pub struct ResilienceMetrics {
+    circuit_state: Family<CircuitLabel, Gauge>,
+    circuit_transitions: Family<CircuitLabel, Counter>,
+    bulkhead_in_flight: Family<DependencyLabel, Gauge>,
+    bulkhead_rejections: Family<DependencyLabel, Counter>,
+}
+
+impl ResilienceMetrics {
+    pub fn new(registry: &mut Registry) -> Self {
+        let metrics = ResilienceMetrics {
+            circuit_state: Family::default(),
+            circuit_transitions: Family::default(),
+            bulkhead_in_flight: Family::default(),
+            bulkhead_rejections: Family::default(),
+        };
+
+        registry.register(
+            "circuit_breaker_state",
+            "1 for the current state of the circuit, 0 for the others",
+            metrics.circuit_state.clone(),
+        );
+        registry.register(
+            "circuit_breaker_transitions",
+            "Number of times the circuit entered the state",
+            metrics.circuit_transitions.clone(),
+        );
+        registry.register(
+            "bulkhead_in_flight_calls",
+            "Number of calls holding a slot of the bulkhead",
+            metrics.bulkhead_in_flight.clone(),
+        );
+        registry.register(
+            "bulkhead_rejections",
+            "Number of calls rejected by a full bulkhead",
+            metrics.bulkhead_rejections.clone(),
+        );
+
+        metrics
+    }
+}
+
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// `round` tells the trial calls of successive half-open states apart.
+    HalfOpen {
+        round: u64,
+        trials: u32,
+        successes: u32,
+    },
+}
+
+impl State {
+    fn circuit(&self) -> CircuitState {
+        match self {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { .. } => CircuitState::Open,
+            State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+}
+
+/// Circuit breaker of one dependency. Every call takes a [`CallPermit`] and reports through it
+/// whether the dependency failed.
+pub struct CircuitBreaker {
+    dependency: String,
+    settings: CircuitBreakerSettings,
+    state: Mutex<State>,
+    rounds: AtomicU64,
+    metrics: ResilienceMetrics,
+}
+
+impl CircuitBreaker {
+    pub fn new(
+        dependency: impl Into<String>,
+        settings: CircuitBreakerSettings,
+        metrics: ResilienceMetrics,
+    ) -> Self {
+        let breaker = CircuitBreaker {
+            dependency: dependency.into(),
+            settings,
+            state: Mutex::new(State::Closed { failures: 0 }),
+            rounds: AtomicU64::new(0),
+            metrics,
+        };
+        breaker.export(CircuitState::Closed);
+        breaker
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.state.lock().unwrap().circuit()
+    }
+
+    /// Permission to call the dependency; `None` while the circuit is open, or half-open with
+    /// every trial call in flight.
+    pub fn try_acquire(&self) -> Option<CallPermit<'_>> {
+        let mut state = self.state.lock().unwrap();
+
+        let trial = match &mut *state {
+            State::Closed { .. } => None,
+            State::Open { until } if Instant::now() < *until => return None,
+            State::Open { .. } => {
+                let round = self.rounds.fetch_add(1, Ordering::Relaxed);
+                self.transition(
+                    &mut state,
+                    State::HalfOpen {
+                        round,
+                        trials: 1,
+                        successes: 0,
+                    },
+                );
+                Some(round)
+            }
+            State::HalfOpen { round, trials, .. } if *trials < self.settings.half_open_calls => {
+                *trials += 1;
+                Some(*round)
+            }
+            State::HalfOpen { .. } => return None,
+        };
+
+        Some(CallPermit {
+            breaker: self,
+            trial,
+            done: false,
+        })
+    }
+
+    fn record(&self, trial: Option<u64>, success: bool) {
+        let mut state = self.state.lock().unwrap();
+
+        // results of calls let through in an earlier state do not count
+        match (&mut *state, trial) {
+            (State::Closed { failures }, None) => {
+                if success {
+                    *failures = 0;
+                } else {
+                    *failures += 1;
+                    if *failures >= self.settings.failure_threshold {
+                        self.open(&mut state);
+                    }
+                }
+            }
+            (
+                State::HalfOpen {
+                    round,
+                    trials,
+                    successes,
+                },
+                Some(trial),
+            ) if *round == trial => {
+                *trials -= 1;
+                if !success {
+                    self.open(&mut state);
+                } else {
+                    *successes += 1;
+                    if *successes >= self.settings.half_open_calls {
+                        self.transition(&mut state, State::Closed { failures: 0 });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Gives back the slot of a trial call that ended without a result, e.g. cancelled.
+    fn release(&self, trial: u64) {
+        if let State::HalfOpen { round, trials, .. } = &mut *self.state.lock().unwrap() {
+            if *round == trial {
+                *trials -= 1;
+            }
+        }
+    }
+
+    fn open(&self, state: &mut State) {
+        let until = Instant::now() + self.settings.open_duration;
+        self.transition(state, State::Open { until });
+    }
+
+    fn transition(&self, state: &mut State, next: State) {
+        let (from, to) = (state.circuit(), next.circuit());
+        *state = next;
+
+        match to {
+            CircuitState::Open => log::warn!(
+                "circuit of {} opened for {:?}",
+                self.dependency,
+                self.settings.open_duration
+            ),
+            _ => log::info!(
+                "circuit of {} went from {} to {}",
+                self.dependency,
+                from.as_str(),
+                to.as_str()
+            ),
+        }
+        self.metrics
+            .circuit_transitions
+            .get_or_create(&CircuitLabel {
+                dependency: self.dependency.clone(),
+                state: to.as_str(),
+            })
+            .inc();
+        self.export(to);
+    }
+
+    fn export(&self, current: CircuitState) {
+        for state in [
+            CircuitState::Closed,
+            CircuitState::Open,
+            CircuitState::HalfOpen,
+        ] {
+            self.metrics
+                .circuit_state
+                .get_or_create(&CircuitLabel {
+                    dependency: self.dependency.clone(),
+                    state: state.as_str(),
+                })
+                .set(i64::from(state == current));
+        }
+    }
+}
+
+/// A call let through by a [`CircuitBreaker`]. Dropping it without reporting a result leaves
+/// the circuit as it is.
+pub struct CallPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    /// Round of the half-open state the call is a trial of.
+    trial: Option<u64>,
+    done: bool,
+}
+
+impl CallPermit<'_> {
+    pub fn success(mut self) {
+        self.done = true;
+        self.breaker.record(self.trial, true);
+    }
+
+    pub fn failure(mut self) {
+        self.done = true;
+        self.breaker.record(self.trial, false);
+    }
+}
+
+impl Drop for CallPermit<'_> {
+    fn drop(&mut self) {
+        if let (Some(trial), false) = (self.trial, self.done) {
+            self.breaker.release(trial);
+        }
+    }
+}
+
+/// Limit of the concurrent calls to one dependency.
+pub struct Bulkhead {
+    dependency: DependencyLabel,
+    slots: Arc<Semaphore>,
+    max_wait: Duration,
+    metrics: ResilienceMetrics,
+}
+
+impl Bulkhead {
+    pub fn new(
+        dependency: impl Into<String>,
+        settings: &BulkheadSettings,
+        metrics: ResilienceMetrics,
+    ) -> Self {
+        Bulkhead {
+            dependency: DependencyLabel {
+                dependency: dependency.into(),
+            },
+            slots: Arc::new(Semaphore::new(settings.max_concurrent_calls)),
+            max_wait: settings.max_wait,
+            metrics,
+        }
+    }
+
+    /// Waits up to the max wait, and at most `budget`, for a free slot. `None` when none
+    /// freed up in time.
+    pub async fn acquire(&self, budget: Duration) -> Option<BulkheadPermit> {
+        let slot = match self.slots.clone().try_acquire_owned() {
+            Ok(slot) => Some(slot),
+            Err(_) => tokio::time::timeout(
+                self.max_wait.min(budget),
+                self.slots.clone().acquire_owned(),
+            )
+            .await
+            .ok()
+            .and_then(Result::ok),
+        };
+
+        let Some(slot) = slot else {
+            self.metrics
+                .bulkhead_rejections
+                .get_or_create(&self.dependency)
+                .inc();
+            return None;
+        };
+
+        let in_flight = self
+            .metrics
+            .bulkhead_in_flight
+            .get_or_create(&self.dependency)
+            .clone();
+        in_flight.inc();
+        Some(BulkheadPermit {
+            _slot: slot,
+            in_flight,
+        })
+    }
+}
+
+/// A slot of a [`Bulkhead`], freed when dropped.
+pub struct BulkheadPermit {
+    _slot: OwnedSemaphorePermit,
+    in_flight: Gauge,
+}
+
+impl Drop for BulkheadPermit {
+    fn drop(&mut self) {
+        self.in_flight.dec();
+    }
+}
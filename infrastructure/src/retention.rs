@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use application::{context::AppContext, history, idempotency};
+
+use crate::scheduler::{Job, Schedule};
+
+/// The `retention` job, deleting the expired idempotent responses and, when `history` is set,
+/// the message history older than it.
+pub fn job(context: AppContext, history: Option<Duration>, schedule: Schedule) -> Job {
+    Job::new("retention", schedule, move || {
+        let context = context.clone();
+        async move { purge(&context, history).await }
+    })
+}
+
+async fn purge(context: &AppContext, history: Option<Duration>) -> eyre::Result<()> {
+    // both are purged, even when one of them fails
+    let messages = match history {
+        Some(retention) => history::purge(context, retention).await,
+        None => Ok(0),
+    };
+    let responses = idempotency::purge(context).await;
+
+    match messages? {
+        0 => {}
+        deleted => log::info!("deleted {deleted} messages past the history retention"),
+    }
+    match responses? {
+        0 => {}
+        deleted => log::debug!("deleted {deleted} expired idempotent responses"),
+    }
+
+    Ok(())
+}
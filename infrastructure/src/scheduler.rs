@@ -0,0 +1,333 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, histogram::Histogram},
+    registry::Registry,
+};
+use tracing::{field::Empty, Instrument};
+
+use crate::{lease::Leases, shutdown::Shutdown};
+
+/// When a job runs.
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    /// Right away, then every period counted from the end of the previous run.
+    Every(Duration),
+    /// At the UTC times matched by a cron expression with a seconds field, e.g. `0 0 3 * * *`.
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    pub fn cron(expression: &str) -> Result<Self, cron::error::Error> {
+        cron::Schedule::from_str(expression).map(|schedule| Schedule::Cron(Box::new(schedule)))
+    }
+
+    /// Time of the run following one at `after`. `None` when the schedule has no more runs.
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        match self {
+            Schedule::Every(period) => Some(after + *period),
+            Schedule::Cron(schedule) => schedule
+                .after(&DateTime::<Utc>::from(after))
+                .next()
+                .map(SystemTime::from),
+        }
+    }
+
+    /// Time to wait for the next run.
+    fn delay(&self, first: bool) -> Option<Duration> {
+        match self {
+            Schedule::Every(_) if first => Some(Duration::ZERO),
+            Schedule::Every(period) => Some(*period),
+            Schedule::Cron(_) => {
+                let now = SystemTime::now();
+                let next = self.next_after(now)?;
+                Some(next.duration_since(now).unwrap_or_default())
+            }
+        }
+    }
+}
+
+type RunFn = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = eyre::Result<()>> + Send>> + Send + Sync>;
+
+/// Periodic work run by a [`Scheduler`].
+pub struct Job {
+    name: String,
+    schedule: Schedule,
+    timeout: Option<Duration>,
+    jitter: Duration,
+    singleton: bool,
+    run: RunFn,
+}
+
+impl Job {
+    pub fn new<F, Fut>(name: impl Into<String>, schedule: Schedule, run: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = eyre::Result<()>> + Send + 'static,
+    {
+        Job {
+            name: name.into(),
+            schedule,
+            timeout: None,
+            jitter: Duration::ZERO,
+            singleton: false,
+            run: Arc::new(move || Box::pin(run())),
+        }
+    }
+
+    /// Cancels runs lasting longer than `timeout`, counting them as timed out.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Delays every run by a random time up to `jitter`, so replicas started together do not
+    /// all run the job at once.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Runs the job on one instance at a time: a run first takes the lease named after the
+    /// job, renews it until it ends, and is skipped while another instance holds it. Runs on
+    /// every instance when the scheduler has no [`Leases`].
+    pub fn singleton(mut self) -> Self {
+        self.singleton = true;
+        self
+    }
+}
+
+/// Labels of a job run. `status` is `success`, `failure` or `timeout`.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct JobLabel {
+    pub job: String,
+    pub status: &'static str,
+}
+
+#[derive(Clone)]
+struct SchedulerMetrics {
+    runs: Family<JobLabel, Counter>,
+    duration: Family<JobLabel, Histogram>,
+}
+
+impl SchedulerMetrics {
+    fn new(registry: &mut Registry) -> Self {
+        let metrics = SchedulerMetrics {
+            runs: Family::default(),
+            duration: Family::new_with_constructor(|| {
+                let buckets = [
+                    1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 10000.0, 30000.0, 60000.0,
+                    300000.0,
+                ];
+                Histogram::new(buckets.into_iter())
+            }),
+        };
+
+        registry.register(
+            "scheduler_job_runs",
+            "Number of job runs",
+            metrics.runs.clone(),
+        );
+        registry.register(
+            "scheduler_job_duration_ms",
+            "Job run duration",
+            metrics.duration.clone(),
+        );
+
+        metrics
+    }
+}
+
+/// Runs jobs on the tokio runtime until shutdown. Each job runs in its own task, one run at a
+/// time: a run that outlasts its period delays the next one instead of overlapping it.
+pub struct Scheduler {
+    jobs: Vec<Job>,
+    metrics: SchedulerMetrics,
+    leases: Option<Arc<Leases>>,
+}
+
+impl Scheduler {
+    pub fn new(registry: &mut Registry) -> Self {
+        Scheduler {
+            jobs: Vec::new(),
+            metrics: SchedulerMetrics::new(registry),
+            leases: None,
+        }
+    }
+
+    /// Coordinates the singleton jobs with the other instances through `leases`.
+    pub fn with_leases(mut self, leases: Arc<Leases>) -> Self {
+        self.leases = Some(leases);
+        self
+    }
+
+    pub fn add(&mut self, job: Job) {
+        self.jobs.push(job);
+    }
+
+    /// Runs the jobs until `shutdown` is triggered, which cancels the runs in progress.
+    pub fn spawn(self, shutdown: Shutdown) -> tokio::task::JoinHandle<()> {
+        let tasks: Vec<_> = self
+            .jobs
+            .into_iter()
+            .map(|job| {
+                let metrics = self.metrics.clone();
+                let leases = self.leases.clone().filter(|_| job.singleton);
+                if let Some(leases) = &leases {
+                    leases.register(&job.name);
+                }
+                let shutdown = shutdown.clone();
+                tokio::spawn(schedule(job, metrics, leases, shutdown))
+            })
+            .collect();
+
+        tokio::spawn(async move {
+            for task in tasks {
+                if let Err(err) = task.await {
+                    log::error!("scheduled job panicked: {err}");
+                }
+            }
+        })
+    }
+}
+
+async fn schedule(
+    job: Job,
+    metrics: SchedulerMetrics,
+    leases: Option<Arc<Leases>>,
+    shutdown: Shutdown,
+) {
+    scheduled(&job, &metrics, leases.as_deref(), &shutdown).await;
+
+    // lets another instance take over without waiting for the lease to expire
+    if let Some(leases) = leases {
+        if let Err(err) = leases.release(&job.name).await {
+            log::warn!("failed to release the lease of job {}: {err}", job.name);
+        }
+    }
+}
+
+async fn scheduled(
+    job: &Job,
+    metrics: &SchedulerMetrics,
+    leases: Option<&Leases>,
+    shutdown: &Shutdown,
+) {
+    let mut first = true;
+
+    loop {
+        let Some(delay) = job.schedule.delay(first) else {
+            log::warn!("job {} has no upcoming run", job.name);
+            return;
+        };
+        first = false;
+
+        let jitter = job.jitter.mul_f64(fastrand::f64());
+        tokio::select! {
+            _ = tokio::time::sleep(delay + jitter) => {}
+            _ = shutdown.wait() => return,
+        }
+
+        tokio::select! {
+            _ = run_leased(job, metrics, leases) => {}
+            _ = shutdown.wait() => {
+                log::info!("job {} cancelled by shutdown", job.name);
+                return;
+            }
+        }
+    }
+}
+
+/// Runs `job` if this instance gets its lease, renewing the lease until the run ends. The run
+/// is cancelled if another instance takes the lease over meanwhile.
+async fn run_leased(job: &Job, metrics: &SchedulerMetrics, leases: Option<&Leases>) {
+    let Some(leases) = leases else {
+        return run(job, metrics).await;
+    };
+
+    match leases.acquire(&job.name).await {
+        Ok(true) => {}
+        Ok(false) => {
+            log::debug!("job {} skipped: another instance holds its lease", job.name);
+            return;
+        }
+        Err(err) => {
+            log::warn!("job {} skipped: {err}", job.name);
+            return;
+        }
+    }
+
+    let every = (leases.ttl() / 3).max(Duration::from_millis(1));
+    let mut renewal = tokio::time::interval_at(tokio::time::Instant::now() + every, every);
+    let run = run(job, metrics);
+    tokio::pin!(run);
+
+    loop {
+        tokio::select! {
+            _ = &mut run => return,
+            _ = renewal.tick() => match leases.acquire(&job.name).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    log::warn!("job {} cancelled: another instance took its lease over", job.name);
+                    return;
+                }
+                // the lease is still held until it expires
+                Err(err) => log::warn!("failed to renew the lease of job {}: {err}", job.name),
+            },
+        }
+    }
+}
+
+async fn run(job: &Job, metrics: &SchedulerMetrics) {
+    let span = tracing::info_span!(
+        "infrastructure.scheduler.job",
+        otel.name = format!("job {}", job.name),
+        otel.status_code = Empty,
+        job = job.name,
+        status = Empty,
+    );
+
+    let started = Instant::now();
+    let run = (job.run)().instrument(span.clone());
+    // `None` when the run timed out
+    let result = match job.timeout {
+        Some(timeout) => tokio::time::timeout(timeout, run).await.ok(),
+        None => Some(run.await),
+    };
+    let elapsed = started.elapsed();
+
+    let status = match result {
+        Some(Ok(())) => "success",
+        Some(Err(err)) => {
+            tracing::error!(parent: &span, "job {} failed: {err}", job.name);
+            "failure"
+        }
+        None => {
+            tracing::error!(parent: &span, "job {} timed out after {elapsed:?}", job.name);
+            "timeout"
+        }
+    };
+    span.record("status", status);
+    span.record(
+        "otel.status_code",
+        if status == "success" { "OK" } else { "ERROR" },
+    );
+
+    let label = JobLabel {
+        job: job.name.clone(),
+        status,
+    };
+    metrics.runs.get_or_create(&label).inc();
+    metrics
+        .duration
+        .get_or_create(&label)
+        .observe(elapsed.as_secs_f64() * 1000.0);
+}
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// Server-wide shutdown signal. Long-lived tasks, such as WebSocket sessions, wait on it to
+/// finish their work before the server stops accepting connections.
+#[derive(Clone)]
+pub struct Shutdown {
+    triggered: Arc<watch::Sender<bool>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown {
+            triggered: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.triggered.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.triggered.borrow()
+    }
+
+    /// Resolves once [`Shutdown::trigger`] has been called.
+    pub async fn wait(&self) {
+        let mut triggered = self.triggered.subscribe();
+        // the sender lives as long as `self`, so this can only resolve with `true`
+        let _ = triggered.wait_for(|triggered| *triggered).await;
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown::new()
+    }
+}
+
+/// Resolves on `SIGINT` or, on Unix, `SIGTERM`.
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(err) => tracing::error!("failed to listen for SIGTERM: {err}"),
+        }
+    }
+
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        tracing::error!("failed to listen for SIGINT: {err}");
+        std::future::pending::<()>().await;
+    }
+}
@@ -1,9 +1,14 @@
-use opentelemetry::{global, trace::TracerProvider};
+use opentelemetry::{
+    global,
+    propagation::{Extractor, Injector},
+    trace::{TraceContextExt, TracerProvider},
+};
 use opentelemetry_otlp::SpanExporter;
 use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
-use tracing::subscriber::set_global_default;
+use tracing::{subscriber::set_global_default, Span};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::{
     filter::filter_fn, fmt::format::FmtSpan, prelude::__tracing_subscriber_SubscriberExt,
     EnvFilter, Layer, Registry,
@@ -90,3 +95,31 @@ pub fn setup(settings: Settings) -> TelemetryGuard {
         tracer_provider: provider,
     }
 }
+
+/// Makes `span` a child of the remote span whose W3C trace context is carried by `carrier`
+/// (e.g. the `traceparent` header of an incoming request). When the carrier holds no context,
+/// the span stays a root.
+pub fn set_remote_parent(span: &Span, carrier: &dyn Extractor) {
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(carrier));
+    if let Err(err) = span.set_parent(parent) {
+        log::debug!("failed to set the remote parent of a span: {err}");
+    }
+}
+
+/// Writes the W3C trace context of `span` into `carrier` (e.g. the headers of an outgoing
+/// message), so the receiver can continue the trace.
+pub fn inject_context(span: &Span, carrier: &mut dyn Injector) {
+    let context = span.context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, carrier));
+}
+
+/// Id of the trace `span` belongs to, as 32 hex digits. `None` when the span is not exported
+/// through OpenTelemetry, e.g. when tracing is not set up.
+pub fn trace_id(span: &Span) -> Option<String> {
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
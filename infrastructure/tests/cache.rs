@@ -0,0 +1,154 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use infrastructure::cache::{Cache, CacheAdmin, CacheMetrics, CacheSettings, Eviction};
+use prometheus_client::{encoding::text::encode, registry::Registry};
+
+fn settings(max_entries: usize, eviction: Eviction) -> CacheSettings {
+    CacheSettings {
+        max_entries,
+        ttl: Duration::from_secs(60),
+        stale_while_revalidate: Duration::ZERO,
+        eviction,
+    }
+}
+
+fn cache(settings: CacheSettings, registry: &mut Registry) -> Arc<Cache<String, u32>> {
+    Arc::new(Cache::new("numbers", settings, CacheMetrics::new(registry)))
+}
+
+fn metrics(registry: &Registry) -> String {
+    let mut metrics = String::new();
+    encode(&mut metrics, registry).unwrap();
+    metrics
+}
+
+fn keys(cache: &Cache<String, u32>) -> Vec<String> {
+    cache.entries().into_iter().map(|entry| entry.key).collect()
+}
+
+#[test]
+fn least_recently_or_frequently_used_entries_are_evicted() {
+    let mut registry = Registry::default();
+    let lru = cache(settings(2, Eviction::Lru), &mut registry);
+    let lfu = Cache::new(
+        "lfu",
+        settings(2, Eviction::Lfu),
+        CacheMetrics::new(&mut Registry::default()),
+    );
+
+    for cache in [lru.as_ref(), &lfu] {
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        cache.get(&"a".to_string());
+        cache.get(&"a".to_string());
+        cache.get(&"b".to_string());
+        cache.insert("c".to_string(), 3);
+    }
+
+    // `a` was used before `b`, but more often
+    assert_eq!(vec!["b", "c"], keys(&lru));
+    assert_eq!(vec!["c", "a"], keys(&lfu));
+    assert_eq!(None, lru.get(&"a".to_string()));
+    let metrics = metrics(&registry);
+    assert!(metrics.contains("cache_hits_total{cache=\"numbers\"} 3"));
+    assert!(metrics.contains("cache_misses_total{cache=\"numbers\"} 1"));
+    assert!(metrics.contains("cache_evictions_total{cache=\"numbers\",reason=\"capacity\"} 1"));
+    assert!(metrics.contains("cache_entries{cache=\"numbers\"} 2"));
+}
+
+#[tokio::test]
+async fn entries_expire_after_their_ttl() {
+    let mut registry = Registry::default();
+    let cache = cache(
+        CacheSettings {
+            ttl: Duration::from_millis(50),
+            ..settings(8, Eviction::Lru)
+        },
+        &mut registry,
+    );
+
+    cache.insert("a".to_string(), 1);
+    assert_eq!(Some(1), cache.get(&"a".to_string()));
+    tokio::time::sleep(Duration::from_millis(80)).await;
+
+    assert_eq!(None, cache.get(&"a".to_string()));
+    assert!(cache.entries().is_empty());
+    assert!(metrics(&registry)
+        .contains("cache_evictions_total{cache=\"numbers\",reason=\"expired\"} 1"));
+}
+
+#[tokio::test]
+async fn concurrent_misses_share_one_load() {
+    let cache = cache(settings(8, Eviction::Lru), &mut Registry::default());
+    let loads = Arc::new(AtomicUsize::new(0));
+    let load = |value: Result<u32, String>| {
+        let loads = loads.clone();
+        move || async move {
+            loads.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            value
+        }
+    };
+
+    let (first, second, third) = tokio::join!(
+        cache.get_with("a".to_string(), load(Ok(1))),
+        cache.get_with("a".to_string(), load(Ok(2))),
+        cache.get_with("a".to_string(), load(Ok(3))),
+    );
+    assert_eq!((1, 1, 1), (first.unwrap(), second.unwrap(), third.unwrap()));
+    assert_eq!(1, loads.load(Ordering::SeqCst));
+
+    // the waiting lookups get the error of a failed load, which is not cached
+    let (first, second) = tokio::join!(
+        cache.get_with("b".to_string(), load(Err("unavailable".to_string()))),
+        cache.get_with("b".to_string(), load(Ok(2))),
+    );
+    assert_eq!("unavailable", first.unwrap_err().as_str());
+    assert_eq!("unavailable", second.unwrap_err().as_str());
+    assert_eq!(2, loads.load(Ordering::SeqCst));
+    assert_eq!(None, cache.get(&"b".to_string()));
+}
+
+#[tokio::test]
+async fn stale_entries_are_served_while_revalidated() {
+    let cache = cache(
+        CacheSettings {
+            ttl: Duration::from_millis(50),
+            stale_while_revalidate: Duration::from_secs(60),
+            ..settings(8, Eviction::Lru)
+        },
+        &mut Registry::default(),
+    );
+    let load = |value: u32| move || async move { Ok::<_, String>(value) };
+
+    assert_eq!(1, cache.get_with("a".to_string(), load(1)).await.unwrap());
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert_eq!("stale", cache.entries()[0].state);
+
+    assert_eq!(1, cache.get_with("a".to_string(), load(2)).await.unwrap());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    assert_eq!(2, cache.get_with("a".to_string(), load(3)).await.unwrap());
+    assert_eq!("fresh", cache.entries()[0].state);
+}
+
+#[test]
+fn entries_can_be_inspected_and_purged() {
+    let cache = cache(settings(8, Eviction::Lru), &mut Registry::default());
+    cache.insert("a".to_string(), 1);
+    cache.insert("b".to_string(), 2);
+    cache.insert("c".to_string(), 3);
+
+    assert_eq!("numbers", cache.name());
+    assert!(cache.purge_key("b"));
+    assert!(!cache.purge_key("b"));
+    assert_eq!(vec!["a", "c"], keys(&cache));
+    assert_eq!(2, cache.purge());
+    assert!(cache.entries().is_empty());
+}
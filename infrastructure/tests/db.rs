@@ -0,0 +1,625 @@
+use std::{
+    net::TcpListener,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime},
+};
+
+use application::ports::{
+    DomainEvent, HealthCheck, HistoryCursor, HistoryFilter, IdempotencyClaim, IdempotencyKey,
+    IdempotencyStore, MessageRecord, MessageRepository, Outbox, OutboxBacklog, OutboxEvent,
+    StoredResponse,
+};
+use infrastructure::{
+    db::{
+        sqlite::{self, SqliteDatabase},
+        Database, Settings,
+    },
+    lease::LeaseStore,
+};
+use prometheus_client::{encoding::text::encode, registry::Registry};
+use tempfile::TempDir;
+
+/// URL of the PostgreSQL server the tests run against: `DATABASE_URL` when set, otherwise a
+/// throwaway cluster started with the PostgreSQL binaries on the `PATH`. `None` when neither is
+/// available, in which case the tests run against SQLite.
+fn server_url() -> Option<&'static str> {
+    static URL: OnceLock<Option<String>> = OnceLock::new();
+
+    URL.get_or_init(|| {
+        std::env::var("DATABASE_URL")
+            .ok()
+            .or_else(|| match start_cluster() {
+                Ok(url) => Some(url),
+                Err(err) => {
+                    eprintln!("PostgreSQL is not available, testing SQLite instead: {err}");
+                    None
+                }
+            })
+    })
+    .as_deref()
+}
+
+/// Initializes a cluster in a temporary directory and starts it on a free port. The server
+/// stops and its directory is removed once the test process exits. PostgreSQL refuses to run
+/// as root, so it is started as the `postgres` user in that case.
+fn start_cluster() -> Result<String, String> {
+    let dir: PathBuf =
+        std::env::temp_dir().join(format!("infrastructure-db-{}", std::process::id()));
+    let port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map_err(|err| err.to_string())?
+        .port();
+    let dir = dir.display();
+
+    let script = format!(
+        "initdb -D {dir} -U postgres --auth=trust > {dir}.log 2>&1 || exit 1
+        postgres -D {dir} -p {port} -k {dir} -h 127.0.0.1 -F >> {dir}.log 2>&1 &
+        server=$!
+        while [ -d /proc/{parent} ]; do sleep 1; done
+        kill -INT $server; wait $server; rm -rf {dir} {dir}.log",
+        parent = std::process::id(),
+    );
+
+    let is_root = Command::new("id")
+        .arg("-u")
+        .output()
+        .is_ok_and(|output| output.stdout.starts_with(b"0\n"));
+    let mut command = if is_root {
+        let mut command = Command::new("runuser");
+        command.args(["-u", "postgres", "--", "sh", "-c", &script]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", &script]);
+        command
+    };
+    let mut cluster = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| err.to_string())?;
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while Instant::now() < deadline {
+        if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+            return Ok(format!("postgres://postgres@127.0.0.1:{port}"));
+        }
+        if let Ok(Some(status)) = cluster.try_wait() {
+            return Err(format!("initdb failed ({status})"));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    Err(format!("server did not start, see {dir}.log"))
+}
+
+fn settings(url: &str) -> Settings {
+    Settings {
+        url: url.to_string(),
+        max_connections: 5,
+        min_connections: 0,
+        acquire_timeout: Duration::from_secs(5),
+        idle_timeout: None,
+        slow_statement_threshold: Duration::from_secs(1),
+    }
+}
+
+/// A migrated database of its own, so tests do not see each other's rows.
+enum TestDatabase {
+    Postgres(Database),
+    Sqlite {
+        database: SqliteDatabase,
+        _dir: TempDir,
+    },
+}
+
+impl TestDatabase {
+    async fn new(registry: &mut Registry) -> TestDatabase {
+        let Some(server) = server_url() else {
+            let dir = tempfile::tempdir().unwrap();
+            let database = SqliteDatabase::connect(
+                &sqlite::Settings {
+                    path: dir.path().join("test.db"),
+                    readers: 4,
+                    busy_timeout: Duration::from_secs(5),
+                    slow_statement_threshold: Duration::from_secs(1),
+                },
+                registry,
+            )
+            .await
+            .unwrap();
+            database.migrate().await.expect("failed to migrate");
+            return TestDatabase::Sqlite {
+                database,
+                _dir: dir,
+            };
+        };
+
+        let name = format!("test_{}", uuid::Uuid::now_v7().simple());
+        let admin = Database::connect(
+            &settings(&format!("{server}/postgres")),
+            &mut Registry::default(),
+        )
+        .unwrap();
+        sqlx::query(&format!("CREATE DATABASE {name}"))
+            .execute(admin.pool())
+            .await
+            .expect("failed to create the test database");
+        admin.close().await;
+
+        let database = Database::connect(&settings(&format!("{server}/{name}")), registry).unwrap();
+        database.migrate().await.expect("failed to migrate");
+        TestDatabase::Postgres(database)
+    }
+
+    fn messages(&self) -> Box<dyn MessageRepository> {
+        match self {
+            TestDatabase::Postgres(database) => Box::new(database.messages()),
+            TestDatabase::Sqlite { database, .. } => Box::new(database.messages()),
+        }
+    }
+
+    fn outbox(&self) -> Box<dyn Outbox> {
+        match self {
+            TestDatabase::Postgres(database) => Box::new(database.outbox()),
+            TestDatabase::Sqlite { database, .. } => Box::new(database.outbox()),
+        }
+    }
+
+    fn idempotency(&self) -> Box<dyn IdempotencyStore> {
+        match self {
+            TestDatabase::Postgres(database) => Box::new(database.idempotency()),
+            TestDatabase::Sqlite { database, .. } => Box::new(database.idempotency()),
+        }
+    }
+
+    fn leases(&self) -> Box<dyn LeaseStore> {
+        match self {
+            TestDatabase::Postgres(database) => Box::new(database.leases()),
+            TestDatabase::Sqlite { database, .. } => Box::new(database.leases()),
+        }
+    }
+
+    fn health(&self) -> &dyn HealthCheck {
+        match self {
+            TestDatabase::Postgres(database) => database,
+            TestDatabase::Sqlite { database, .. } => database,
+        }
+    }
+
+    async fn migrate(&self) -> eyre::Result<()> {
+        match self {
+            TestDatabase::Postgres(database) => database.migrate().await,
+            TestDatabase::Sqlite { database, .. } => database.migrate().await,
+        }
+    }
+
+    async fn applied_migrations(&self) -> i64 {
+        const QUERY: &str = "SELECT COUNT(*) FROM _sqlx_migrations";
+        match self {
+            TestDatabase::Postgres(database) => {
+                sqlx::query_scalar(QUERY).fetch_one(database.pool()).await
+            }
+            TestDatabase::Sqlite { database, .. } => {
+                sqlx::query_scalar(QUERY)
+                    .fetch_one(database.readers())
+                    .await
+            }
+        }
+        .unwrap()
+    }
+}
+
+fn record(id: &str) -> MessageRecord {
+    MessageRecord {
+        id: id.to_string(),
+        caller: Some("alice".to_string()),
+        message: "ping".to_string(),
+        reply: "pong".to_string(),
+        locale: "en".to_string(),
+        known: true,
+        trace_id: None,
+        created_at: SystemTime::UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+    }
+}
+
+#[tokio::test]
+async fn messages_are_saved_and_loaded() {
+    let database = TestDatabase::new(&mut Registry::default()).await;
+    let messages = database.messages();
+
+    messages.save(&record("1"), &[]).await.unwrap();
+    messages
+        .save(
+            &MessageRecord {
+                caller: None,
+                known: false,
+                ..record("2")
+            },
+            &[],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(Some(record("1")), messages.get("1").await.unwrap());
+    assert_eq!(None, messages.get("2").await.unwrap().unwrap().caller);
+    assert_eq!(None, messages.get("3").await.unwrap());
+    assert!(messages.save(&record("1"), &[]).await.is_err());
+}
+
+fn filter(limit: usize) -> HistoryFilter {
+    HistoryFilter {
+        caller: None,
+        from: None,
+        to: None,
+        after: None,
+        limit,
+    }
+}
+
+#[tokio::test]
+async fn history_is_listed_by_page_and_filtered() {
+    let database = TestDatabase::new(&mut Registry::default()).await;
+    let messages = database.messages();
+    let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+
+    for (id, caller, secs) in [
+        ("a", "alice", 10),
+        ("b", "bob", 20),
+        ("c", "alice", 20),
+        ("d", "alice", 30),
+    ] {
+        messages
+            .save(
+                &MessageRecord {
+                    caller: Some(caller.to_string()),
+                    created_at: at(secs),
+                    ..record(id)
+                },
+                &[],
+            )
+            .await
+            .unwrap();
+    }
+    let ids = |records: Vec<MessageRecord>| -> Vec<String> {
+        records.into_iter().map(|record| record.id).collect()
+    };
+
+    assert_eq!(
+        vec!["d", "c", "b", "a"],
+        ids(messages.list(&filter(10)).await.unwrap())
+    );
+    assert_eq!(
+        vec!["b", "a"],
+        ids(messages
+            .list(&HistoryFilter {
+                after: Some(HistoryCursor {
+                    created_at: at(20),
+                    id: "c".to_string(),
+                }),
+                ..filter(10)
+            })
+            .await
+            .unwrap())
+    );
+    assert_eq!(
+        vec!["d", "c"],
+        ids(messages
+            .list(&HistoryFilter {
+                caller: Some("alice".to_string()),
+                from: Some(at(20)),
+                ..filter(10)
+            })
+            .await
+            .unwrap())
+    );
+    assert_eq!(
+        vec!["c", "b"],
+        ids(messages
+            .list(&HistoryFilter {
+                to: Some(at(30)),
+                ..filter(2)
+            })
+            .await
+            .unwrap())
+    );
+}
+
+#[tokio::test]
+async fn history_older_than_cutoff_is_deleted() {
+    let database = TestDatabase::new(&mut Registry::default()).await;
+    let messages = database.messages();
+    let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+
+    for (id, secs) in [("a", 10), ("b", 20), ("c", 30)] {
+        messages
+            .save(
+                &MessageRecord {
+                    created_at: at(secs),
+                    ..record(id)
+                },
+                &[],
+            )
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(2, messages.delete_before(at(30)).await.unwrap());
+    assert_eq!(0, messages.delete_before(at(30)).await.unwrap());
+    assert_eq!(1, messages.list(&filter(10)).await.unwrap().len());
+}
+
+fn event(id: &str, record: MessageRecord) -> OutboxEvent {
+    OutboxEvent {
+        id: id.to_string(),
+        created_at: record.created_at,
+        event: DomainEvent::MessageReplied(record),
+        attempts: 0,
+    }
+}
+
+#[tokio::test]
+async fn events_are_saved_with_their_message() {
+    let database = TestDatabase::new(&mut Registry::default()).await;
+    let (messages, outbox) = (database.messages(), database.outbox());
+
+    messages
+        .save(&record("1"), &[event("e1", record("1"))])
+        .await
+        .unwrap();
+    // the message already exists, so its event is rolled back too
+    messages
+        .save(&record("1"), &[event("e2", record("1"))])
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        OutboxBacklog {
+            pending: 1,
+            oldest: Some(record("1").created_at),
+        },
+        outbox.backlog().await.unwrap()
+    );
+    let far = SystemTime::now() + Duration::from_secs(3600);
+    assert_eq!(
+        vec![event("e1", record("1"))],
+        outbox.lease(far, far, 10).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn outbox_events_are_leased_retried_and_delivered() {
+    let database = TestDatabase::new(&mut Registry::default()).await;
+    let (messages, outbox) = (database.messages(), database.outbox());
+    let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+
+    for (id, secs) in [("1", 10), ("2", 20), ("3", 30)] {
+        let record = MessageRecord {
+            created_at: at(secs),
+            ..record(id)
+        };
+        messages
+            .save(&record, &[event(&format!("e{id}"), record.clone())])
+            .await
+            .unwrap();
+    }
+    let ids = |events: Vec<OutboxEvent>| -> Vec<String> {
+        events.into_iter().map(|event| event.id).collect()
+    };
+
+    assert_eq!(
+        vec!["e1", "e2"],
+        ids(outbox.lease(at(25), at(100), 10).await.unwrap())
+    );
+    // leased events are not handed out again until the lease ends
+    assert_eq!(
+        vec!["e3"],
+        ids(outbox.lease(at(40), at(100), 10).await.unwrap())
+    );
+    assert!(outbox.lease(at(50), at(100), 10).await.unwrap().is_empty());
+
+    outbox.delivered("e1").await.unwrap();
+    outbox
+        .failed("e2", "broker unavailable", at(60))
+        .await
+        .unwrap();
+
+    let retried = outbox.lease(at(60), at(200), 10).await.unwrap();
+    assert_eq!(vec!["e2"], ids(retried.clone()));
+    assert_eq!(1, retried[0].attempts);
+    assert_eq!(
+        vec!["e3"],
+        ids(outbox.lease(at(100), at(200), 10).await.unwrap())
+    );
+    assert_eq!(2, outbox.backlog().await.unwrap().pending);
+}
+
+fn idempotency_key(key: &str) -> IdempotencyKey {
+    IdempotencyKey {
+        caller: "alice".to_string(),
+        key: key.to_string(),
+    }
+}
+
+fn stored_response() -> StoredResponse {
+    StoredResponse {
+        status: 200,
+        headers: vec![("content-type".to_string(), "application/json".to_string())],
+        body: vec![0, 159, 146, 150],
+    }
+}
+
+#[tokio::test]
+async fn idempotent_responses_are_claimed_once_and_replayed() {
+    let database = TestDatabase::new(&mut Registry::default()).await;
+    let store = database.idempotency();
+    let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+    let key = idempotency_key("order-1");
+
+    assert_eq!(
+        IdempotencyClaim::Claimed,
+        store.claim(&key, "abc", at(0), at(60)).await.unwrap()
+    );
+    assert_eq!(
+        IdempotencyClaim::InFlight,
+        store.claim(&key, "abc", at(1), at(61)).await.unwrap()
+    );
+    assert_eq!(
+        IdempotencyClaim::Mismatch,
+        store.claim(&key, "def", at(1), at(61)).await.unwrap()
+    );
+
+    store
+        .complete(&key, &stored_response(), at(60))
+        .await
+        .unwrap();
+    store.release(&key).await.unwrap();
+
+    assert_eq!(
+        IdempotencyClaim::Completed(stored_response()),
+        store.claim(&key, "abc", at(2), at(62)).await.unwrap()
+    );
+    assert_eq!(
+        IdempotencyClaim::Claimed,
+        store
+            .claim(&idempotency_key("order-2"), "abc", at(2), at(62))
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn expired_or_released_idempotency_keys_are_free() {
+    let database = TestDatabase::new(&mut Registry::default()).await;
+    let store = database.idempotency();
+    let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+
+    let released = idempotency_key("released");
+    store.claim(&released, "abc", at(0), at(60)).await.unwrap();
+    store.release(&released).await.unwrap();
+    assert_eq!(
+        IdempotencyClaim::Claimed,
+        store.claim(&released, "def", at(1), at(61)).await.unwrap()
+    );
+
+    let expired = idempotency_key("expired");
+    store.claim(&expired, "abc", at(0), at(60)).await.unwrap();
+    store
+        .complete(&expired, &stored_response(), at(60))
+        .await
+        .unwrap();
+    assert_eq!(
+        IdempotencyClaim::Claimed,
+        store.claim(&expired, "def", at(60), at(120)).await.unwrap()
+    );
+
+    assert_eq!(0, store.purge_expired(at(60)).await.unwrap());
+    assert_eq!(2, store.purge_expired(at(200)).await.unwrap());
+}
+
+#[tokio::test]
+async fn leases_are_held_by_one_instance_until_released_or_expired() {
+    let database = TestDatabase::new(&mut Registry::default()).await;
+    let leases = database.leases();
+    let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+
+    assert!(leases.acquire("job", "a", at(0), at(30)).await.unwrap());
+    assert!(!leases.acquire("job", "b", at(10), at(40)).await.unwrap());
+    // renewed by its holder
+    assert!(leases.acquire("job", "a", at(20), at(50)).await.unwrap());
+    assert!(!leases.acquire("job", "b", at(40), at(70)).await.unwrap());
+    assert_eq!(
+        Some("a".to_string()),
+        leases.holder("job", at(40)).await.unwrap()
+    );
+
+    // taken over once expired
+    assert_eq!(None, leases.holder("job", at(50)).await.unwrap());
+    assert!(leases.acquire("job", "b", at(50), at(80)).await.unwrap());
+    assert_eq!(
+        Some("b".to_string()),
+        leases.holder("job", at(60)).await.unwrap()
+    );
+
+    // only released by its holder
+    leases.release("job", "a").await.unwrap();
+    assert!(!leases.acquire("job", "a", at(60), at(90)).await.unwrap());
+    leases.release("job", "b").await.unwrap();
+    assert_eq!(None, leases.holder("job", at(60)).await.unwrap());
+    assert!(leases.acquire("job", "a", at(60), at(90)).await.unwrap());
+}
+
+#[tokio::test]
+async fn migrations_are_applied_once() {
+    let database = TestDatabase::new(&mut Registry::default()).await;
+
+    database.migrate().await.unwrap();
+
+    assert_eq!(
+        infrastructure::db::MIGRATOR.iter().count() as i64,
+        database.applied_migrations().await
+    );
+}
+
+#[tokio::test]
+async fn health_check_passes_when_database_is_reachable() {
+    let database = TestDatabase::new(&mut Registry::default()).await;
+
+    assert_eq!("database", database.health().name());
+    database.health().check().await.unwrap();
+}
+
+#[tokio::test]
+async fn health_check_fails_when_database_is_unreachable() {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let database = Database::connect(
+        &Settings {
+            acquire_timeout: Duration::from_secs(1),
+            ..settings(&format!("postgres://postgres@127.0.0.1:{port}/app"))
+        },
+        &mut Registry::default(),
+    )
+    .unwrap();
+
+    let err = database.check().await.unwrap_err();
+
+    assert!(err.to_string().starts_with("database is unavailable"));
+}
+
+#[tokio::test]
+async fn pool_and_queries_are_measured() {
+    let mut registry = Registry::default();
+    let database = TestDatabase::new(&mut registry).await;
+
+    database.messages().save(&record("1"), &[]).await.unwrap();
+    database
+        .messages()
+        .save(&record("1"), &[])
+        .await
+        .unwrap_err();
+
+    let mut metrics = String::new();
+    encode(&mut metrics, &registry).unwrap();
+
+    let pool = match database {
+        TestDatabase::Postgres(_) => "main",
+        TestDatabase::Sqlite { .. } => "writer",
+    };
+    for state in ["idle", "in_use"] {
+        assert!(metrics.contains(&format!(
+            "db_pool_connections{{pool=\"{pool}\",state=\"{state}\"}}"
+        )));
+    }
+    assert!(metrics.contains(&format!("db_pool_max_connections{{pool=\"{pool}\"}}")));
+    assert!(metrics.contains("db_query_duration_ms_count{query=\"messages.save\",status=\"ok\"} 1"));
+    assert!(
+        metrics.contains("db_query_duration_ms_count{query=\"messages.save\",status=\"error\"} 1")
+    );
+}
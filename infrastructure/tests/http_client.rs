@@ -0,0 +1,319 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use application::{outbox::Backoff, ports::HealthCheck};
+use http_body_util::Full;
+use hyper::{body::Bytes, server::conn::http1, service::service_fn, Response};
+use hyper_util::rt::TokioIo;
+use infrastructure::{
+    cache::{Cache, CacheMetrics, CacheSettings, Eviction},
+    http_client::{HttpClient, HttpClientError, UpstreamSettings},
+    resilience::{BulkheadSettings, CircuitBreakerSettings, CircuitState},
+    telemetry,
+};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use prometheus_client::{encoding::text::encode, registry::Registry};
+use tracing::Instrument;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// What an upstream received: the path and query, and the headers of each request.
+type Received = Arc<Mutex<Vec<(String, http::HeaderMap)>>>;
+
+/// Serves `statuses` in turn, then the last one over and over, each after `delay`. Returns
+/// the base url of the server.
+async fn upstream(statuses: Vec<u16>, delay: Duration) -> (String, Received) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let received = Received::default();
+
+    let requests = received.clone();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (requests, statuses) = (requests.clone(), statuses.clone());
+            tokio::spawn(async move {
+                let service = service_fn(move |req: http::Request<_>| {
+                    let status = {
+                        let mut requests = requests.lock().unwrap();
+                        let uri = req.uri().path_and_query().unwrap().to_string();
+                        requests.push((uri, req.headers().clone()));
+                        statuses[(requests.len() - 1).min(statuses.len() - 1)]
+                    };
+                    async move {
+                        tokio::time::sleep(delay).await;
+                        let mut response = Response::new(Full::new(Bytes::from("ok")));
+                        *response.status_mut() = http::StatusCode::from_u16(status).unwrap();
+                        Ok::<_, Infallible>(response)
+                    }
+                });
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    (url, received)
+}
+
+fn settings(base_url: &str, max_attempts: u32, timeout: Duration) -> UpstreamSettings {
+    UpstreamSettings {
+        base_url: base_url.to_string(),
+        timeout,
+        deadline: Duration::from_secs(5),
+        max_attempts,
+        backoff: Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(4),
+        },
+        circuit_breaker: CircuitBreakerSettings {
+            failure_threshold: 100,
+            open_duration: Duration::from_secs(60),
+            half_open_calls: 1,
+        },
+        bulkhead: BulkheadSettings {
+            max_concurrent_calls: 16,
+            max_wait: Duration::ZERO,
+        },
+    }
+}
+
+fn client(upstream: UpstreamSettings, registry: &mut Registry) -> HttpClient {
+    HttpClient::new(HashMap::from([("billing".to_string(), upstream)]), registry).unwrap()
+}
+
+fn metrics(registry: &Registry) -> String {
+    let mut metrics = String::new();
+    encode(&mut metrics, registry).unwrap();
+    metrics
+}
+
+#[tokio::test]
+async fn requests_carry_the_trace_context_and_are_measured() {
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    let provider = SdkTracerProvider::builder().build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    let _subscriber = tracing::subscriber::set_default(subscriber);
+
+    let (url, received) = upstream(vec![200], Duration::ZERO).await;
+    let mut registry = Registry::default();
+    let client = client(
+        settings(&format!("{url}/api/"), 1, Duration::from_secs(1)),
+        &mut registry,
+    );
+
+    let span = tracing::info_span!("handler");
+    let response = client
+        .get("billing", "/v1/invoices")
+        .unwrap()
+        .query(&[("page", "1")])
+        .send()
+        .instrument(span.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(200, response.status().as_u16());
+    let received = received.lock().unwrap();
+    let (uri, headers) = &received[0];
+    assert_eq!("/api/v1/invoices?page=1", uri);
+    let trace_id = telemetry::trace_id(&span).expect("the span is traced");
+    assert!(headers["traceparent"]
+        .to_str()
+        .unwrap()
+        .starts_with(&format!("00-{trace_id}-")));
+    assert!(metrics(&registry).contains(
+        "http_client_request_duration_ms_count{upstream=\"billing\",method=\"GET\",status=\"200\"} 1"
+    ));
+}
+
+#[tokio::test]
+async fn failed_idempotent_requests_are_retried() {
+    let (url, received) = upstream(vec![503, 502, 200], Duration::ZERO).await;
+    let mut registry = Registry::default();
+    let client = client(settings(&url, 3, Duration::from_secs(1)), &mut registry);
+
+    let response = client.get("billing", "/").unwrap().send().await.unwrap();
+
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(3, received.lock().unwrap().len());
+    let metrics = metrics(&registry);
+    assert!(metrics.contains("http_client_retries_total{upstream=\"billing\"} 2"));
+    assert!(metrics.contains(
+        "http_client_request_duration_ms_count{upstream=\"billing\",method=\"GET\",status=\"503\"} 1"
+    ));
+}
+
+#[tokio::test]
+async fn other_requests_are_sent_once() {
+    let (url, received) = upstream(vec![503, 200], Duration::ZERO).await;
+    let client = client(
+        settings(&url, 3, Duration::from_secs(1)),
+        &mut Registry::default(),
+    );
+
+    let response = client
+        .post("billing", "/v1/invoices")
+        .unwrap()
+        .json(&serde_json::json!({ "amount": 1 }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(503, response.status().as_u16());
+    assert_eq!(1, received.lock().unwrap().len());
+    assert!(matches!(
+        client.get("payments", "/"),
+        Err(HttpClientError::UnknownUpstream(_))
+    ));
+}
+
+#[tokio::test]
+async fn the_deadline_bounds_every_attempt() {
+    let (url, received) = upstream(vec![200], Duration::from_millis(500)).await;
+    let client = client(
+        UpstreamSettings {
+            deadline: Duration::from_millis(300),
+            ..settings(&url, 5, Duration::from_millis(200))
+        },
+        &mut Registry::default(),
+    );
+
+    let started = Instant::now();
+    let err = client
+        .get("billing", "/")
+        .unwrap()
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(
+        matches!(err, HttpClientError::DeadlineExceeded { .. }),
+        "{err}"
+    );
+    assert!(started.elapsed() < Duration::from_millis(450));
+    // a 200ms attempt timed out, then the second one ran out of budget
+    assert_eq!(2, received.lock().unwrap().len());
+}
+
+#[tokio::test]
+async fn failing_upstreams_are_cut_off_until_a_trial_succeeds() {
+    let (url, received) = upstream(vec![500, 500, 200], Duration::ZERO).await;
+    let mut registry = Registry::default();
+    let client = client(
+        UpstreamSettings {
+            circuit_breaker: CircuitBreakerSettings {
+                failure_threshold: 2,
+                open_duration: Duration::from_millis(100),
+                half_open_calls: 1,
+            },
+            ..settings(&url, 1, Duration::from_secs(1))
+        },
+        &mut registry,
+    );
+    let get = || async { client.get("billing", "/").unwrap().send().await };
+
+    assert_eq!(500, get().await.unwrap().status().as_u16());
+    assert_eq!(500, get().await.unwrap().status().as_u16());
+    assert_eq!(Some(CircuitState::Open), client.circuit("billing"));
+    assert!(matches!(
+        get().await,
+        Err(HttpClientError::CircuitOpen { .. })
+    ));
+    assert_eq!(2, received.lock().unwrap().len());
+    // readiness reports the open circuit without failing
+    assert!(client.check().await.is_ok());
+    assert_eq!(
+        Some("open"),
+        client.details().await.get("billing").map(String::as_str)
+    );
+    assert!(metrics(&registry)
+        .contains("circuit_breaker_state{dependency=\"billing\",state=\"open\"} 1"));
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(200, get().await.unwrap().status().as_u16());
+    assert_eq!(Some(CircuitState::Closed), client.circuit("billing"));
+    let metrics = metrics(&registry);
+    assert!(metrics.contains("circuit_breaker_state{dependency=\"billing\",state=\"closed\"} 1"));
+    for state in ["open", "half_open", "closed"] {
+        assert!(metrics.contains(&format!(
+            "circuit_breaker_transitions_total{{dependency=\"billing\",state=\"{state}\"}} 1"
+        )));
+    }
+}
+
+#[tokio::test]
+async fn the_bulkhead_bounds_concurrent_requests() {
+    let (url, received) = upstream(vec![200], Duration::from_millis(200)).await;
+    let mut registry = Registry::default();
+    let client = client(
+        UpstreamSettings {
+            bulkhead: BulkheadSettings {
+                max_concurrent_calls: 2,
+                max_wait: Duration::from_millis(50),
+            },
+            ..settings(&url, 1, Duration::from_secs(1))
+        },
+        &mut registry,
+    );
+    let get = || async { client.get("billing", "/").unwrap().send().await };
+
+    let (first, second, third) = tokio::join!(get(), get(), get());
+
+    assert_eq!(200, first.unwrap().status().as_u16());
+    assert_eq!(200, second.unwrap().status().as_u16());
+    assert!(matches!(third, Err(HttpClientError::BulkheadFull { .. })));
+    assert_eq!(2, received.lock().unwrap().len());
+    let metrics = metrics(&registry);
+    assert!(metrics.contains("bulkhead_rejections_total{dependency=\"billing\"} 1"));
+    assert!(metrics.contains("bulkhead_in_flight_calls{dependency=\"billing\"} 0"));
+}
+
+#[tokio::test]
+async fn cached_responses_are_fetched_once() {
+    let (url, received) = upstream(vec![200, 200, 404], Duration::from_millis(20)).await;
+    let mut registry = Registry::default();
+    let responses = Arc::new(Cache::new(
+        "upstream_responses",
+        CacheSettings {
+            max_entries: 8,
+            ttl: Duration::from_secs(60),
+            stale_while_revalidate: Duration::ZERO,
+            eviction: Eviction::Lru,
+        },
+        CacheMetrics::new(&mut registry),
+    ));
+    let client = Arc::new(
+        client(settings(&url, 1, Duration::from_secs(1)), &mut registry).with_cache(responses),
+    );
+
+    let (first, second) = tokio::join!(
+        client.get_cached("billing", "/v1/rates"),
+        client.get_cached("billing", "/v1/rates"),
+    );
+    assert_eq!("ok", first.unwrap().body);
+    assert_eq!("ok", second.unwrap().body);
+    assert_eq!(1, received.lock().unwrap().len());
+
+    client.get_cached("billing", "/v1/fees").await.unwrap();
+    let err = client
+        .get_cached("billing", "/v1/missing")
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(*err, HttpClientError::Status { status, .. } if status == 404),
+        "{err}"
+    );
+    assert_eq!(3, received.lock().unwrap().len());
+
+    client.get_cached("billing", "/v1/rates").await.unwrap();
+    assert_eq!(3, received.lock().unwrap().len());
+    let metrics = metrics(&registry);
+    assert!(metrics.contains("cache_hits_total{cache=\"upstream_responses\"} 1"));
+    assert!(metrics.contains("cache_misses_total{cache=\"upstream_responses\"} 4"));
+}
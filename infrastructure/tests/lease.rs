@@ -0,0 +1,97 @@
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use application::ports::HealthCheck;
+use infrastructure::{
+    lease::{FileLeaseStore, LeaseStore, Leases},
+    memory::InMemoryLeaseStore,
+};
+use prometheus_client::{encoding::text::encode, registry::Registry};
+
+#[tokio::test]
+async fn file_leases_are_held_until_released() {
+    let dir = tempfile::tempdir().unwrap();
+    // two stores on one directory stand for two processes of the host
+    let first = FileLeaseStore::new(dir.path()).unwrap();
+    let second = FileLeaseStore::new(dir.path()).unwrap();
+    let now = SystemTime::now();
+
+    assert_eq!(None, second.holder("job", now).await.unwrap());
+    assert!(first.acquire("job", "a", now, now).await.unwrap());
+    assert!(first.acquire("job", "a", now, now).await.unwrap());
+    assert!(!second.acquire("job", "b", now, now).await.unwrap());
+    // reading the holder does not lock the lease
+    let third = FileLeaseStore::new(dir.path()).unwrap();
+    assert_eq!(
+        Some("a".to_string()),
+        third.holder("job", now).await.unwrap()
+    );
+    first.release("job", "a").await.unwrap();
+    assert!(third.acquire("job", "c", now, now).await.unwrap());
+    third.release("job", "c").await.unwrap();
+    assert!(first.acquire("job", "a", now, now).await.unwrap());
+    assert_eq!(
+        Some("a".to_string()),
+        second.holder("job", now).await.unwrap()
+    );
+
+    first.release("job", "a").await.unwrap();
+    assert_eq!(None, second.holder("job", now).await.unwrap());
+    assert!(second.acquire("job", "b", now, now).await.unwrap());
+    assert_eq!(
+        Some("b".to_string()),
+        first.holder("job", now).await.unwrap()
+    );
+
+    // the lock goes away with its holder, whose name is read until the lease is taken again
+    drop(second);
+    assert_eq!(
+        Some("b".to_string()),
+        first.holder("job", now).await.unwrap()
+    );
+    assert!(first.acquire("job", "a", now, now).await.unwrap());
+    assert_eq!(
+        Some("a".to_string()),
+        first.holder("job", now).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn leases_report_their_holders() {
+    let store = Arc::new(InMemoryLeaseStore::default());
+    let mut registry = Registry::default();
+    let leases = Leases::new(store.clone(), "a", Duration::from_secs(30), &mut registry);
+    let other = Leases::new(
+        store,
+        "b",
+        Duration::from_secs(30),
+        &mut Registry::default(),
+    );
+
+    leases.register("retention");
+    assert!(leases.acquire("outbox.relay").await.unwrap());
+    assert!(!other.acquire("outbox.relay").await.unwrap());
+    assert!(other.acquire("retention").await.unwrap());
+
+    assert_eq!("leases", leases.name());
+    leases.check().await.unwrap();
+    assert_eq!(
+        BTreeMap::from([
+            ("instance".to_string(), "a".to_string()),
+            ("outbox.relay".to_string(), "a".to_string()),
+            ("retention".to_string(), "b".to_string()),
+        ]),
+        leases.details().await
+    );
+
+    let mut metrics = String::new();
+    encode(&mut metrics, &registry).unwrap();
+    assert!(metrics.contains("lease_held{lease=\"outbox.relay\"} 1"));
+    assert!(metrics.contains("lease_held{lease=\"retention\"} 0"));
+
+    leases.release("outbox.relay").await.unwrap();
+    assert_eq!("none", leases.details().await["outbox.relay"]);
+}
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use application::{
+    messages,
+    outbox::{Backoff, RelaySettings},
+    template::RequestMetadata,
+};
+use infrastructure::{memory, outbox::OutboxRelay};
+use prometheus_client::{encoding::text::encode, registry::Registry};
+
+#[tokio::test]
+async fn relay_publishes_the_outbox_and_measures_it() {
+    let mut registry = Registry::default();
+    let relay = OutboxRelay::new(
+        RelaySettings {
+            batch_size: 1,
+            lease: Duration::from_secs(30),
+            backoff: Backoff {
+                initial: Duration::from_secs(1),
+                max: Duration::from_secs(60),
+            },
+        },
+        &mut registry,
+    );
+    let context = memory::context();
+    let metadata = RequestMetadata {
+        caller: None,
+        trace_id: None,
+    };
+    for message in ["ping", "bye"] {
+        let result = messages::reply(&Default::default(), message, &Default::default(), &metadata);
+        messages::record(&context, message, &result, &metadata)
+            .await
+            .unwrap();
+    }
+    assert_eq!(2, context.outbox.backlog().await.unwrap().pending);
+
+    relay.run_once(&context).await.unwrap();
+
+    let mut metrics = String::new();
+    encode(&mut metrics, &registry).unwrap();
+
+    assert_eq!(0, context.outbox.backlog().await.unwrap().pending);
+    assert!(metrics.contains("outbox_published_events_total{event=\"message.replied\"} 1"));
+    assert!(metrics.contains("outbox_published_events_total{event=\"message.unknown\"} 1"));
+    assert!(metrics.contains("outbox_pending_events 0"));
+    assert!(metrics.contains("outbox_lag_ms 0"));
+}
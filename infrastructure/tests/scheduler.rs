@@ -0,0 +1,182 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use infrastructure::{
+    lease::{LeaseStore, Leases},
+    memory::InMemoryLeaseStore,
+    scheduler::{Job, Schedule, Scheduler},
+    shutdown::Shutdown,
+};
+use prometheus_client::{encoding::text::encode, registry::Registry};
+
+fn metrics(registry: &Registry) -> String {
+    let mut metrics = String::new();
+    encode(&mut metrics, registry).unwrap();
+    metrics
+}
+
+#[tokio::test]
+async fn interval_jobs_run_one_at_a_time_until_shutdown() {
+    let mut registry = Registry::default();
+    let mut scheduler = Scheduler::new(&mut registry);
+    let runs = Arc::new(AtomicUsize::new(0));
+    let running = Arc::new(AtomicUsize::new(0));
+    let overlapped = Arc::new(AtomicUsize::new(0));
+
+    let (counted, active, overlaps) = (runs.clone(), running.clone(), overlapped.clone());
+    scheduler.add(Job::new(
+        "tick",
+        Schedule::Every(Duration::from_millis(10)),
+        move || {
+            let (counted, active, overlaps) = (counted.clone(), active.clone(), overlaps.clone());
+            async move {
+                if active.fetch_add(1, Ordering::SeqCst) > 0 {
+                    overlaps.fetch_add(1, Ordering::SeqCst);
+                }
+                // outlasts the period
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+                counted.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        },
+    ));
+    let shutdown = Shutdown::new();
+    let scheduled = scheduler.spawn(shutdown.clone());
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    shutdown.trigger();
+    scheduled.await.unwrap();
+    let stopped_at = runs.load(Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(stopped_at >= 2, "ran {stopped_at} times");
+    assert_eq!(stopped_at, runs.load(Ordering::SeqCst));
+    assert_eq!(0, overlapped.load(Ordering::SeqCst));
+    assert!(metrics(&registry).contains(&format!(
+        "scheduler_job_runs_total{{job=\"tick\",status=\"success\"}} {stopped_at}"
+    )));
+}
+
+#[tokio::test]
+async fn failed_and_timed_out_runs_are_measured() {
+    let mut registry = Registry::default();
+    let mut scheduler = Scheduler::new(&mut registry);
+    scheduler.add(Job::new(
+        "failing",
+        Schedule::Every(Duration::from_secs(60)),
+        || async { Err(eyre::eyre!("unavailable")) },
+    ));
+    scheduler.add(
+        Job::new("slow", Schedule::Every(Duration::from_secs(60)), || async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        })
+        .timeout(Duration::from_millis(20)),
+    );
+    let shutdown = Shutdown::new();
+    let scheduled = scheduler.spawn(shutdown.clone());
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    shutdown.trigger();
+    scheduled.await.unwrap();
+
+    let metrics = metrics(&registry);
+    assert!(metrics.contains("scheduler_job_runs_total{job=\"failing\",status=\"failure\"} 1"));
+    assert!(metrics.contains("scheduler_job_runs_total{job=\"slow\",status=\"timeout\"} 1"));
+    assert!(metrics.contains("scheduler_job_duration_ms_count{job=\"slow\",status=\"timeout\"} 1"));
+}
+
+#[tokio::test]
+async fn jitter_delays_runs() {
+    let mut scheduler = Scheduler::new(&mut Registry::default());
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counted = runs.clone();
+    scheduler.add(
+        Job::new(
+            "jittered",
+            Schedule::Every(Duration::from_secs(60)),
+            move || {
+                counted.fetch_add(1, Ordering::SeqCst);
+                async { Ok(()) }
+            },
+        )
+        .jitter(Duration::from_secs(3600)),
+    );
+    let shutdown = Shutdown::new();
+    let scheduled = scheduler.spawn(shutdown.clone());
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    shutdown.trigger();
+    scheduled.await.unwrap();
+
+    // a run before the shutdown would need a jitter under 50ms out of an hour
+    assert_eq!(0, runs.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn singleton_jobs_run_on_one_instance() {
+    let store = Arc::new(InMemoryLeaseStore::default());
+    let runs = Arc::new(AtomicUsize::new(0));
+    let shutdown = Shutdown::new();
+
+    let mut instances = Vec::new();
+    for holder in ["a", "b", "c"] {
+        let leases = Leases::new(
+            store.clone(),
+            holder,
+            Duration::from_secs(30),
+            &mut Registry::default(),
+        );
+        let mut scheduler = Scheduler::new(&mut Registry::default()).with_leases(Arc::new(leases));
+        let counted = runs.clone();
+        scheduler.add(
+            Job::new(
+                "singleton",
+                Schedule::Every(Duration::from_secs(60)),
+                move || {
+                    counted.fetch_add(1, Ordering::SeqCst);
+                    async { Ok(()) }
+                },
+            )
+            .singleton(),
+        );
+        instances.push(scheduler.spawn(shutdown.clone()));
+    }
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let holder = store.holder("singleton", SystemTime::now()).await.unwrap();
+    shutdown.trigger();
+    for scheduled in instances {
+        scheduled.await.unwrap();
+    }
+
+    assert_eq!(1, runs.load(Ordering::SeqCst));
+    assert!(holder.is_some());
+    // released on shutdown
+    assert_eq!(
+        None,
+        store.holder("singleton", SystemTime::now()).await.unwrap()
+    );
+}
+
+#[test]
+fn cron_schedules_run_at_the_matching_times() {
+    let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+    // every day at 03:00 UTC
+    let schedule = Schedule::cron("0 0 3 * * *").unwrap();
+
+    // 2024-01-01T04:00:00Z
+    let after = at(1_704_081_600);
+    assert_eq!(Some(at(1_704_164_400)), schedule.next_after(after));
+    assert_eq!(
+        Some(after + Duration::from_secs(90)),
+        Schedule::Every(Duration::from_secs(90)).next_after(after)
+    );
+    assert!(Schedule::cron("every day").is_err());
+}
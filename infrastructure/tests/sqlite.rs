@@ -0,0 +1,135 @@
+use std::time::{Duration, SystemTime};
+
+use application::ports::{MessageRecord, MessageRepository};
+use infrastructure::db::{
+    sqlite::{Settings, SqliteDatabase},
+    MIGRATOR,
+};
+use prometheus_client::{encoding::text::encode, registry::Registry};
+use tempfile::TempDir;
+
+async fn database(registry: &mut Registry) -> (SqliteDatabase, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let database = SqliteDatabase::connect(
+        &Settings {
+            path: dir.path().join("app.db"),
+            readers: 2,
+            busy_timeout: Duration::from_secs(5),
+            slow_statement_threshold: Duration::from_secs(1),
+        },
+        registry,
+    )
+    .await
+    .unwrap();
+    database.migrate().await.unwrap();
+
+    (database, dir)
+}
+
+fn record(id: &str) -> MessageRecord {
+    MessageRecord {
+        id: id.to_string(),
+        caller: None,
+        message: "ping".to_string(),
+        reply: "pong".to_string(),
+        locale: "en".to_string(),
+        known: true,
+        trace_id: None,
+        created_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+    }
+}
+
+#[tokio::test]
+async fn database_file_is_created_in_wal_mode() {
+    let (database, dir) = database(&mut Registry::default()).await;
+
+    assert!(dir.path().join("app.db").exists());
+    for pool in [database.writer(), database.readers()] {
+        let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!("wal", mode);
+    }
+}
+
+#[tokio::test]
+async fn readers_cannot_write() {
+    let (database, _dir) = database(&mut Registry::default()).await;
+
+    let err = sqlx::query("DELETE FROM messages")
+        .execute(database.readers())
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("readonly"));
+}
+
+#[tokio::test]
+async fn readers_are_not_blocked_by_an_open_write() {
+    let (database, _dir) = database(&mut Registry::default()).await;
+    let messages = database.messages();
+    messages.save(&record("1"), &[]).await.unwrap();
+
+    let mut write = database.writer().begin().await.unwrap();
+    sqlx::query("DELETE FROM messages")
+        .execute(&mut *write)
+        .await
+        .unwrap();
+
+    let read = tokio::time::timeout(Duration::from_secs(1), messages.get("1"))
+        .await
+        .expect("read blocked by the writer");
+    assert_eq!(Some(record("1")), read.unwrap());
+
+    write.commit().await.unwrap();
+    assert_eq!(None, messages.get("1").await.unwrap());
+}
+
+#[tokio::test]
+async fn reopening_keeps_messages_and_migrations() {
+    let dir = tempfile::tempdir().unwrap();
+    let settings = Settings {
+        path: dir.path().join("app.db"),
+        readers: 1,
+        busy_timeout: Duration::from_secs(5),
+        slow_statement_threshold: Duration::from_secs(1),
+    };
+
+    let database = SqliteDatabase::connect(&settings, &mut Registry::default())
+        .await
+        .unwrap();
+    database.migrate().await.unwrap();
+    database.messages().save(&record("1"), &[]).await.unwrap();
+    database.close().await;
+
+    let database = SqliteDatabase::connect(&settings, &mut Registry::default())
+        .await
+        .unwrap();
+    database.migrate().await.unwrap();
+
+    let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM _sqlx_migrations")
+        .fetch_one(database.readers())
+        .await
+        .unwrap();
+    assert_eq!(MIGRATOR.iter().count() as i64, applied);
+    assert_eq!(
+        Some(record("1")),
+        database.messages().get("1").await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn writer_and_reader_pools_are_measured() {
+    let mut registry = Registry::default();
+    let (database, _dir) = database(&mut registry).await;
+    database.messages().get("1").await.unwrap();
+
+    let mut metrics = String::new();
+    encode(&mut metrics, &registry).unwrap();
+
+    assert!(metrics.contains("db_pool_max_connections{pool=\"writer\"} 1"));
+    assert!(metrics.contains("db_pool_max_connections{pool=\"reader\"} 2"));
+    assert!(metrics.contains("db_pool_connections{pool=\"writer\",state=\"idle\"} 1"));
+    assert!(metrics.contains("db_query_duration_ms_count{query=\"messages.get\",status=\"ok\"} 1"));
+}